@@ -2,8 +2,9 @@ use cosmic::{
     app::{Core, Task},
     executor,
     iced_widget::text_input,
+    prelude::CollectionWidget,
     widget::{
-        self, button,
+        self, button, column,
         segmented_button::{Entity, SingleSelectModel},
         text,
     },
@@ -50,6 +51,26 @@ impl App {
     fn close_dialog(&mut self) {
         self.dialog.take();
     }
+
+    fn check_dialog_key(
+        &self,
+        page_id: Entity,
+        data_path: &[DataPathType],
+        key: &str,
+        previous: Option<&str>,
+    ) -> Option<anyhow::Error> {
+        self.nav_model
+            .data::<Page>(page_id)
+            .and_then(|page| page.check_dialog_key(data_path, key, previous).err())
+    }
+}
+
+fn dialog_control<'a>(name: &'a str, error: Option<String>) -> Element<'a, AppMsg> {
+    column()
+        .push(text_input("name", name).on_input(AppMsg::DialogInput))
+        .push_maybe(error.map(text))
+        .spacing(10)
+        .into()
 }
 
 impl cosmic::Application for App {
@@ -182,36 +203,65 @@ impl cosmic::Application for App {
                 name,
                 data_path,
                 page_id,
-            } => widget::dialog("Create")
-                .control(text_input("name", name).on_input(AppMsg::DialogInput))
-                .primary_action(button::text("create").on_press(AppMsg::PageMsg(
-                    *page_id,
-                    PageMsg::ChangeMsg(
-                        data_path.clone(),
-                        ChangeMsg::AddNewNodeToObject(name.clone()),
-                    ),
-                )))
-                .secondary_action(button::text("cancel").on_press(AppMsg::CloseDialog))
-                .into(),
+            } => {
+                let error = self.check_dialog_key(*page_id, data_path, name, None);
+
+                widget::dialog("Create")
+                    .control(dialog_control(
+                        name,
+                        // don't nag before the user typed something
+                        error
+                            .as_ref()
+                            .filter(|_| !name.is_empty())
+                            .map(|e| e.to_string()),
+                    ))
+                    .primary_action(button::text("create").on_press_maybe(error.is_none().then(
+                        || {
+                            AppMsg::PageMsg(
+                                *page_id,
+                                PageMsg::ChangeMsg(
+                                    data_path.clone(),
+                                    ChangeMsg::AddNewNodeToObject(name.clone()),
+                                ),
+                            )
+                        },
+                    )))
+                    .secondary_action(button::text("cancel").on_press(AppMsg::CloseDialog))
+                    .into()
+            }
             Dialog::RenameKey {
                 previous,
                 name,
                 data_path,
                 page_id,
-            } => widget::dialog("Rename")
-                .control(text_input("name", name).on_input(AppMsg::DialogInput))
-                .primary_action(button::text("rename").on_press(AppMsg::PageMsg(
-                    *page_id,
-                    PageMsg::ChangeMsg(
-                        data_path.clone(),
-                        ChangeMsg::RenameKey {
-                            prev: previous.clone(),
-                            new: name.clone(),
+            } => {
+                let error = self.check_dialog_key(*page_id, data_path, name, Some(previous));
+
+                widget::dialog("Rename")
+                    .control(dialog_control(
+                        name,
+                        error
+                            .as_ref()
+                            .filter(|_| name != previous)
+                            .map(|e| e.to_string()),
+                    ))
+                    .primary_action(button::text("rename").on_press_maybe(error.is_none().then(
+                        || {
+                            AppMsg::PageMsg(
+                                *page_id,
+                                PageMsg::ChangeMsg(
+                                    data_path.clone(),
+                                    ChangeMsg::RenameKey {
+                                        prev: previous.clone(),
+                                        new: name.clone(),
+                                    },
+                                ),
+                            )
                         },
-                    ),
-                )))
-                .secondary_action(button::text("cancel").on_press(AppMsg::CloseDialog))
-                .into(),
+                    )))
+                    .secondary_action(button::text("cancel").on_press(AppMsg::CloseDialog))
+                    .into()
+            }
         })
     }
 
@@ -1,19 +1,47 @@
-use std::{collections::BTreeMap, fs, panic::catch_unwind, path::PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, fs,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
 
 use figment::{providers, Figment, Profile};
-use schemars::schema::Schema;
 use serde::Deserialize;
 
-use crate::node::{from_json_schema::schema_object_to_node, from_json_schema::ToSchemaObject};
+use crate::node::NodeContainer;
 
+/// Drafts that are checked, with the `$schema` used when a test schema doesn't define one
+const DRAFTS: &[(&str, &str)] = &[
+    ("draft7", "http://json-schema.org/draft-07/schema#"),
+    (
+        "draft2020-12",
+        "https://json-schema.org/draft/2020-12/schema",
+    ),
+];
+
+/// Can be overridden with the `JSON_SCHEMA_TEST_SUITE` env variable
 fn test_path() -> PathBuf {
-    PathBuf::from("../JSON-Schema-Test-Suite/tests/draft7")
+    match env::var_os("JSON_SCHEMA_TEST_SUITE") {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("../JSON-Schema-Test-Suite"),
+    }
+}
+
+/// Id of the test cases that passed at some point.
+/// Regenerate with `UPDATE_BASELINE=1 just test_suite`
+fn baseline_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("json_schema_test_suite_baseline.txt")
 }
 
+const BASELINE_HEADER: &str = "\
+# Test cases of the JSON-Schema-Test-Suite that pass, see json_schema_test_suite.rs
+# Regenerate with `UPDATE_BASELINE=1 just test_suite`
+";
+
 #[derive(Debug, Deserialize)]
 struct TestGroup {
     description: String,
-    schema: Schema,
+    schema: json::Value,
     tests: Vec<Test>,
 }
 
@@ -24,104 +52,213 @@ struct Test {
     valid: bool,
 }
 
-type Tests = Vec<TestGroup>;
+#[derive(Debug, Default)]
+struct Report {
+    /// (passed, total) by `draft/file`
+    files: BTreeMap<String, (usize, usize)>,
+    /// (passed, total) by keyword, e.g. the file name, for all drafts
+    keywords: BTreeMap<String, (usize, usize)>,
+    /// Id of the passed test cases
+    passed: BTreeSet<String>,
+    /// Files that can't be parsed
+    errors: Vec<String>,
+    panics: usize,
+}
 
-#[test]
-#[ignore = "always fails"]
-fn test_all_suite() {
-    let mut nb_of_error_deserialization = 0;
-    let mut nb_parsed_schema = 0;
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
 
-    let mut nb_parsed_test = 0;
+    for dir_entry in read_dir.flatten() {
+        let path = dir_entry.path();
 
-    let mut nb_of_passed_schema = 0;
-    let mut nb_of_passed_test = 0;
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else if path.extension().is_some_and(|e| e == "json") {
+            files.push(path);
+        }
+    }
+}
 
-    let mut tests = Vec::new();
+fn panic_message(err: &Box<dyn std::any::Any + Send>) -> String {
+    err.downcast_ref::<String>()
+        .cloned()
+        .or_else(|| err.downcast_ref::<&str>().map(|s| s.to_string()))
+        .unwrap_or_default()
+}
 
-    for dir_entry in fs::read_dir(test_path()).unwrap() {
-        let dir_entry = dir_entry.unwrap();
+/// Return true if the data is valid for this tree
+fn run_test(tree: &Option<NodeContainer>, data: &json::Value) -> bool {
+    match tree.clone() {
+        Some(mut tree) => {
+            let config = Figment::new().merge(providers::Serialized::from(data, Profile::Default));
 
-        let path = dir_entry.path();
+            tree.apply_figment(&config).is_ok() && tree.is_valid()
+        }
+        None => false,
+    }
+}
 
-        match dir_entry.metadata().unwrap().is_file() {
-            true => {
-                let content = fs::read_to_string(&path).unwrap();
+fn run_draft(report: &mut Report, draft: &str, schema_uri: &str) {
+    let draft_path = test_path().join("tests").join(draft);
 
-                match json::from_str::<Tests>(&content) {
-                    Ok(test_groups) => {
-                        nb_parsed_test += test_groups
-                            .iter()
-                            .map(|e| e.tests.len() as i32)
-                            .sum::<i32>();
+    let mut files = Vec::new();
+    collect_files(&draft_path, &mut files);
+    files.sort();
 
-                        nb_parsed_schema += test_groups.len();
-                        tests.push(test_groups);
-                    }
-                    Err(e) => {
-                        nb_of_error_deserialization += 1;
-                        eprintln!("err {e}: {}", path.display());
-                    }
-                }
-            }
-            false => {
-                // todo
+    for path in files {
+        let file = format!(
+            "{draft}/{}",
+            path.strip_prefix(&draft_path).unwrap().display()
+        );
+
+        let test_groups = match fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| Ok(json::from_str::<Vec<TestGroup>>(&content)?))
+        {
+            Ok(test_groups) => test_groups,
+            Err(e) => {
+                report.errors.push(format!("{file}: {e}"));
+                continue;
             }
-        }
-    }
+        };
+
+        let (mut passed, mut total) = (0, 0);
 
-    for test_groups in &tests {
         for test_group in test_groups {
-            let tree = match catch_unwind(|| {
-                schema_object_to_node(
-                    "test",
-                    &schemars::Map::new(),
-                    &test_group.schema.to_object(),
-                )
-            }) {
-                Ok(n) => {
-                    nb_of_passed_schema += 1;
-                    n
-                }
+            let mut schema = test_group.schema;
+
+            if let json::Value::Object(obj) = &mut schema {
+                obj.entry("$schema").or_insert(schema_uri.into());
+            }
+
+            let tree = match catch_unwind(AssertUnwindSafe(|| {
+                NodeContainer::from_json_value(schema, None).ok()
+            })) {
+                Ok(tree) => tree,
                 Err(err) => {
+                    eprintln!(
+                        "panic: {file}: {}: {}",
+                        test_group.description,
+                        panic_message(&err)
+                    );
+                    report.panics += 1;
+                    total += test_group.tests.len();
                     continue;
                 }
             };
 
             for test in &test_group.tests {
-                match catch_unwind(|| {
-                    let config = Figment::new()
-                        .merge(providers::Serialized::from(&test.data, Profile::Default));
-
-                    let tree = tree.clone();
-
-                    match tree {
-                        Some(mut tree) => {
-                            if tree.apply_figment(&config).is_err() {
-                                false
-                            } else {
-                                tree.is_valid()
-                            }
-                        }
-                        None => false,
-                    }
-                }) {
+                total += 1;
+
+                let id = format!("{file}::{}::{}", test_group.description, test.description);
+
+                match catch_unwind(AssertUnwindSafe(|| run_test(&tree, &test.data))) {
                     Ok(is_valid) => {
                         if is_valid == test.valid {
-                            nb_of_passed_test += 1;
+                            passed += 1;
+                            report.passed.insert(id);
                         }
                     }
                     Err(err) => {
-                        continue;
+                        eprintln!("panic: {id}: {}", panic_message(&err));
+                        report.panics += 1;
                     }
                 };
             }
         }
+
+        let keyword = path.file_stem().unwrap().to_string_lossy().to_string();
+        let entry = report.keywords.entry(keyword).or_default();
+        entry.0 += passed;
+        entry.1 += total;
+
+        report.files.insert(file, (passed, total));
+    }
+}
+
+#[test]
+#[ignore = "needs a clone of the JSON-Schema-Test-Suite, run it with `just test_suite`"]
+fn test_all_suite() {
+    assert!(
+        test_path().exists(),
+        "{} not found.\nclone https://github.com/json-schema-org/JSON-Schema-Test-Suite to run it",
+        test_path().display()
+    );
+
+    let mut report = Report::default();
+
+    for (draft, schema_uri) in DRAFTS {
+        run_draft(&mut report, draft, schema_uri);
+    }
+
+    for (file, (passed, total)) in &report.files {
+        println!("{passed:>4}/{total:<4} {file}");
+    }
+
+    println!();
+
+    for (keyword, (passed, total)) in &report.keywords {
+        println!("{passed:>4}/{total:<4} {keyword}");
+    }
+
+    for error in &report.errors {
+        println!("can't parse {error}");
+    }
+
+    let (passed, total) = report
+        .files
+        .values()
+        .fold((0, 0), |(p, t), (passed, total)| (p + passed, t + total));
+
+    println!("total: {passed}/{total}, panics: {}", report.panics);
+
+    if env::var_os("UPDATE_BASELINE").is_some() {
+        let mut content = String::from(BASELINE_HEADER);
+        for id in &report.passed {
+            content.push_str(id);
+            content.push('\n');
+        }
+        fs::write(baseline_path(), content).unwrap();
+        return;
+    }
+
+    let baseline = fs::read_to_string(baseline_path()).unwrap_or_default();
+    let baseline: BTreeSet<_> = baseline
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+
+    // an empty baseline would not catch any regression
+    assert!(
+        !baseline.is_empty(),
+        "the baseline is empty, generate it with `UPDATE_BASELINE=1 just test_suite`"
+    );
+
+    let regressions: Vec<_> = baseline
+        .iter()
+        .filter(|id| !report.passed.contains(**id))
+        .collect();
+
+    let improvements = report
+        .passed
+        .iter()
+        .filter(|id| !baseline.contains(id.as_str()))
+        .count();
+
+    if improvements > 0 {
+        println!("{improvements} new passing tests, update the baseline with `UPDATE_BASELINE=1`");
     }
 
-    println!("nb_of_error_deserialization: {nb_of_error_deserialization}");
-    println!("nb_parsed_schema: {nb_parsed_schema}");
-    println!("nb_parsed_test: {nb_parsed_test}");
-    println!("nb_of_passed_schema: {nb_of_passed_schema}");
-    println!("nb_of_passed_test: {nb_of_passed_test}");
+    assert!(
+        regressions.is_empty(),
+        "{} regressions:\n{}",
+        regressions.len(),
+        regressions
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
 }
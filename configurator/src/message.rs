@@ -15,6 +15,8 @@ pub enum AppMsg {
 pub enum PageMsg {
     SelectDataPath(Option<usize>),
     OpenDataPath(DataPathType),
+    /// Open this path, e.g. to show an invalid node
+    GoTo(Vec<DataPathType>),
     ChangeMsg(Vec<DataPathType>, ChangeMsg),
     DialogAddNewNodeToObject(Vec<DataPathType>),
     DialogRenameKey(Vec<DataPathType>, String),
@@ -24,12 +26,19 @@ pub enum PageMsg {
 #[derive(Clone, Debug)]
 pub enum ChangeMsg {
     ApplyDefault,
+    /// Remove the value from the user config, so the system value or the default is used
+    ResetToInherited,
     ChangeBool(bool),
     ChangeString(String),
     ChangeNumber(String),
+    /// Raw JSON or RON
+    ChangeAny(String),
     ChangeEnum(usize),
     Remove(DataPathType),
     AddNewNodeToObject(String),
     AddNewNodeToArray,
-    RenameKey { prev: String, new: String },
+    RenameKey {
+        prev: String,
+        new: String,
+    },
 }
@@ -0,0 +1,27 @@
+use anyhow::anyhow;
+use figment::value::Value;
+
+use super::{from_json_schema::json_value_to_figment_value, NodeAny};
+
+impl NodeAny {
+    pub fn new() -> Self {
+        Self {
+            value: None,
+            value_string: String::new(),
+        }
+    }
+
+    /// Parse the raw input of the user. JSON is tried first, then RON.
+    pub fn parse(input: &str) -> anyhow::Result<Value> {
+        match json::from_str::<json::Value>(input) {
+            Ok(value) => Ok(json_value_to_figment_value(&value)),
+            Err(json_err) => ron::from_str::<Value>(input)
+                .map_err(|ron_err| anyhow!("not valid JSON ({json_err}) nor RON ({ron_err})")),
+        }
+    }
+
+    /// Text shown in the editor
+    pub fn format(value: &Value) -> String {
+        json::to_string(value).unwrap_or_default()
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, iter};
 
 use anyhow::{anyhow, bail};
 use figment::{
@@ -7,50 +7,196 @@ use figment::{
 };
 use indexmap::map::MutableKeys;
 
-use crate::utils::{data_default_profile_figment, json_value_eq_figment_value};
+use crate::utils::{bool_from_str, data_default_profile_figment, json_value_eq_figment_value};
 
-use super::{Node, NodeContainer};
+use super::{data_path::DataPathType, EnumRepr, Node, NodeAny, NodeContainer, NodeError, Origin};
+
+/// Conversions accepted for the formats that can't express all the values of the schema.
+/// Other formats must have the exact type.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lenient {
+    /// Untyped formats, like INI, only have strings
+    pub string_to_scalar: bool,
+    /// Formats like KDL can't tell a list of one element from the element
+    pub value_to_array: bool,
+}
 
 impl NodeContainer {
-    // todo: use figment Value instead
+    /// Apply the values of `figment`, all of them are considered as user values
     pub fn apply_figment(&mut self, figment: &Figment) -> anyhow::Result<()> {
+        self.apply_layers(figment, &|_| Origin::User, Lenient::default())
+    }
+
+    /// Apply the values of `figment`. `origin_of` tells from which layer a value comes,
+    /// using the tag figment put on it
+    pub fn apply_layers(
+        &mut self,
+        figment: &Figment,
+        origin_of: &dyn Fn(Tag) -> Origin,
+        lenient: Lenient,
+    ) -> anyhow::Result<()> {
         match data_default_profile_figment(figment) {
-            Some(data) => self.apply_value(Value::Dict(Tag::Default, data), true),
-            None => self.apply_value(Value::Dict(Tag::Default, BTreeMap::new()), false),
+            Some(data) => {
+                self.apply_value_impl(Value::Dict(Tag::Default, data), origin_of, lenient)
+            }
+            None => self.apply_value(Value::Dict(Tag::Default, BTreeMap::new()), Origin::Default),
+        }
+    }
+
+    /// Apply a value coming from a single layer. It has the type of the schema.
+    pub fn apply_value(&mut self, value: Value, origin: Origin) -> anyhow::Result<()> {
+        self.apply_value_impl(value, &|_| origin.clone(), Lenient::default())
+    }
+
+    /// Remove the user value of the field at `data_path`. It takes the value of `layer`
+    /// (e.g. the system config) or its default, like if the user never defined it.
+    pub fn reset_to_inherited(
+        &mut self,
+        data_path: &[DataPathType],
+        layer: Option<&Value>,
+        origin_of: &dyn Fn(Tag) -> Origin,
+        lenient: Lenient,
+    ) -> anyhow::Result<()> {
+        let inherited = layer.and_then(|layer| self.layer_value_at(layer, data_path).cloned());
+
+        let node = self.get_at_mut(data_path)?;
+
+        let Some((DataPathType::Name(name), parent_path)) = data_path.split_last() else {
+            bail!(NodeError::unsupported(
+                data_path,
+                &node.node,
+                "reset to inherited"
+            ));
+        };
+
+        match (inherited, &node.default) {
+            (Some(value), _) => {
+                node.remove_value_rec();
+                node.apply_value_impl(value, origin_of, lenient)?;
+            }
+            (None, Some(default)) => {
+                let default = default.clone();
+                node.remove_value_rec();
+                node.apply_value(default, Origin::Default)?;
+            }
+            // keys added by the user disappear
+            (None, None) if node.removable => {
+                if let Node::Object(node_object) = &mut self.get_at_mut(parent_path)?.node {
+                    node_object.nodes.shift_remove(name);
+                }
+            }
+            (None, None) => node.remove_value_rec(),
         }
+
+        // the parents may no longer contain a user value
+        for end in (0..data_path.len()).rev() {
+            let parent = self.get_at_mut(&data_path[..end])?;
+
+            let origin = match &parent.node {
+                Node::Object(node_object) => {
+                    Origin::most_specific(node_object.nodes.values().map(|n| &n.origin))
+                }
+                Node::Enum(node_enum) => node_enum.selected().map(|(_, n)| n.origin.clone()),
+                _ => None,
+            };
+
+            parent.origin = origin.unwrap_or_default();
+        }
+
+        Ok(())
     }
 
-    // todo: the modified logic in the function seems wrong (i probably fixed it)
-    // todo2: analyze the entire logic
-    pub fn apply_value(&mut self, value: Value, modified: bool) -> anyhow::Result<()> {
+    /// The part of `layer` that belongs to the node at `data_path`.
+    /// Enums have no level in the value, so the path is followed in the tree.
+    fn layer_value_at<'a>(
+        &self,
+        layer: &'a Value,
+        data_path: &[DataPathType],
+    ) -> Option<&'a Value> {
+        let Some((component, rest)) = data_path.split_first() else {
+            return Some(layer);
+        };
+
+        match (&self.node, component, layer) {
+            (Node::Enum(node_enum), DataPathType::Indice(pos), layer) => {
+                node_enum.nodes.get(*pos)?.layer_value_at(layer, rest)
+            }
+            (Node::Object(node_object), DataPathType::Name(name), Value::Dict(_, dict)) => {
+                node_object
+                    .nodes
+                    .get(name)?
+                    .layer_value_at(dict.get(name)?, rest)
+            }
+            (Node::Array(node_array), DataPathType::Indice(pos), Value::Array(_, values)) => {
+                node_array
+                    .values
+                    .as_ref()?
+                    .get(*pos)?
+                    .layer_value_at(values.get(*pos)?, rest)
+            }
+            _ => None,
+        }
+    }
+
+    fn apply_value_impl(
+        &mut self,
+        value: Value,
+        origin_of: &dyn Fn(Tag) -> Origin,
+        lenient: Lenient,
+    ) -> anyhow::Result<()> {
         // debug!("merge_figment_rec {:?} {:?}", &self, &value);
-        self.modified = modified;
+        self.origin = origin_of(value.tag());
+
+        // a value is present, so we need the definition
+        self.expand()?;
 
         match (value, &mut self.node) {
             (Value::String(tag, value), Node::String(node_string)) => {
                 node_string.value = Some(value);
             }
             (Value::Dict(tag, values), Node::Enum(node_enum)) => {
-                let pos = values
-                    .iter()
-                    .find_map(|(key, value)| {
+                let pos = match &node_enum.repr {
+                    EnumRepr::External => values.iter().find_map(|(key, value)| {
                         let key = Value::String(tag, key.clone());
                         node_enum.nodes.iter().position(|e| e.is_matching(&key))
-                    })
-                    .ok_or_else(|| {
+                    }),
+                    EnumRepr::Internal { tag: key } | EnumRepr::Adjacent { tag: key, .. } => {
+                        let name = values.get(key).and_then(Value::as_str).ok_or_else(|| {
+                            anyhow!("the tag {key} of the enum is missing in \n{values:#?}")
+                        })?;
+
+                        node_enum.position_of(name)
+                    }
+                    EnumRepr::Untagged => {
+                        let value = Value::Dict(tag, values.clone());
+                        node_enum.nodes.iter().position(|e| e.is_matching(&value))
+                    }
+                }
+                .ok_or_else(|| {
                         anyhow!(
                             "can't find a compatible enum variant for dict \n{values:#?}.\n{node_enum:#?}"
                         )
                     })?;
 
                 node_enum.value = Some(pos);
-                node_enum.nodes[pos].apply_value(Value::Dict(tag, values), modified)?;
+                node_enum.nodes[pos].apply_value_impl(
+                    Value::Dict(tag, values),
+                    origin_of,
+                    lenient,
+                )?;
+                self.origin = node_enum.nodes[pos].origin.clone();
             }
             (value, Node::Enum(node_enum)) => {
                 let pos = node_enum
                     .nodes
                     .iter()
                     .position(|e| e.is_matching(&value))
+                    .or_else(|| {
+                        node_enum
+                            .nodes
+                            .iter()
+                            .position(|e| lenient.string_to_scalar && e.is_matching_untyped(&value))
+                    })
                     .ok_or_else(|| {
                         anyhow!(
                             "can't find a compatible enum variant for \n{value:#?}.\n{node_enum:#?}"
@@ -58,10 +204,16 @@ impl NodeContainer {
                     })?;
 
                 node_enum.value = Some(pos);
-                node_enum.nodes[pos].apply_value(value, modified)?;
+                node_enum.nodes[pos].apply_value_impl(value, origin_of, lenient)?;
+                self.origin = node_enum.nodes[pos].origin.clone();
             }
-            (Value::String(tag, value), Node::Value(node_value)) => {
-                // pass
+            (value, Node::Value(node_value)) => {
+                if !json_value_eq_figment_value(&node_value.value, &value) {
+                    bail!(
+                        "expected the constant {}, found {value:?}",
+                        node_value.value
+                    );
+                }
             }
             (Value::Bool(tag, value), Node::Bool(node_bool)) => node_bool.value = Some(value),
             (Value::Num(tag, value), Node::Number(node_number)) => {
@@ -73,6 +225,14 @@ impl NodeContainer {
                 node_number.value_string = value.to_string();
                 node_number.value = Some(value);
             }
+            (Value::String(tag, value), Node::Bool(node_bool)) if lenient.string_to_scalar => {
+                node_bool.value =
+                    Some(bool_from_str(&value).ok_or_else(|| anyhow!("{value} is not a bool"))?);
+            }
+            (Value::String(tag, value), Node::Number(node_number)) if lenient.string_to_scalar => {
+                node_number.value = Some(node_number.parse_from_str(&value)?);
+                node_number.value_string = value;
+            }
             (Value::Dict(tag, mut values), Node::Object(node_object)) => {
                 // hashmap are overided by existence of a value
                 node_object.nodes.retain(|_, node| !node.removable);
@@ -80,33 +240,64 @@ impl NodeContainer {
                 // for known object field ?
                 for (key, n) in &mut node_object.nodes {
                     if let Some(value) = values.remove(key) {
-                        n.apply_value(value, modified)?;
+                        n.apply_value_impl(value, origin_of, lenient)?;
                     } else if let Some(default) = &n.default {
-                        n.apply_value(default.clone(), false)?;
+                        n.apply_value(default.clone(), Origin::Default)?;
                     }
                 }
 
                 // for hashmap ?
-                if let Some(template) = node_object.template() {
-                    for (key, value) in values {
-                        let mut node_type = template.clone();
-                        node_type.apply_value(value, modified)?;
-                        node_object.nodes.insert(key, node_type);
+                let mut unknown_keys = Vec::new();
+
+                for (key, value) in values {
+                    node_object.check_key(&key)?;
+
+                    match node_object.template(&key) {
+                        Some(mut node_type) => {
+                            node_type.apply_value_impl(value, origin_of, lenient)?;
+                            node_object.nodes.insert(key, node_type);
+                        }
+                        None => unknown_keys.push(key),
                     }
                 }
+
+                if node_object.deny_unknown && !unknown_keys.is_empty() {
+                    bail!("unknown keys {unknown_keys:?}: additional properties are not allowed");
+                }
+
+                // merged dicts keep the tag of the first layer
+                let children = node_object.nodes.values().map(|n| &n.origin);
+                if let Some(origin) =
+                    Origin::most_specific(iter::once(&self.origin).chain(children))
+                {
+                    self.origin = origin;
+                }
             }
             (Value::Array(tag, values), Node::Array(node_array)) => {
                 let mut nodes = Vec::new();
 
                 for (pos, value) in values.into_iter().enumerate() {
-                    let mut new_node = node_array.template(Some(pos));
-                    new_node.apply_value(value, modified)?;
+                    let mut new_node = node_array
+                        .template(Some(pos))
+                        .ok_or_else(|| anyhow!("the array can't have more than {pos} items"))?;
+                    new_node.apply_value_impl(value, origin_of, lenient)?;
                     nodes.push(new_node);
                 }
 
                 node_array.values = Some(nodes);
             }
+            (value, Node::Array(node_array)) if lenient.value_to_array => {
+                let mut new_node = node_array
+                    .template(Some(0))
+                    .ok_or_else(|| anyhow!("the array can't have items"))?;
+                new_node.apply_value_impl(value, origin_of, lenient)?;
+                node_array.values = Some(vec![new_node]);
+            }
             (Value::Empty(tag, value), Node::Null) => {}
+            (value, Node::Any(node_any)) => {
+                node_any.value_string = NodeAny::format(&value);
+                node_any.value = Some(value);
+            }
             (value, node) => bail!("no compatible node for value = \n{value:#?}. \n{node:#?}"),
         };
 
@@ -140,9 +331,23 @@ impl NodeContainer {
                 node_array.values.take();
             }
             Node::Value(node_value) => {}
-            Node::Any => todo!(),
+            Node::Any(node_any) => {
+                node_any.value.take();
+            }
+            Node::Lazy(node_lazy) => {}
         };
-        self.modified = false;
+        self.origin = Origin::Default;
+    }
+
+    /// A string of an untyped format that can be converted to this node
+    fn is_matching_untyped(&self, value: &Value) -> bool {
+        match (value, &self.node) {
+            (Value::String(tag, value), Node::Bool(_)) => bool_from_str(value).is_some(),
+            (Value::String(tag, value), Node::Number(node_number)) => {
+                node_number.parse_from_str(value).is_ok()
+            }
+            _ => false,
+        }
     }
 
     fn is_matching(&self, value: &Value) -> bool {
@@ -158,10 +363,18 @@ impl NodeContainer {
             (Value::Num(tag, num), Node::Number(node_number)) => true,
             (Value::Empty(tag, empty), Node::Null) => true,
             (Value::Dict(tag, values), Node::Object(node_object)) => {
-                node_object.nodes.iter().all(|(key, n)| {
-                    let v = values.get(key).unwrap();
-                    n.is_matching(v)
-                })
+                node_object
+                    .nodes
+                    .iter()
+                    .all(|(key, n)| match values.get(key) {
+                        Some(v) => n.is_matching(v),
+                        None => !node_object.is_required(key),
+                    })
+                    && (!node_object.deny_unknown
+                        || values.keys().all(|key| {
+                            node_object.nodes.contains_key(key)
+                                || node_object.template(key).is_some()
+                        }))
             }
             (Value::Array(tag, values), Node::Array(node_array)) => {
                 // todo: more complicated logic
@@ -170,6 +383,8 @@ impl NodeContainer {
             (value, Node::Value(node_value)) => {
                 json_value_eq_figment_value(&node_value.value, value)
             }
+            (value, Node::Enum(node_enum)) => node_enum.nodes.iter().any(|n| n.is_matching(value)),
+            (value, Node::Lazy(_)) => self.expanded().is_ok_and(|node| node.is_matching(value)),
             _ => false,
         }
     }
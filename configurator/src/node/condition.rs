@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+
+use figment::value::{Tag, Value};
+
+use crate::utils::json_value_eq_figment_value;
+
+use super::{Node, NodeArrayTemplate, NodeContainer};
+
+/// Constraints that depend on the value of the node
+#[derive(Debug, Clone)]
+pub enum Condition {
+    /// `if`/`then`/`else`
+    If {
+        condition: Box<NodeContainer>,
+        then: Option<Box<NodeContainer>>,
+        otherwise: Option<Box<NodeContainer>>,
+    },
+    /// `not`
+    Not(Box<NodeContainer>),
+    /// `dependentRequired`: when `key` is present, `required` must be present too
+    DependentRequired { key: String, required: Vec<String> },
+    /// `dependentSchemas`: when `key` is present, the object must also match `schema`
+    DependentSchema {
+        key: String,
+        schema: Box<NodeContainer>,
+    },
+}
+
+fn has_key(value: &Value, key: &str) -> bool {
+    match value {
+        Value::Dict(_, values) => values.contains_key(key),
+        _ => false,
+    }
+}
+
+impl Condition {
+    pub fn holds(&self, value: &Value) -> bool {
+        match self {
+            Condition::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let branch = if condition.accepts(value) {
+                    then
+                } else {
+                    otherwise
+                };
+                branch.as_ref().is_none_or(|node| node.accepts(value))
+            }
+            Condition::Not(node) => !node.accepts(value),
+            Condition::DependentRequired { key, required } => {
+                !has_key(value, key) || required.iter().all(|k| has_key(value, k))
+            }
+            Condition::DependentSchema { key, schema } => {
+                !has_key(value, key) || schema.accepts(value)
+            }
+        }
+    }
+
+    /// Subschemas that currently apply to the value
+    fn active_branches<'a>(&'a self, value: &Value) -> Option<&'a NodeContainer> {
+        match self {
+            Condition::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                if condition.accepts(value) {
+                    then.as_deref()
+                } else {
+                    otherwise.as_deref()
+                }
+            }
+            Condition::DependentSchema { key, schema } if has_key(value, key) => Some(schema),
+            _ => None,
+        }
+    }
+
+    /// Subschemas that may apply to the value
+    pub fn branches(&self) -> Vec<&NodeContainer> {
+        match self {
+            Condition::If {
+                then, otherwise, ..
+            } => then.iter().chain(otherwise.iter()).map(|n| &**n).collect(),
+            Condition::DependentSchema { schema, .. } => vec![schema],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl NodeContainer {
+    /// Return true if the value satisfy all the constraints of this node.
+    /// Contrary to `apply_value`, this doesn't modify the node.
+    pub fn accepts(&self, value: &Value) -> bool {
+        let valid = match (&self.node, value) {
+            (Node::Any(_), _) => true,
+            (Node::Null, Value::Empty(..)) => true,
+            (Node::Bool(_), Value::Bool(..)) => true,
+            (Node::String(node_string), Value::String(_, value)) => {
+                node_string.validate(value).is_ok()
+            }
+            (Node::Number(node_number), Value::Num(_, num)) => node_number
+                .try_from_figment_num(*num)
+                .is_ok_and(|value| node_number.check_constraints(&value).is_ok()),
+            (Node::Object(node_object), Value::Dict(_, values)) => {
+                node_object
+                    .required
+                    .iter()
+                    .all(|key| values.contains_key(key))
+                    && values.iter().all(|(key, value)| {
+                        match node_object
+                            .nodes
+                            .get(key)
+                            .cloned()
+                            .or_else(|| node_object.template(key))
+                        {
+                            Some(node) => node.accepts(value),
+                            None => !node_object.deny_unknown,
+                        }
+                    })
+            }
+            (Node::Array(node_array), Value::Array(_, values)) => {
+                let len_ok = node_array
+                    .min
+                    .is_none_or(|min| values.len() >= min as usize)
+                    && node_array
+                        .max
+                        .is_none_or(|max| values.len() <= max as usize);
+
+                let unique_ok = !node_array.unique
+                    || values
+                        .iter()
+                        .enumerate()
+                        .all(|(pos, value)| !values[..pos].contains(value));
+
+                let items_ok =
+                    values
+                        .iter()
+                        .enumerate()
+                        .all(|(pos, value)| match &node_array.template {
+                            NodeArrayTemplate::All(template) => template.accepts(value),
+                            NodeArrayTemplate::FirstN(vec, additional) => vec
+                                .get(pos)
+                                .or(additional.as_deref())
+                                .is_some_and(|node| node.accepts(value)),
+                        });
+
+                len_ok && unique_ok && items_ok
+            }
+            (Node::Enum(node_enum), value) => node_enum.nodes.iter().any(|n| n.accepts(value)),
+            (Node::Value(node_value), value) => {
+                json_value_eq_figment_value(&node_value.value, value)
+            }
+            _ => false,
+        };
+
+        valid && self.conditions.iter().all(|c| c.holds(value))
+    }
+
+    /// Keys declared by a conditional subschema which is not active.
+    /// They are not shown nor written.
+    pub fn hidden_keys(&self) -> HashSet<&str> {
+        let Node::Object(node_object) = &self.node else {
+            return HashSet::new();
+        };
+
+        if node_object.conditional_keys.is_empty() {
+            return HashSet::new();
+        }
+
+        let mut hidden: HashSet<&str> = node_object
+            .conditional_keys
+            .iter()
+            .map(|key| key.as_str())
+            .collect();
+
+        if let Some(value) = self.value(&Tag::Default) {
+            for branch in self
+                .conditions
+                .iter()
+                .filter_map(|c| c.active_branches(&value))
+            {
+                if let Node::Object(branch_object) = &branch.node {
+                    for key in branch_object.nodes.keys() {
+                        hidden.remove(key.as_str());
+                    }
+                }
+            }
+        }
+
+        hidden
+    }
+}
@@ -2,7 +2,7 @@ use std::fmt::Display;
 
 use derive_more::derive::Unwrap;
 
-use crate::node::{Node, NodeContainer};
+use crate::node::{Node, NodeContainer, NodeError, Origin};
 
 #[derive(Debug, Clone, Unwrap, PartialEq, Eq)]
 #[unwrap(ref)]
@@ -54,6 +54,12 @@ impl DataPath {
         self.pos.replace(next_pos);
     }
 
+    /// Replace the whole path
+    pub fn go_to(&mut self, data_path: Vec<DataPathType>) {
+        self.pos = data_path.len().checked_sub(1);
+        self.vec = data_path;
+    }
+
     pub fn change_to(&mut self, pos: Option<usize>) {
         self.pos = pos;
     }
@@ -63,7 +69,7 @@ impl DataPath {
     }
 
     pub fn get_current(&self) -> Option<&DataPathType> {
-        self.pos.map(|pos| self.get_at(pos).unwrap())
+        self.pos.and_then(|pos| self.get_at(pos))
     }
 
     pub fn current(&self) -> &[DataPathType] {
@@ -135,94 +141,79 @@ fn sanitize_path_rec<'a>(
 }
 
 impl NodeContainer {
-    pub fn get_at<'a>(
-        &self,
-        mut data_path: impl Iterator<Item = &'a DataPathType>,
-    ) -> Option<&Self> {
-        match data_path.next() {
-            Some(component) => match &self.node {
-                Node::Object(node_object) => {
-                    let name = component.unwrap_name_ref();
-
-                    let node = node_object.nodes.get(name).unwrap();
-
-                    node.get_at(data_path)
-                }
-                Node::Enum(node_enum) => {
-                    let p = component.unwrap_indice_ref();
-                    let node = &node_enum.nodes[*p];
-
-                    node.get_at(data_path)
-                }
-                Node::Array(node_array) => {
-                    let p = component.unwrap_indice_ref();
-                    let node = &node_array.values.as_ref().unwrap()[*p];
-
-                    node.get_at(data_path)
-                }
-                _ => panic!(),
-            },
-            None => Some(self),
+    fn child(&self, component: &DataPathType) -> Option<&Self> {
+        match (&self.node, component) {
+            (Node::Object(node_object), DataPathType::Name(name)) => node_object.nodes.get(name),
+            (Node::Enum(node_enum), DataPathType::Indice(pos)) => node_enum.nodes.get(*pos),
+            (Node::Array(node_array), DataPathType::Indice(pos)) => node_array
+                .values
+                .as_ref()
+                .and_then(|values| values.get(*pos)),
+            _ => None,
         }
     }
 
-    pub fn get_at_mut<'a>(
-        &mut self,
-        mut data_path: impl Iterator<Item = &'a DataPathType>,
-    ) -> Option<&mut Self> {
-        match data_path.next() {
-            Some(component) => match &mut self.node {
-                Node::Object(node_object) => {
-                    let name = component.unwrap_name_ref();
-
-                    let node = node_object.nodes.get_mut(name).unwrap();
-
-                    node.get_at_mut(data_path)
-                }
-                Node::Enum(node_enum) => {
-                    let p = component.unwrap_indice_ref();
-                    let node = &mut node_enum.nodes[*p];
-
-                    node.get_at_mut(data_path)
-                }
-                Node::Array(node_array) => {
-                    let p = component.unwrap_indice_ref();
-                    let node = &mut node_array.values.as_mut().unwrap()[*p];
-
-                    node.get_at_mut(data_path)
-                }
-                _ => panic!(),
-            },
-            None => Some(self),
+    fn child_mut(&mut self, component: &DataPathType) -> Option<&mut Self> {
+        match (&mut self.node, component) {
+            (Node::Object(node_object), DataPathType::Name(name)) => {
+                node_object.nodes.get_mut(name)
+            }
+            (Node::Enum(node_enum), DataPathType::Indice(pos)) => node_enum.nodes.get_mut(*pos),
+            (Node::Array(node_array), DataPathType::Indice(pos)) => node_array
+                .values
+                .as_mut()
+                .and_then(|values| values.get_mut(*pos)),
+            _ => None,
         }
     }
 
-    pub fn set_modified<'a>(&mut self, mut data_path: impl Iterator<Item = &'a DataPathType>) {
-        self.modified = true;
+    pub fn get_at(&self, data_path: &[DataPathType]) -> Result<&Self, NodeError> {
+        let mut node = self;
 
-        if let Some(component) = data_path.next() {
-            match &mut self.node {
-                Node::Object(node_object) => {
-                    let name = component.unwrap_name_ref();
+        for (pos, component) in data_path.iter().enumerate() {
+            node = node
+                .child(component)
+                .ok_or_else(|| NodeError::not_found(&data_path[..=pos], &node.node))?;
+        }
 
-                    let node = node_object.nodes.get_mut(name).unwrap();
+        Ok(node)
+    }
 
-                    node.set_modified(data_path);
-                }
-                Node::Enum(node_enum) => {
-                    let p = component.unwrap_indice_ref();
-                    let node = &mut node_enum.nodes[*p];
+    pub fn get_at_mut(&mut self, data_path: &[DataPathType]) -> Result<&mut Self, NodeError> {
+        let mut node = self;
 
-                    node.set_modified(data_path);
-                }
-                Node::Array(node_array) => {
-                    let p = component.unwrap_indice_ref();
-                    let node = &mut node_array.values.as_mut().unwrap()[*p];
+        for (pos, component) in data_path.iter().enumerate() {
+            let kind = node.node.kind();
 
-                    node.set_modified(data_path);
-                }
-                _ => {}
-            }
+            node = node
+                .child_mut(component)
+                .ok_or_else(|| NodeError::NotFound {
+                    data_path: data_path[..=pos].to_vec(),
+                    kind,
+                })?;
         }
+
+        Ok(node)
+    }
+
+    /// Mark all the nodes along the path as edited by the user.
+    /// Nothing is marked if the path doesn't exist.
+    pub fn set_edited(&mut self, data_path: &[DataPathType]) -> Result<(), NodeError> {
+        self.get_at(data_path)?;
+
+        let mut node = self;
+        node.origin = Origin::Edit;
+
+        for component in data_path {
+            // resolved above
+            let Some(child) = node.child_mut(component) else {
+                break;
+            };
+
+            child.origin = Origin::Edit;
+            node = child;
+        }
+
+        Ok(())
     }
 }
@@ -0,0 +1,483 @@
+use std::fmt::Display;
+
+use serde::{
+    de::{
+        self, value::BorrowedStrDeserializer, DeserializeSeed, EnumAccess, IntoDeserializer,
+        MapAccess, SeqAccess, VariantAccess, Visitor,
+    },
+    forward_to_deserialize_any, Deserialize, Deserializer,
+};
+
+use super::{
+    data_path::DataPathType,
+    error::FmtDataPath,
+    ser::{external_tag, is_option},
+    Node, NodeContainer, NumberValue,
+};
+
+/// Turn the tree into a typed config, e.g. the config struct of the app
+pub fn from_node<'de, T: Deserialize<'de>>(node: &'de NodeContainer) -> Result<T, DeError> {
+    T::deserialize(ValueDeserializer::new(node))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeError {
+    /// Node where the error happened. None until the error reach a node
+    pub data_path: Option<Vec<DataPathType>>,
+    pub message: String,
+}
+
+impl DeError {
+    /// Errors of the inner nodes keep their path
+    fn at(mut self, data_path: &[DataPathType]) -> Self {
+        if self.data_path.is_none() {
+            self.data_path = Some(data_path.to_vec());
+        }
+        self
+    }
+}
+
+impl Display for DeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(data_path) = &self.data_path {
+            write!(f, "{}: ", FmtDataPath(data_path))?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for DeError {}
+
+impl de::Error for DeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self {
+            data_path: None,
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// Deserializer over the current value of the tree.
+/// Enums follow the same rules as the serializer (see `ser.rs`).
+pub struct ValueDeserializer<'de> {
+    node: &'de NodeContainer,
+    data_path: Vec<DataPathType>,
+}
+
+impl<'de> ValueDeserializer<'de> {
+    pub fn new(node: &'de NodeContainer) -> Self {
+        Self {
+            node,
+            data_path: Vec::new(),
+        }
+    }
+
+    fn child(&self, component: DataPathType, node: &'de NodeContainer) -> Self {
+        let mut data_path = self.data_path.clone();
+        data_path.push(component);
+        Self { node, data_path }
+    }
+
+    fn no_value(&self) -> DeError {
+        de::Error::custom(format!("no {} value is defined", self.node.node.kind()))
+    }
+
+    fn deserialize_any_impl<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match &self.node.node {
+            Node::Null => visitor.visit_unit(),
+            Node::Bool(node_bool) => match node_bool.value {
+                Some(value) => visitor.visit_bool(value),
+                None => Err(self.no_value()),
+            },
+            Node::String(node_string) => match &node_string.value {
+                Some(value) => visitor.visit_borrowed_str(value),
+                None => Err(self.no_value()),
+            },
+            Node::Number(node_number) => match node_number.value {
+                Some(NumberValue::U8(n)) => visitor.visit_u8(n),
+                Some(NumberValue::U16(n)) => visitor.visit_u16(n),
+                Some(NumberValue::U32(n)) => visitor.visit_u32(n),
+                Some(NumberValue::U64(n)) => visitor.visit_u64(n),
+                Some(NumberValue::U128(n)) => visitor.visit_u128(n),
+                Some(NumberValue::USize(n)) => visitor.visit_u64(n as u64),
+                Some(NumberValue::I8(n)) => visitor.visit_i8(n),
+                Some(NumberValue::I16(n)) => visitor.visit_i16(n),
+                Some(NumberValue::I32(n)) => visitor.visit_i32(n),
+                Some(NumberValue::I64(n)) => visitor.visit_i64(n),
+                Some(NumberValue::I128(n)) => visitor.visit_i128(n),
+                Some(NumberValue::ISize(n)) => visitor.visit_i64(n as i64),
+                Some(NumberValue::F32(n)) => visitor.visit_f32(n),
+                Some(NumberValue::F64(n)) => visitor.visit_f64(n),
+                None => Err(self.no_value()),
+            },
+            Node::Object(node_object) => {
+                let hidden = self.node.hidden_keys();
+
+                let entries = node_object
+                    .nodes
+                    .iter()
+                    .filter(|(key, node)| !hidden.contains(key.as_str()) && node.is_serializable())
+                    .map(|(key, node)| {
+                        (
+                            key.as_str(),
+                            self.child(DataPathType::Name(key.clone()), node),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                visitor.visit_map(MapDeserializer {
+                    entries: entries.into_iter(),
+                    value: None,
+                })
+            }
+            Node::Enum(node_enum) => match node_enum.selected() {
+                Some((pos, node)) => self
+                    .child(DataPathType::Indice(pos), node)
+                    .deserialize_any_impl(visitor),
+                None => Err(de::Error::custom("no variant is selected")),
+            },
+            Node::Array(node_array) => match &node_array.values {
+                Some(values) => {
+                    let values = values
+                        .iter()
+                        .enumerate()
+                        .map(|(pos, node)| self.child(DataPathType::Indice(pos), node))
+                        .collect::<Vec<_>>();
+
+                    visitor.visit_seq(SeqDeserializer {
+                        values: values.into_iter(),
+                    })
+                }
+                None => Err(self.no_value()),
+            },
+            Node::Value(node_value) => {
+                Deserializer::deserialize_any(&node_value.value, visitor).map_err(de::Error::custom)
+            }
+            Node::Any(node_any) => match &node_any.value {
+                Some(value) => {
+                    Deserializer::deserialize_any(value, visitor).map_err(de::Error::custom)
+                }
+                None => Err(self.no_value()),
+            },
+            Node::Lazy(_) => Err(de::Error::custom("the definition is not loaded")),
+        }
+    }
+
+    fn deserialize_option_impl<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match &self.node.node {
+            Node::Null => visitor.visit_none(),
+            Node::Enum(node_enum) if is_option(node_enum) => match node_enum.selected() {
+                Some((_, node)) if matches!(node.node, Node::Null) => visitor.visit_none(),
+                Some((pos, node)) => {
+                    visitor.visit_some(self.child(DataPathType::Indice(pos), node))
+                }
+                None => Err(de::Error::custom("no variant is selected")),
+            },
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_enum_impl<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match &self.node.node {
+            Node::Enum(node_enum) => match node_enum.selected() {
+                Some((pos, node)) => self
+                    .child(DataPathType::Indice(pos), node)
+                    .deserialize_enum_impl(visitor),
+                None => Err(de::Error::custom("no variant is selected")),
+            },
+            Node::Value(node_value) if let Some(name) = node_value.value.as_str() => {
+                visitor.visit_enum(VariantDeserializer { name, inner: None })
+            }
+            Node::String(node_string) if let Some(name) = &node_string.value => {
+                visitor.visit_enum(VariantDeserializer { name, inner: None })
+            }
+            Node::Object(node_object) if let Some((name, node)) = external_tag(node_object) => {
+                let inner = self.child(DataPathType::Name(name.clone()), node);
+
+                visitor.visit_enum(VariantDeserializer {
+                    name,
+                    inner: Some(inner),
+                })
+            }
+            node => Err(de::Error::custom(format!(
+                "a {} can't be read as an enum variant",
+                node.kind()
+            ))),
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        let data_path = self.data_path.clone();
+        self.deserialize_any_impl(visitor)
+            .map_err(|e| e.at(&data_path))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        let data_path = self.data_path.clone();
+        self.deserialize_option_impl(visitor)
+            .map_err(|e| e.at(&data_path))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        let data_path = self.data_path.clone();
+        self.deserialize_enum_impl(visitor)
+            .map_err(|e| e.at(&data_path))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        let data_path = self.data_path.clone();
+        visitor
+            .visit_newtype_struct(self)
+            .map_err(|e| e.at(&data_path))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DeError> for ValueDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+struct MapDeserializer<'de> {
+    entries: std::vec::IntoIter<(&'de str, ValueDeserializer<'de>)>,
+    value: Option<ValueDeserializer<'de>>,
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer<'de> {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, DeError> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(BorrowedStrDeserializer::new(key))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value, DeError> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::custom("value requested before its key")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct SeqDeserializer<'de> {
+    values: std::vec::IntoIter<ValueDeserializer<'de>>,
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer<'de> {
+    type Error = DeError;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<Option<S::Value>, DeError> {
+        self.values
+            .next()
+            .map(|value| seed.deserialize(value))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+struct VariantDeserializer<'de> {
+    name: &'de str,
+    /// None for unit variants
+    inner: Option<ValueDeserializer<'de>>,
+}
+
+impl<'de> EnumAccess<'de> for VariantDeserializer<'de> {
+    type Error = DeError;
+    type Variant = Self;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<(S::Value, Self), DeError> {
+        let variant = seed.deserialize(BorrowedStrDeserializer::new(self.name))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for VariantDeserializer<'de> {
+    type Error = DeError;
+
+    fn unit_variant(self) -> Result<(), DeError> {
+        match self.inner {
+            None => Ok(()),
+            Some(inner) => Deserialize::deserialize(inner),
+        }
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value, DeError> {
+        match self.inner {
+            Some(inner) => seed.deserialize(inner),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, DeError> {
+        match self.inner {
+            Some(inner) => inner.deserialize_seq(visitor),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        match self.inner {
+            Some(inner) => inner.deserialize_map(visitor),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"struct variant",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use figment::{providers, Figment, Profile};
+    use schemars::{schema_for, JsonSchema};
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+    use crate::{
+        node::{data_path::DataPathType, Node, NodeContainer},
+        test_common::*,
+    };
+
+    use super::from_node;
+
+    fn tree_with_default<S: JsonSchema + Default + Serialize>() -> (S, NodeContainer) {
+        let schema = schema_for!(S);
+
+        let mut tree = NodeContainer::from_json_schema(&schema);
+
+        let config1 = S::default();
+
+        let figment = Figment::new().join(providers::Serialized::from(&config1, Profile::Default));
+
+        tree.apply_figment(&figment).unwrap();
+
+        (config1, tree)
+    }
+
+    fn test_schema<S: JsonSchema + Default + Serialize + DeserializeOwned>() {
+        let (config1, tree) = tree_with_default::<S>();
+
+        let config2: S = from_node(&tree).unwrap();
+
+        assert_eq!(
+            json::to_value(&config1).unwrap(),
+            json::to_value(&config2).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_all() {
+        test_schema::<TestBool>();
+        test_schema::<TestString>();
+        test_schema::<TestNumber>();
+        test_schema::<TestFloat>();
+        test_schema::<TestEnumSimple>();
+        test_schema::<TestEnumComplex>();
+        test_schema::<TestOption>();
+        test_schema::<TestOptionComplex>();
+        test_schema::<TestOptionEnum>();
+        test_schema::<TestTuple>();
+        test_schema::<TestVec>();
+        test_schema::<TestHashMap>();
+        test_schema::<Rec>();
+        test_schema::<TestVeryComplex>();
+        test_schema::<TestEnumTagged>();
+    }
+
+    /// What the UI produce can be read by the app
+    #[test]
+    fn test_edited_tree() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Config {
+            x: String,
+        }
+
+        let (_, mut tree) = tree_with_default::<TestString>();
+
+        let path = [DataPathType::Name("x".into())];
+        let Node::String(node_string) = &mut tree.get_at_mut(&path).unwrap().node else {
+            panic!("x should be a string");
+        };
+        node_string.value = Some("edited".into());
+
+        assert_eq!(
+            from_node::<Config>(&tree).unwrap(),
+            Config { x: "edited".into() }
+        );
+    }
+
+    #[test]
+    fn test_error_path() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Config {
+            x: String,
+            missing: bool,
+        }
+
+        let (_, tree) = tree_with_default::<TestString>();
+
+        let error = from_node::<Config>(&tree).unwrap_err();
+        assert_eq!(error.data_path, Some(vec![]));
+
+        let (_, tree) = tree_with_default::<TestNumber>();
+
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Other {
+            x: String,
+        }
+
+        let error = from_node::<Other>(&tree).unwrap_err();
+        assert_eq!(error.data_path, Some(vec![DataPathType::Name("x".into())]));
+    }
+}
@@ -0,0 +1,291 @@
+use std::fmt::Display;
+
+use figment::value::Tag;
+
+use super::{data_path::DataPathType, error::FmtDataPath, Condition, Node, NodeAny, NodeContainer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The value is written, but probably not what the user wants
+    Warning,
+    /// The tree can't be written
+    Error,
+}
+
+/// A value that breaks a constraint of the schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Keyword of the schema, e.g. `minLength`
+    pub keyword: &'static str,
+    pub message: String,
+}
+
+impl Violation {
+    pub fn new(keyword: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            keyword,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Violation {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Path of the offending node, relative to the node that was checked
+    pub data_path: Vec<DataPathType>,
+    pub keyword: &'static str,
+    pub message: String,
+    pub severity: Severity,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", FmtDataPath(&self.data_path), self.message)
+    }
+}
+
+fn push(
+    diagnostics: &mut Vec<Diagnostic>,
+    data_path: &[DataPathType],
+    severity: Severity,
+    violation: Violation,
+) {
+    diagnostics.push(Diagnostic {
+        data_path: data_path.to_vec(),
+        keyword: violation.keyword,
+        message: violation.message,
+        severity,
+    });
+}
+
+fn no_value(node: &Node) -> Violation {
+    Violation::new("type", format!("no {} value is defined", node.kind()))
+}
+
+impl Condition {
+    fn violation(&self) -> Violation {
+        match self {
+            Condition::If { .. } => Violation::new("if", "the conditional subschema doesn't match"),
+            Condition::Not(_) => Violation::new("not", "the value matches a forbidden schema"),
+            Condition::DependentRequired { key, required } => Violation::new(
+                "dependentRequired",
+                format!("{} are required when {key} is present", required.join(", ")),
+            ),
+            Condition::DependentSchema { key, .. } => Violation::new(
+                "dependentSchemas",
+                format!("the object doesn't match the schema required by {key}"),
+            ),
+        }
+    }
+}
+
+impl NodeContainer {
+    /// Everything that prevents this node from being written, plus some warnings.
+    /// Errors are sorted in tree order, so the first one is the first offending node.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        self.diagnostics_rec(&mut Vec::new(), &mut diagnostics);
+        diagnostics
+    }
+
+    fn diagnostics_rec(
+        &self,
+        data_path: &mut Vec<DataPathType>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        for violation in &self.ignored {
+            push(diagnostics, data_path, Severity::Warning, violation.clone());
+        }
+
+        match &self.node {
+            Node::Null | Node::Value(_) => {}
+            Node::Any(node_any) => match &node_any.value {
+                Some(_) => {
+                    if let Err(e) = NodeAny::parse(&node_any.value_string) {
+                        // the input was rejected in the UI
+                        let message = format!("{e}, the previous value is kept");
+                        push(
+                            diagnostics,
+                            data_path,
+                            Severity::Warning,
+                            Violation::new("type", message),
+                        );
+                    }
+                }
+                None => push(
+                    diagnostics,
+                    data_path,
+                    Severity::Error,
+                    no_value(&self.node),
+                ),
+            },
+            Node::Bool(node_bool) => {
+                if node_bool.value.is_none() {
+                    push(
+                        diagnostics,
+                        data_path,
+                        Severity::Error,
+                        no_value(&self.node),
+                    );
+                }
+            }
+            Node::String(node_string) => match &node_string.value {
+                Some(value) => {
+                    if let Err(violation) = node_string.validate(value) {
+                        push(diagnostics, data_path, Severity::Error, violation);
+                    }
+                }
+                None => push(
+                    diagnostics,
+                    data_path,
+                    Severity::Error,
+                    no_value(&self.node),
+                ),
+            },
+            Node::Number(node_number) => match &node_number.value {
+                Some(value) => {
+                    if let Err(violation) = node_number.check_constraints(value) {
+                        push(diagnostics, data_path, Severity::Error, violation);
+                    } else if node_number
+                        .try_parse_from_str(&node_number.value_string)
+                        .is_err()
+                    {
+                        // the input was rejected in the UI
+                        let message = format!(
+                            "{} is not accepted, {value} is kept",
+                            node_number.value_string
+                        );
+                        push(
+                            diagnostics,
+                            data_path,
+                            Severity::Warning,
+                            Violation::new("type", message),
+                        );
+                    }
+                }
+                None => push(
+                    diagnostics,
+                    data_path,
+                    Severity::Error,
+                    no_value(&self.node),
+                ),
+            },
+            Node::Object(node_object) => {
+                let hidden = self.hidden_keys();
+
+                for key in &node_object.required {
+                    if !node_object.nodes.contains_key(key) {
+                        let violation =
+                            Violation::new("required", format!("the property {key} is required"));
+                        push(diagnostics, data_path, Severity::Error, violation);
+                    }
+                }
+
+                for (key, node) in &node_object.nodes {
+                    // optional fields without value are not written
+                    if !hidden.contains(key.as_str())
+                        && (node_object.is_required(key) || node.has_value())
+                    {
+                        node.child_diagnostics(
+                            DataPathType::Name(key.clone()),
+                            data_path,
+                            diagnostics,
+                        );
+                    }
+                }
+            }
+            Node::Enum(node_enum) => match node_enum.selected() {
+                Some((pos, node)) => {
+                    node.child_diagnostics(DataPathType::Indice(pos), data_path, diagnostics)
+                }
+                None => push(
+                    diagnostics,
+                    data_path,
+                    Severity::Error,
+                    Violation::new("oneOf", "no variant is selected"),
+                ),
+            },
+            Node::Array(node_array) => match &node_array.values {
+                Some(values) => {
+                    if let Err(violation) = node_array.check_constraints() {
+                        push(diagnostics, data_path, Severity::Error, violation);
+                    }
+
+                    if values
+                        .iter()
+                        .any(|node| node.value(&Tag::Default).is_none())
+                    {
+                        push(
+                            diagnostics,
+                            data_path,
+                            Severity::Error,
+                            Violation::new(
+                                "items",
+                                "an item has no value, the array can't be written",
+                            ),
+                        );
+                    }
+
+                    for (pos, node) in values.iter().enumerate() {
+                        node.child_diagnostics(DataPathType::Indice(pos), data_path, diagnostics);
+                    }
+                }
+                None => push(
+                    diagnostics,
+                    data_path,
+                    Severity::Error,
+                    no_value(&self.node),
+                ),
+            },
+            Node::Lazy(_) => push(
+                diagnostics,
+                data_path,
+                Severity::Error,
+                Violation::new("$ref", "the definition is not loaded"),
+            ),
+        }
+
+        if !self.conditions.is_empty()
+            && let Some(value) = self.value(&Tag::Default)
+        {
+            for condition in self.conditions.iter().filter(|c| !c.holds(&value)) {
+                push(
+                    diagnostics,
+                    data_path,
+                    Severity::Error,
+                    condition.violation(),
+                );
+            }
+        }
+    }
+
+    fn child_diagnostics(
+        &self,
+        component: DataPathType,
+        data_path: &mut Vec<DataPathType>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        data_path.push(component);
+        self.diagnostics_rec(data_path, diagnostics);
+        data_path.pop();
+    }
+
+    /// Return true if the node can be written
+    pub fn is_valid(&self) -> bool {
+        !self.diagnostics().iter().any(Diagnostic::is_error)
+    }
+}
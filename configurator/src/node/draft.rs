@@ -0,0 +1,174 @@
+use super::Violation;
+
+/// Node construction works on the draft-07 vocabulary of schemars' `SchemaObject`,
+/// plus the keywords kept in its `extensions` (`dependentRequired`, ...).
+/// Newer drafts are rewritten into it before being deserialized.
+///
+/// Some keywords can't always be rewritten: `unevaluatedProperties` and `unevaluatedItems`
+/// next to a subschema that evaluates the value in place (`allOf`, `$ref`, ...), and a
+/// `$dynamicRef` to an anchor. They are left in `extensions`, and ignored with a warning
+/// on the node, see [`Draft::unsupported`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Draft {
+    Draft4,
+    Draft7,
+    Draft2019_09,
+    Draft2020_12,
+}
+
+/// Keywords whose subschemas evaluate the properties and items of the value, like the schema
+/// itself
+const IN_PLACE_APPLICATORS: [&str; 7] = [
+    "allOf",
+    "anyOf",
+    "oneOf",
+    "$ref",
+    "$dynamicRef",
+    "if",
+    "dependentSchemas",
+];
+
+impl Draft {
+    /// Documents without `$schema` are read with the most recent draft,
+    /// which is a superset of the others for the keywords we support.
+    pub fn detect(document: &json::Value) -> Self {
+        match document.get("$schema").and_then(|s| s.as_str()) {
+            Some(uri) if uri.contains("draft-07") || uri.contains("draft-06") => Draft::Draft7,
+            Some(uri) if uri.contains("draft-04") => Draft::Draft4,
+            Some(uri) if uri.contains("2019-09") => Draft::Draft2019_09,
+            _ => Draft::Draft2020_12,
+        }
+    }
+
+    /// The keywords `normalize` left in `extensions` because they can't be rewritten
+    pub fn unsupported(extensions: &schemars::Map<String, json::Value>) -> Vec<Violation> {
+        let mut violations = Vec::new();
+
+        for keyword in ["unevaluatedProperties", "unevaluatedItems"] {
+            if extensions.contains_key(keyword) {
+                let message = format!(
+                    "{keyword} next to {} is not supported, it is ignored",
+                    IN_PLACE_APPLICATORS.join(", ")
+                );
+                violations.push(Violation::new(keyword, message));
+            }
+        }
+
+        if let Some(reference) = extensions.get("$dynamicRef") {
+            let message = format!("$dynamicRef to an anchor is not supported: {reference}");
+            violations.push(Violation::new("$dynamicRef", message));
+        }
+
+        violations
+    }
+
+    /// Rewrite a schema and all its subschemas in the draft-07 vocabulary
+    pub fn normalize(self, schema: &mut json::Value) {
+        let json::Value::Object(obj) = schema else {
+            return;
+        };
+
+        if self >= Draft::Draft2020_12 {
+            // `prefixItems` + `items` are the old `items` array + `additionalItems`
+            if let Some(prefix_items) = obj.remove("prefixItems") {
+                if let Some(items) = obj.remove("items") {
+                    obj.insert("additionalItems".into(), items);
+                }
+                obj.insert("items".into(), prefix_items);
+            }
+
+            // without an anchor, `$dynamicRef` is a `$ref`
+            if obj
+                .get("$dynamicRef")
+                .and_then(json::Value::as_str)
+                .is_some_and(|reference| {
+                    reference.split_once('#').is_none_or(|(_, fragment)| {
+                        fragment.is_empty() || fragment.starts_with('/')
+                    })
+                })
+                && let Some(reference) = obj.remove("$dynamicRef")
+            {
+                obj.entry("$ref").or_insert(reference);
+            }
+        }
+
+        if self == Draft::Draft4 {
+            // `exclusiveMinimum: true` made `minimum` exclusive
+            for (bound, exclusive) in [
+                ("minimum", "exclusiveMinimum"),
+                ("maximum", "exclusiveMaximum"),
+            ] {
+                if let Some(json::Value::Bool(is_exclusive)) = obj.get(exclusive) {
+                    if *is_exclusive && let Some(value) = obj.remove(bound) {
+                        obj.insert(exclusive.into(), value);
+                    } else {
+                        obj.remove(exclusive);
+                    }
+                }
+            }
+        }
+
+        if self >= Draft::Draft2019_09 {
+            if let Some(reference) = obj.remove("$recursiveRef") {
+                obj.entry("$ref").or_insert(reference);
+            }
+
+            // without these keywords, the evaluated properties and items are the ones of
+            // the schema itself, like for `additionalProperties` and `additionalItems`
+            let in_place = IN_PLACE_APPLICATORS.iter().any(|k| obj.contains_key(*k));
+
+            if !in_place && let Some(unevaluated) = obj.remove("unevaluatedProperties") {
+                // otherwise, `additionalProperties` evaluates all the other properties
+                obj.entry("additionalProperties").or_insert(unevaluated);
+            }
+
+            if !in_place
+                && !obj.contains_key("contains")
+                && let Some(unevaluated) = obj.remove("unevaluatedItems")
+            {
+                match obj.get("items") {
+                    // a tuple
+                    Some(json::Value::Array(_)) => {
+                        obj.entry("additionalItems").or_insert(unevaluated);
+                    }
+                    // all the items are evaluated
+                    Some(_) => {}
+                    None => {
+                        obj.insert("items".into(), unevaluated);
+                    }
+                }
+            }
+        }
+
+        for (keyword, value) in obj.iter_mut() {
+            match keyword.as_str() {
+                "additionalProperties"
+                | "propertyNames"
+                | "additionalItems"
+                | "contains"
+                | "not"
+                | "if"
+                | "then"
+                | "else" => self.normalize(value),
+                "properties" | "patternProperties" | "definitions" | "$defs"
+                | "dependentSchemas" | "dependencies" => {
+                    if let json::Value::Object(schemas) = value {
+                        schemas.values_mut().for_each(|s| self.normalize(s));
+                    }
+                }
+                "allOf" | "anyOf" | "oneOf" => {
+                    if let json::Value::Array(schemas) = value {
+                        schemas.iter_mut().for_each(|s| self.normalize(s));
+                    }
+                }
+                "items" => match value {
+                    json::Value::Array(schemas) => {
+                        schemas.iter_mut().for_each(|s| self.normalize(s));
+                    }
+                    value => self.normalize(value),
+                },
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt::Display;
+
+use super::{data_path::DataPathType, Node};
+
+/// Error raised when an operation doesn't fit the shape of the tree,
+/// e.g. a stale message pointing to a node that was removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeError {
+    /// No node at this path. `kind` is the last node that was reached
+    NotFound {
+        data_path: Vec<DataPathType>,
+        kind: &'static str,
+    },
+    /// The operation can't be applied on this kind of node
+    Unsupported {
+        data_path: Vec<DataPathType>,
+        kind: &'static str,
+        operation: &'static str,
+    },
+}
+
+impl NodeError {
+    pub fn not_found(data_path: &[DataPathType], node: &Node) -> Self {
+        NodeError::NotFound {
+            data_path: data_path.to_vec(),
+            kind: node.kind(),
+        }
+    }
+
+    pub fn unsupported(data_path: &[DataPathType], node: &Node, operation: &'static str) -> Self {
+        NodeError::Unsupported {
+            data_path: data_path.to_vec(),
+            kind: node.kind(),
+            operation,
+        }
+    }
+
+    pub fn data_path(&self) -> &[DataPathType] {
+        match self {
+            NodeError::NotFound { data_path, .. } => data_path,
+            NodeError::Unsupported { data_path, .. } => data_path,
+        }
+    }
+}
+
+/// `/a/0`, or `/` for the root
+pub(super) struct FmtDataPath<'a>(pub &'a [DataPathType]);
+
+impl Display for FmtDataPath<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "/");
+        }
+
+        for component in self.0 {
+            write!(f, "/{component}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for NodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NodeError::NotFound { data_path, kind } => {
+                write!(f, "no node at {} (in {kind})", FmtDataPath(data_path))
+            }
+            NodeError::Unsupported {
+                data_path,
+                kind,
+                operation,
+            } => write!(
+                f,
+                "can't {operation} on {kind} at {}",
+                FmtDataPath(data_path)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NodeError {}
@@ -1,20 +1,37 @@
 use core::num;
 use std::{
     borrow::{BorrowMut, Cow},
-    collections::BTreeMap,
+    collections::{BTreeMap, HashSet},
+    path::PathBuf,
 };
 
 use figment::value::{Empty, Num, Tag};
 use json::value::Index;
 use schemars::schema::{
-    InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec, SubschemaValidation,
+    InstanceType, NumberValidation, RootSchema, Schema, SchemaObject, SingleOrVec,
+    StringValidation, SubschemaValidation,
 };
 
-use super::*;
+use super::{
+    resolver::Scope,
+    ser::{self, external_tag},
+    *,
+};
 
 impl NodeContainer {
     pub fn from_json_schema(schema: &RootSchema) -> Self {
-        schema_object_to_node("root", &schema.definitions, &schema.schema).unwrap()
+        Self::from_json_value(json::to_value(schema).unwrap(), None).unwrap()
+    }
+
+    /// `base_dir` is used to resolve references to other files
+    pub fn from_json_value(schema: json::Value, base_dir: Option<PathBuf>) -> anyhow::Result<Self> {
+        let resolver = Arc::new(Resolver::new(schema, base_dir));
+
+        let scope = Scope::new(&resolver);
+        let node = scope.expand_root();
+        scope.check()?;
+
+        node.ok_or_else(|| anyhow!("no value can match this schema"))
     }
 }
 
@@ -22,12 +39,12 @@ impl NodeContainer {
 #[instrument(skip_all)]
 pub(crate) fn schema_object_to_node(
     from: &str,
-    def: &schemars::Map<String, Schema>,
+    scope: &Scope,
     schema_object: &SchemaObject,
 ) -> Option<NodeContainer> {
     // debug!("enter function from {from}.\n{:#?}", schema_object);
 
-    let mut res = NodeContainer::from_node(Node::Any);
+    let mut res = NodeContainer::from_node(Node::Any(NodeAny::new()));
 
     if let Some(single_or_vec) = &schema_object.instance_type {
         fn instance_type_to_node(instance_type: &InstanceType, format: Option<&String>) -> Node {
@@ -41,7 +58,10 @@ pub(crate) fn schema_object_to_node(
                         .and_then(|s| NumberValue::kind_from_str(s))
                         .unwrap_or(NumberValueLight::F64),
                 )),
-                InstanceType::String => Node::String(NodeString::new()),
+                InstanceType::String => Node::String(NodeString {
+                    format: format.cloned(),
+                    ..NodeString::new()
+                }),
                 InstanceType::Integer => Node::Number(NodeNumber::new(
                     format
                         .and_then(|s| NumberValue::kind_from_str(s))
@@ -78,27 +98,69 @@ pub(crate) fn schema_object_to_node(
         let mut nodes = IndexMap::new();
 
         for (name, type_definition) in &obj.properties {
-            let node = schema_object_to_node("object", def, &type_definition.to_object())?;
+            ser::register_name(name);
+            let node = schema_object_to_node("object", scope, &type_definition.to_object())?;
             nodes.insert(name.clone(), node);
         }
 
-        let additional_properties = if !obj.properties.is_empty() {
-            None
-        } else {
-            obj.additional_properties
-                .as_ref()
-                .map(|additional_properties| {
-                    schema_object_to_node("object", def, &additional_properties.to_object())
-                })?
+        // declared properties and additional properties can coexist
+        let (additional_properties, deny_unknown) = match obj.additional_properties.as_deref() {
+            Some(Schema::Bool(false)) => (None, true),
+            Some(additional_properties) => {
+                match schema_object_to_node("object", scope, &additional_properties.to_object()) {
+                    Some(node) => (Some(node), false),
+                    // no value can satisfy this schema
+                    None => (None, true),
+                }
+            }
+            None => (None, false),
         };
 
-        let node =
-            NodeContainer::from_node(Node::Object(NodeObject::new(nodes, additional_properties)));
+        let mut pattern_templates = Vec::new();
+
+        for (pattern, type_definition) in &obj.pattern_properties {
+            let regex = match Regex::new(pattern) {
+                Ok(regex) => regex,
+                Err(e) => {
+                    warn!("unsupported pattern {pattern}: {e}");
+                    continue;
+                }
+            };
+
+            let node =
+                schema_object_to_node("object pattern", scope, &type_definition.to_object())?;
+            pattern_templates.push((regex, node));
+        }
+
+        let property_names = match &obj.property_names {
+            Some(property_names) => Some(Box::new(schema_object_to_node(
+                "object property names",
+                scope,
+                &property_names.to_object(),
+            )?)),
+            None => None,
+        };
+
+        let node_object = NodeObject {
+            required: obj.required.iter().cloned().collect(),
+            deny_unknown,
+            pattern_templates,
+            property_names,
+            ..NodeObject::new(nodes, additional_properties)
+        };
+
+        let node = NodeContainer::from_node(Node::Object(node_object));
 
         res = res.merge(&node)?;
     }
 
     if let Some(enum_values) = &schema_object.enum_values {
+        // unit variants
+        enum_values
+            .iter()
+            .filter_map(json::Value::as_str)
+            .for_each(ser::register_name);
+
         let node = if enum_values.len() == 1 {
             NodeContainer::from_node(Node::Value(NodeValue::new(enum_values[0].clone())))
         } else {
@@ -116,12 +178,21 @@ pub(crate) fn schema_object_to_node(
         res = res.merge(&node)?;
     }
 
+    if let Some(const_value) = &schema_object.const_value {
+        if let Some(name) = const_value.as_str() {
+            ser::register_name(name);
+        }
+
+        let node = NodeContainer::from_node(Node::Value(NodeValue::new(const_value.clone())));
+        res = res.merge(&node)?;
+    }
+
     if let Some(array) = &schema_object.array {
         let template = match &array.items {
             Some(single_or_vec) => match single_or_vec {
                 // this means items of the array all share the type described by this schema
                 SingleOrVec::Single(schema) => {
-                    let node = schema_object_to_node("array single", def, &schema.to_object())?;
+                    let node = schema_object_to_node("array single", scope, &schema.to_object())?;
                     NodeArrayTemplate::All(Box::new(node))
                 }
                 // items are of type array.
@@ -129,14 +200,24 @@ pub(crate) fn schema_object_to_node(
                     let template: Option<Vec<_>> = vec
                         .iter()
                         .map(|schema| {
-                            schema_object_to_node("array multiple", def, &schema.to_object())
+                            schema_object_to_node("array multiple", scope, &schema.to_object())
                         })
                         .collect();
 
-                    NodeArrayTemplate::FirstN(template?)
+                    // the tuple is open unless `additionalItems` is false
+                    let additional = match &array.additional_items {
+                        Some(schema) => {
+                            schema_object_to_node("array additional", scope, &schema.to_object())
+                        }
+                        None => Some(NodeContainer::from_node(Node::Any(NodeAny::new()))),
+                    };
+
+                    NodeArrayTemplate::FirstN(template?, additional.map(Box::new))
                 }
             },
-            None => NodeArrayTemplate::All(Box::new(NodeContainer::from_node(Node::Any))),
+            None => NodeArrayTemplate::All(Box::new(NodeContainer::from_node(Node::Any(
+                NodeAny::new(),
+            )))),
         };
 
         let node = NodeContainer::from_node(Node::Array(NodeArray {
@@ -144,6 +225,7 @@ pub(crate) fn schema_object_to_node(
             template,
             min: array.min_items,
             max: array.max_items,
+            unique: array.unique_items.unwrap_or(false),
         }));
 
         res = res.merge(&node)?;
@@ -154,22 +236,22 @@ pub(crate) fn schema_object_to_node(
             let mut nodes = Vec::new();
 
             for schema in all_of {
-                let node = schema_object_to_node("all_of", def, &schema.to_object())?;
+                let node = schema_object_to_node("all_of", scope, &schema.to_object())?;
                 nodes.push(node);
             }
 
-            let node = if nodes.len() > 1 {
-                todo!()
-            } else {
-                nodes.remove(0)
-            };
+            // allOf is an intersection: every branch must hold at the same time
+            let node = nodes.iter().try_fold(
+                NodeContainer::from_node(Node::Any(NodeAny::new())),
+                |acc, node| acc.merge(node),
+            )?;
             res = res.merge(&node)?;
         }
 
         if let Some(one_of) = &subschemas.one_of {
             let mut nodes = Vec::new();
             for schema in one_of {
-                let node = schema_object_to_node("one_of", def, &schema.to_object())?;
+                let node = schema_object_to_node("one_of", scope, &schema.to_object())?;
 
                 nodes.push(node);
             }
@@ -181,7 +263,7 @@ pub(crate) fn schema_object_to_node(
         if let Some(any_of) = &subschemas.any_of {
             let mut nodes = Vec::new();
             for schema in any_of {
-                let node = schema_object_to_node("one_of", def, &schema.to_object())?;
+                let node = schema_object_to_node("one_of", scope, &schema.to_object())?;
 
                 nodes.push(node);
             }
@@ -191,19 +273,187 @@ pub(crate) fn schema_object_to_node(
         }
     }
 
-    if let Some(definition) = &schema_object.reference {
-        if let Some(definition) = definition.strip_prefix("#/definitions/") {
-            let schema = def.get(definition).unwrap();
+    if let Some(reference) = &schema_object.reference {
+        let node = scope.resolve(reference)?;
+        res = res.merge(&node)?;
+    }
 
-            let node = schema_object_to_node("definition", def, &schema.to_object())?;
-            res = res.merge(&node)?;
-        }
+    res.apply_conditions("conditions", scope, schema_object)?;
+
+    if let Some(number) = &schema_object.number {
+        res.apply_number_validation(number);
+    }
+
+    if let Some(string) = &schema_object.string {
+        res.apply_string_validation(string);
     }
 
-    let res = res.metadata(&schema_object.metadata);
+    let mut res = res.metadata(&schema_object.metadata);
+    res.ignored
+        .extend(Draft::unsupported(&schema_object.extensions));
     Some(res)
 }
 
+impl NodeContainer {
+    /// Conditional keywords are kept as [`Condition`] and evaluated against the value.
+    /// Fields declared only in a conditional subschema are added to the object,
+    /// but hidden while the subschema is not active.
+    /// Return None if no value can match the schema
+    fn apply_conditions(
+        &mut self,
+        from: &str,
+        scope: &Scope,
+        schema_object: &SchemaObject,
+    ) -> Option<()> {
+        // an enum without variants accepts nothing
+        fn never() -> Box<NodeContainer> {
+            Box::new(NodeContainer::from_node(Node::Enum(NodeEnum::new(
+                Vec::new(),
+            ))))
+        }
+
+        let to_node = |schema: &Schema| schema_object_to_node(from, scope, &schema.to_object());
+
+        let mut conditions = Vec::new();
+
+        if let Some(subschemas) = &schema_object.subschemas {
+            if let Some(not) = &subschemas.not {
+                // if the subschema accepts nothing, `not` accepts everything
+                if let Some(node) = to_node(not) {
+                    if matches!(node.node, Node::Any(_)) && node.conditions.is_empty() {
+                        return None;
+                    }
+                    conditions.push(Condition::Not(Box::new(node)));
+                }
+            }
+
+            if let Some(if_schema) = &subschemas.if_schema {
+                let branch = |schema: &Option<Box<Schema>>| {
+                    schema
+                        .as_ref()
+                        .map(|schema| to_node(schema).map(Box::new).unwrap_or_else(never))
+                };
+
+                match to_node(if_schema) {
+                    Some(condition) => conditions.push(Condition::If {
+                        condition: Box::new(condition),
+                        then: branch(&subschemas.then_schema),
+                        otherwise: branch(&subschemas.else_schema),
+                    }),
+                    // the condition never match
+                    None => {
+                        if let Some(otherwise) = &subschemas.else_schema {
+                            *self = self.merge(&to_node(otherwise)?)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut dependencies = Vec::new();
+
+        for keyword in ["dependentRequired", "dependentSchemas", "dependencies"] {
+            if let Some(json::Value::Object(map)) = schema_object.extensions.get(keyword) {
+                dependencies.extend(map.iter());
+            }
+        }
+
+        for (key, dependency) in dependencies {
+            match dependency {
+                json::Value::Array(required) => conditions.push(Condition::DependentRequired {
+                    key: key.clone(),
+                    required: required
+                        .iter()
+                        .filter_map(|k| k.as_str().map(ToOwned::to_owned))
+                        .collect(),
+                }),
+                dependency => match json::from_value::<Schema>(dependency.clone()) {
+                    Ok(schema) => conditions.push(Condition::DependentSchema {
+                        key: key.clone(),
+                        schema: to_node(&schema).map(Box::new).unwrap_or_else(never),
+                    }),
+                    Err(e) => warn!("invalid dependency for {key}: {e}"),
+                },
+            }
+        }
+
+        if let Node::Object(node_object) = &mut self.node {
+            for condition in &conditions {
+                for branch in condition.branches() {
+                    if let Node::Object(branch_object) = &branch.node {
+                        for (key, node) in &branch_object.nodes {
+                            if !node_object.nodes.contains_key(key) {
+                                node_object.nodes.insert(key.clone(), node.clone());
+                                node_object.conditional_keys.push(key.clone());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        self.conditions.extend(conditions);
+
+        Some(())
+    }
+
+    /// Number validation only apply to numbers,
+    /// so we don't create a node for it.
+    fn apply_number_validation(&mut self, number: &NumberValidation) {
+        match &mut self.node {
+            Node::Number(node_number) => {
+                node_number.merge_constraints(&NodeNumber {
+                    minimum: number.minimum,
+                    maximum: number.maximum,
+                    exclusive_minimum: number.exclusive_minimum,
+                    exclusive_maximum: number.exclusive_maximum,
+                    multiple_of: number.multiple_of,
+                    ..NodeNumber::new(node_number.kind)
+                });
+            }
+            Node::Enum(node_enum) => {
+                for node in &mut node_enum.nodes {
+                    node.apply_number_validation(number);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// String validation only apply to strings,
+    /// so we don't create a node for it.
+    fn apply_string_validation(&mut self, string: &StringValidation) {
+        match &mut self.node {
+            Node::String(node_string) => {
+                let patterns = string
+                    .pattern
+                    .iter()
+                    .filter_map(|pattern| match Regex::new(pattern) {
+                        Ok(regex) => Some(regex),
+                        Err(e) => {
+                            warn!("unsupported pattern {pattern}: {e}");
+                            None
+                        }
+                    })
+                    .collect();
+
+                node_string.merge_constraints(&NodeString {
+                    min_length: string.min_length,
+                    max_length: string.max_length,
+                    patterns,
+                    ..NodeString::new()
+                });
+            }
+            Node::Enum(node_enum) => {
+                for node in &mut node_enum.nodes {
+                    node.apply_string_validation(string);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 pub trait ToSchemaObject {
     fn to_object(&self) -> Cow<'_, SchemaObject>;
 }
@@ -224,23 +474,25 @@ impl ToSchemaObject for Schema {
     }
 }
 
+/// serde_json stores a number in the first of u64, i64 and f64 that can hold it,
+/// so this conversion is exact.
+pub(crate) fn json_number_to_num(number: &json::Number) -> Num {
+    if let Some(n) = number.as_u64() {
+        Num::U64(n)
+    } else if let Some(n) = number.as_i64() {
+        Num::I64(n)
+    } else if let Some(n) = number.as_f64() {
+        Num::F64(n)
+    } else {
+        panic!("not a valid number")
+    }
+}
+
 pub(crate) fn json_value_to_figment_value(json_value: &json::Value) -> Value {
     match json_value {
         json::Value::Null => Value::Empty(Tag::Default, Empty::None),
         json::Value::Bool(value) => Value::Bool(Tag::Default, *value),
-        json::Value::Number(number) => {
-            let num = if let Some(n) = number.as_u64() {
-                Num::U64(n)
-            } else if let Some(n) = number.as_i64() {
-                Num::I64(n)
-            } else if let Some(n) = number.as_f64() {
-                Num::F64(n)
-            } else {
-                panic!("not a valid number")
-            };
-
-            Value::Num(Tag::Default, num)
-        }
+        json::Value::Number(number) => Value::Num(Tag::Default, json_number_to_num(number)),
         json::Value::String(str) => Value::String(Tag::Default, str.clone()),
         json::Value::Array(vec) => {
             let array = vec.iter().map(json_value_to_figment_value).collect();
@@ -259,66 +511,355 @@ pub(crate) fn json_value_to_figment_value(json_value: &json::Value) -> Value {
 }
 
 impl NodeContainer {
-    fn merge(&self, other: &NodeContainer) -> Option<NodeContainer> {
-        match (&self.node, &other.node) {
-            (Node::Null, Node::Null) => Some(other.clone()),
+    /// Intersection of two nodes. None means that no value can
+    /// be valid for both nodes.
+    pub(crate) fn merge(&self, other: &NodeContainer) -> Option<NodeContainer> {
+        let res = match (&self.node, &other.node) {
+            (Node::Lazy(node_lazy1), Node::Lazy(node_lazy2)) => {
+                NodeContainer::from_node(Node::Lazy(node_lazy1.merge(node_lazy2)))
+            }
+            (Node::Lazy(_), Node::Any(_)) => self.clone(),
+            (Node::Any(_), Node::Lazy(_)) => other.clone(),
+            // the other node is finite, so this will stop
+            (Node::Lazy(_), _) => return self.expanded().ok()?.merge(other),
+            (_, Node::Lazy(_)) => return self.merge(&other.expanded().ok()?),
+            (Node::Null, Node::Null) => other.clone(),
             // (Node::Null, Node::Any) => Some(other.clone()),
-            (Node::Bool(node_bool), Node::Null) => Some(other.clone()),
-            (Node::Bool(node_bool), Node::Bool(node_bool2)) => Some(other.clone()),
-            (Node::String(node_string), Node::String(node_string2)) => Some(other.clone()),
-            (Node::Number(node_number), Node::Number(node_number2)) => Some(other.clone()),
-            (Node::Object(node_object), Node::Object(node_object2)) => Some(other.clone()),
-            (Node::Enum(node_enum1), Node::Enum(node_enum2)) => todo!("product?"),
-            (Node::Enum(node_enum), node_other) => {
-                match node_enum
+            (Node::Bool(node_bool), Node::Null) => other.clone(),
+            (Node::Bool(node_bool), Node::Bool(node_bool2)) => other.clone(),
+            (Node::String(node_string), Node::String(node_string2)) => {
+                let mut node_string2 = node_string2.clone();
+                node_string2.merge_constraints(node_string);
+                NodeContainer::from_node(Node::String(node_string2))
+            }
+            (Node::Number(node_number), Node::Number(node_number2)) => {
+                let mut node_number2 = node_number2.clone();
+                node_number2.merge_constraints(node_number);
+                NodeContainer::from_node(Node::Number(node_number2))
+            }
+            (Node::Object(node_object), Node::Object(node_object2)) => {
+                let mut nodes = node_object.nodes.clone();
+
+                for (key, node2) in &node_object2.nodes {
+                    let node = match nodes.get(key) {
+                        Some(node1) => node1.merge(node2)?,
+                        None => node2.clone(),
+                    };
+                    nodes.insert(key.clone(), node);
+                }
+
+                let template = match (&node_object.template, &node_object2.template) {
+                    (Some(template1), Some(template2)) => Some(template1.merge(template2)?),
+                    (template1, template2) => {
+                        template1.as_deref().or(template2.as_deref()).cloned()
+                    }
+                };
+
+                let mut required = node_object.required.clone();
+                for key in &node_object2.required {
+                    if !required.contains(key) {
+                        required.push(key.clone());
+                    }
+                }
+
+                let mut pattern_templates = node_object.pattern_templates.clone();
+                pattern_templates.extend(node_object2.pattern_templates.iter().cloned());
+
+                let property_names =
+                    match (&node_object.property_names, &node_object2.property_names) {
+                        (Some(names1), Some(names2)) => Some(Box::new(names1.merge(names2)?)),
+                        (names1, names2) => names1.as_ref().or(names2.as_ref()).cloned(),
+                    };
+
+                let mut conditional_keys = node_object.conditional_keys.clone();
+                for key in &node_object2.conditional_keys {
+                    if !conditional_keys.contains(key) {
+                        conditional_keys.push(key.clone());
+                    }
+                }
+
+                let node_object = NodeObject {
+                    required,
+                    deny_unknown: node_object.deny_unknown || node_object2.deny_unknown,
+                    conditional_keys,
+                    pattern_templates,
+                    property_names,
+                    ..NodeObject::new(nodes, template)
+                };
+
+                NodeContainer::from_node(Node::Object(node_object))
+            }
+            (Node::Enum(node_enum1), Node::Enum(node_enum2)) => {
+                // product of the two enums: only the compatible pairs are kept
+                let mut nodes: Vec<_> = node_enum1
                     .nodes
                     .iter()
-                    .enumerate()
-                    .find_map(|(pos, n)| n.merge(other).map(|n| (pos, n)))
-                {
-                    Some((pos, new)) => {
-                        let mut node_self = self.clone();
-                        let mut new_enum = node_enum.clone();
-                        new_enum.nodes[pos] = new;
-                        node_self.node = Node::Enum(new_enum);
-
-                        Some(node_self)
-                    }
-                    None => None,
+                    .flat_map(|n1| node_enum2.nodes.iter().filter_map(|n2| n1.merge(n2)))
+                    .collect();
+
+                match nodes.len() {
+                    0 => return None,
+                    1 => nodes.remove(0),
+                    _ => NodeContainer::from_node(Node::Enum(NodeEnum::new(nodes))),
                 }
             }
+            (Node::Enum(node_enum), node_other) => {
+                let mut node_self = self.clone();
+                node_self.node = Node::Enum(node_enum.merge_variants(other)?);
+                node_self
+            }
             // (node_self, Node::Enum(node_other)) => {
             //     todo!()
             // }
-            (Node::Array(node_array), Node::Array(node_array2)) => Some(other.clone()),
-            (_, Node::Value(node_value2)) => Some(other.clone()),
-            (Node::Value(node_value1), _) => Some(self.clone()),
-            (Node::Any, _) => Some(other.clone()),
-            (_, Node::Any) => Some(self.clone()),
-            (_, Node::Enum(node_enum)) => {
-                match node_enum
-                    .nodes
-                    .iter()
-                    .enumerate()
-                    .find_map(|(pos, n)| n.merge(self).map(|n| (pos, n)))
-                {
-                    Some((pos, new)) => {
-                        let mut node_other = other.clone();
-                        let mut new_enum = node_enum.clone();
-                        new_enum.nodes[pos] = new;
-                        node_other.node = Node::Enum(new_enum);
-
-                        Some(node_other)
+            (Node::Array(node_array), Node::Array(node_array2)) => {
+                let template = match (&node_array.template, &node_array2.template) {
+                    (NodeArrayTemplate::All(template1), NodeArrayTemplate::All(template2)) => {
+                        NodeArrayTemplate::All(Box::new(template1.merge(template2)?))
+                    }
+                    (
+                        NodeArrayTemplate::FirstN(vec1, additional1),
+                        NodeArrayTemplate::FirstN(vec2, additional2),
+                    ) => {
+                        let mut vec = Vec::new();
+
+                        for pos in 0..std::cmp::max(vec1.len(), vec2.len()) {
+                            let node1 = vec1.get(pos).or(additional1.as_deref());
+                            let node2 = vec2.get(pos).or(additional2.as_deref());
+
+                            match (node1, node2) {
+                                (Some(node1), Some(node2)) => vec.push(node1.merge(node2)?),
+                                // no more items are allowed
+                                _ => break,
+                            }
+                        }
+
+                        let additional = match (additional1, additional2) {
+                            (Some(node1), Some(node2)) => node1.merge(node2).map(Box::new),
+                            _ => None,
+                        };
+
+                        NodeArrayTemplate::FirstN(vec, additional)
+                    }
+                    (
+                        NodeArrayTemplate::All(template),
+                        NodeArrayTemplate::FirstN(vec, additional),
+                    )
+                    | (
+                        NodeArrayTemplate::FirstN(vec, additional),
+                        NodeArrayTemplate::All(template),
+                    ) => {
+                        let vec: Option<Vec<_>> = vec.iter().map(|n| n.merge(template)).collect();
+                        let additional = additional
+                            .as_ref()
+                            .and_then(|n| n.merge(template))
+                            .map(Box::new);
+                        NodeArrayTemplate::FirstN(vec?, additional)
                     }
-                    None => None,
+                };
+
+                // keep the most restrictive bounds
+                let min = match (node_array.min, node_array2.min) {
+                    (Some(min1), Some(min2)) => Some(std::cmp::max(min1, min2)),
+                    (min1, min2) => min1.or(min2),
+                };
+
+                let max = match (node_array.max, node_array2.max) {
+                    (Some(max1), Some(max2)) => Some(std::cmp::min(max1, max2)),
+                    (max1, max2) => max1.or(max2),
+                };
+
+                NodeContainer::from_node(Node::Array(NodeArray {
+                    values: None,
+                    template,
+                    min,
+                    max,
+                    unique: node_array.unique || node_array2.unique,
+                }))
+            }
+            (_, Node::Value(node_value2)) => {
+                if !self.node.is_compatible_with_value(&node_value2.value) {
+                    return None;
+                }
+                other.clone()
+            }
+            (Node::Value(node_value1), _) => {
+                if !other.node.is_compatible_with_value(&node_value1.value) {
+                    return None;
                 }
+                self.clone()
+            }
+            (Node::Any(_), _) => other.clone(),
+            (_, Node::Any(_)) => self.clone(),
+            (_, Node::Enum(node_enum)) => {
+                let mut node_other = other.clone();
+                node_other.node = Node::Enum(node_enum.merge_variants(self)?);
+                node_other
             }
             _ => {
                 warn!("none");
                 dbg!(&self, &other);
 
-                None
+                return None;
             }
+        };
+
+        let mut res = res.merge_metadata(self, other);
+        res.conditions = self
+            .conditions
+            .iter()
+            .chain(other.conditions.iter())
+            .cloned()
+            .collect();
+        res.ignored = self
+            .ignored
+            .iter()
+            .chain(other.ignored.iter())
+            .cloned()
+            .collect();
+
+        Some(res)
+    }
+
+    /// Keep the metadata of both nodes, `other` having the priority.
+    fn merge_metadata(self, node1: &NodeContainer, node2: &NodeContainer) -> Self {
+        Self {
+            default: self
+                .default
+                .or_else(|| node2.default.clone())
+                .or_else(|| node1.default.clone()),
+            title: self
+                .title
+                .or_else(|| node2.title.clone())
+                .or_else(|| node1.title.clone()),
+            desc: self
+                .desc
+                .or_else(|| node2.desc.clone())
+                .or_else(|| node1.desc.clone()),
+            ..self
+        }
+    }
+}
+
+impl NodeEnum {
+    /// Merge `other` in all the compatible variants.
+    /// Variants that are not compatible are dropped.
+    fn merge_variants(&self, other: &NodeContainer) -> Option<NodeEnum> {
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .filter_map(|node| node.merge(other))
+            .collect();
+
+        if nodes.is_empty() {
+            None
+        } else {
+            Some(NodeEnum::new(nodes))
+        }
+    }
+}
+
+/// Name of the variant, if `key` is the tag of an internally/adjacently tagged enum
+fn variant_name<'a>(node_object: &'a NodeObject, key: &str) -> Option<&'a str> {
+    if !node_object.is_required(key) {
+        return None;
+    }
+
+    match &node_object.nodes.get(key)?.node {
+        Node::Value(node_value) => node_value.value.as_str(),
+        _ => None,
+    }
+}
+
+impl EnumRepr {
+    /// Serde doesn't write the representation in the schema, so it is guessed
+    /// from the shape of the variants:
+    /// - objects sharing a required string constant are internally tagged,
+    ///   or adjacently tagged if their only other key is a required content.
+    ///   It must be in two variants at least: with one, a struct variant of an internally
+    ///   tagged enum has the same shape, and both representations give the same data
+    /// - string constants and `{ "Variant": ... }` are externally tagged
+    /// - anything else is untagged
+    pub(super) fn detect(nodes: &[NodeContainer]) -> Self {
+        let objects = nodes
+            .iter()
+            .map(|node| match &node.node {
+                Node::Object(node_object) => Some(node_object),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default();
+
+        if let Some(first) = objects.first() {
+            // each variant has its own name
+            let tag = first.nodes.keys().find(|key| {
+                let names: HashSet<_> = objects
+                    .iter()
+                    .filter_map(|node_object| variant_name(node_object, key))
+                    .collect();
+
+                names.len() == objects.len()
+            });
+
+            if let Some(tag) = tag {
+                let others: HashSet<_> = objects
+                    .iter()
+                    .flat_map(|node_object| node_object.nodes.keys())
+                    .filter(|key| *key != tag)
+                    .collect();
+
+                if let [content] = Vec::from_iter(others).as_slice() {
+                    let with_content: Vec<_> = objects
+                        .iter()
+                        .filter(|node_object| node_object.nodes.contains_key(*content))
+                        .collect();
+
+                    if with_content.len() >= 2
+                        && with_content
+                            .iter()
+                            .all(|node_object| node_object.is_required(content))
+                    {
+                        return EnumRepr::Adjacent {
+                            tag: tag.clone(),
+                            content: (*content).clone(),
+                        };
+                    }
+                }
+
+                return EnumRepr::Internal { tag: tag.clone() };
+            }
+        }
+
+        let is_external = nodes.iter().all(|node| match &node.node {
+            Node::Value(node_value) => node_value.value.is_string(),
+            Node::Object(node_object) => external_tag(node_object).is_some(),
+            _ => false,
+        });
+
+        if is_external {
+            EnumRepr::External
+        } else {
+            EnumRepr::Untagged
+        }
+    }
+}
+
+impl Node {
+    /// Return true if this json value can be a value of this node
+    fn is_compatible_with_value(&self, value: &json::Value) -> bool {
+        match (self, value) {
+            (Node::Any(_), _) => true,
+            (Node::Null, json::Value::Null) => true,
+            (Node::Bool(_), json::Value::Bool(_)) => true,
+            (Node::String(_), json::Value::String(_)) => true,
+            (Node::Number(_), json::Value::Number(_)) => true,
+            (Node::Object(_), json::Value::Object(_)) => true,
+            (Node::Array(_), json::Value::Array(_)) => true,
+            (Node::Value(node_value), value) => &node_value.value == value,
+            (Node::Enum(node_enum), value) => node_enum
+                .nodes
+                .iter()
+                .any(|n| n.node.is_compatible_with_value(value)),
+            _ => false,
         }
     }
 }
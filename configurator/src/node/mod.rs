@@ -1,20 +1,39 @@
-use std::{borrow::Cow, collections::BTreeMap, fmt::Display};
+use std::{borrow::Cow, collections::BTreeMap, fmt::Display, sync::Arc};
 
+use anyhow::{anyhow, bail};
 use derive_more::derive::Unwrap;
 use figment::value::{Num, Tag, Value};
 use from_json_schema::json_value_to_figment_value;
 use indexmap::IndexMap;
 use light_enum::LightEnum;
+use regex::Regex;
 use schemars::schema::SchemaObject;
 
-use crate::utils::{figment_value_to_f64, figment_value_to_i128};
+use crate::utils::{figment_value_to_f64, figment_value_to_i128, json_value_eq_figment_value};
 
+mod any;
 mod apply_figment;
+pub use apply_figment::Lenient;
+mod condition;
+pub use condition::Condition;
+mod de;
+pub use de::{from_node, DeError, ValueDeserializer};
+mod diagnostic;
+pub use diagnostic::{Diagnostic, Severity, Violation};
+mod draft;
+pub use draft::Draft;
 pub mod data_path;
+mod error;
+pub use error::NodeError;
 pub mod from_json_schema;
 mod number;
 pub use number::{NumberValue, NumberValueLight};
+mod origin;
+pub use origin::Origin;
+mod resolver;
+pub use resolver::{Reference, Resolver};
 mod ser;
+mod string;
 #[cfg(test)]
 mod tests;
 mod to_figment_value;
@@ -25,11 +44,15 @@ pub struct NodeContainer {
     pub default: Option<Value>,
     pub title: Option<String>,
     pub desc: Option<String>,
-    /// Node that are modified should be written to disk
-    pub modified: bool,
+    /// Layer that provides the value. Only the user layer is written to disk
+    pub origin: Origin,
     /// Used for HashMap. We need to know if the node
     /// was created by a "template"
     pub removable: bool,
+    /// Constraints that depend on the value (`if`, `not`, dependencies, ...)
+    pub conditions: Vec<Condition>,
+    /// Keywords of the schema that can't be represented, reported as warnings
+    pub ignored: Vec<Violation>,
 }
 
 impl NodeContainer {
@@ -39,14 +62,16 @@ impl NodeContainer {
             default: None,
             title: None,
             desc: None,
-            modified: false,
+            origin: Origin::Default,
             removable: false,
+            conditions: Vec::new(),
+            ignored: Vec::new(),
         }
     }
 }
 
 #[derive(Debug, Clone, Unwrap)]
-#[unwrap(ref_mut)]
+#[unwrap(ref, ref_mut)]
 pub enum Node {
     Null,
     Bool(NodeBool),
@@ -59,7 +84,28 @@ pub enum Node {
     /// represent a final value
     /// currently only string is supported
     Value(NodeValue),
-    Any,
+    /// Unconstrained value, edited as raw text
+    Any(NodeAny),
+    /// Recursive definition, expanded on demand
+    Lazy(NodeLazy),
+}
+
+impl Node {
+    /// Used in error messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Node::Null => "null",
+            Node::Bool(_) => "bool",
+            Node::String(_) => "string",
+            Node::Number(_) => "number",
+            Node::Object(_) => "object",
+            Node::Enum(_) => "enum",
+            Node::Array(_) => "array",
+            Node::Value(_) => "value",
+            Node::Any(_) => "any",
+            Node::Lazy(_) => "lazy",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -81,6 +127,11 @@ pub struct NodeBool {
 #[derive(Debug, Clone)]
 pub struct NodeString {
     pub value: Option<String>,
+    pub min_length: Option<u32>,
+    pub max_length: Option<u32>,
+    /// All patterns must match
+    pub patterns: Vec<Regex>,
+    pub format: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -88,6 +139,24 @@ pub struct NodeNumber {
     pub kind: NumberValueLight,
     pub value: Option<NumberValue>,
     pub value_string: String,
+    pub minimum: Option<f64>,
+    pub maximum: Option<f64>,
+    pub exclusive_minimum: Option<f64>,
+    pub exclusive_maximum: Option<f64>,
+    pub multiple_of: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NodeAny {
+    pub value: Option<Value>,
+    /// Raw input of the user, as JSON or RON
+    pub value_string: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct NodeLazy {
+    pub references: Vec<Reference>,
+    pub resolver: Arc<Resolver>,
 }
 
 #[derive(Debug, Clone)]
@@ -99,18 +168,46 @@ pub struct NodeValue {
 pub struct NodeEnum {
     pub value: Option<usize>,
     pub nodes: Vec<NodeContainer>,
+    /// How the variants are written, guessed from the schema
+    pub repr: EnumRepr,
+}
+
+/// Serde representation of an enum
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnumRepr {
+    /// `"Unit"` or `{ "Variant": ... }`
+    External,
+    /// `{ "type": "Variant", ... }`
+    Internal { tag: String },
+    /// `{ "t": "Variant", "c": ... }`
+    Adjacent { tag: String, content: String },
+    /// The first variant matching the value
+    Untagged,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct NodeObject {
     pub nodes: IndexMap<String, NodeContainer>,
+    /// Template for keys that don't match any pattern (`additionalProperties`)
     pub template: Option<Box<NodeContainer>>,
+    /// Templates for keys matching a regex (`patternProperties`)
+    pub pattern_templates: Vec<(Regex, NodeContainer)>,
+    /// Schema that all keys must satisfy (`propertyNames`)
+    pub property_names: Option<Box<NodeContainer>>,
+    /// Keys that must have a value
+    pub required: Vec<String>,
+    /// Keys that are neither declared nor covered by the template are rejected
+    pub deny_unknown: bool,
+    /// Keys declared by a conditional subschema (`then`, `else`, `dependentSchemas`)
+    pub conditional_keys: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
 pub enum NodeArrayTemplate {
     All(Box<NodeContainer>),
-    FirstN(Vec<NodeContainer>),
+    /// A tuple. The items after it use the second template (`additionalItems`),
+    /// None means that there can't be more items
+    FirstN(Vec<NodeContainer>, Option<Box<NodeContainer>>),
 }
 
 #[derive(Debug, Clone)]
@@ -119,6 +216,8 @@ pub struct NodeArray {
     pub template: NodeArrayTemplate,
     pub min: Option<u32>,
     pub max: Option<u32>,
+    /// All items must be different
+    pub unique: bool,
 }
 
 impl NodeBool {
@@ -127,12 +226,6 @@ impl NodeBool {
     }
 }
 
-impl NodeString {
-    pub fn new() -> Self {
-        Self { value: None }
-    }
-}
-
 impl NodeValue {
     pub fn new(value: json::Value) -> Self {
         Self { value }
@@ -141,7 +234,11 @@ impl NodeValue {
 
 impl NodeEnum {
     pub fn new(nodes: Vec<NodeContainer>) -> Self {
-        Self { value: None, nodes }
+        Self {
+            value: None,
+            repr: EnumRepr::detect(&nodes),
+            nodes,
+        }
     }
 }
 
@@ -150,18 +247,106 @@ impl NodeObject {
         Self {
             nodes,
             template: node_type.map(Box::new),
+            pattern_templates: Vec::new(),
+            property_names: None,
+            required: Vec::new(),
+            deny_unknown: false,
+            conditional_keys: Vec::new(),
         }
     }
 
-    pub fn template(&self) -> Option<NodeContainer> {
-        match &self.template {
-            Some(template) => {
-                let mut template = *template.clone();
-                template.removable = true;
-                Some(template)
+    pub fn is_required(&self, key: &str) -> bool {
+        self.required.iter().any(|k| k == key)
+    }
+
+    /// Return true if new keys can be added to this object
+    pub fn can_add(&self) -> bool {
+        self.template.is_some() || !self.pattern_templates.is_empty()
+    }
+
+    /// Template of a new node for this key.
+    /// When the key match some patterns, all the matching templates are merged,
+    /// otherwise the default template is used.
+    pub fn template(&self, key: &str) -> Option<NodeContainer> {
+        let mut matching = self
+            .pattern_templates
+            .iter()
+            .filter(|(regex, _)| regex.is_match(key))
+            .map(|(_, template)| template);
+
+        let mut template = match matching.next() {
+            Some(first) => matching.try_fold(first.clone(), |acc, template| acc.merge(template))?,
+            None => *self.template.clone()?,
+        };
+
+        template.removable = true;
+        Some(template)
+    }
+
+    /// Check the key against `propertyNames`
+    pub fn check_key(&self, key: &str) -> anyhow::Result<()> {
+        let Some(property_names) = &self.property_names else {
+            return Ok(());
+        };
+
+        let value = Value::String(Tag::Default, key.to_owned());
+
+        let mut node = (**property_names).clone();
+        node.apply_value(value.clone(), Origin::Default)
+            .map_err(|_| anyhow!("the key {key} is not allowed"))?;
+
+        match &node.node {
+            Node::String(node_string) => node_string
+                .validate(key)
+                .map_err(|e| anyhow!("the key {e}"))?,
+            Node::Value(node_value) if !json_value_eq_figment_value(&node_value.value, &value) => {
+                bail!("the key must be {}", node_value.value)
             }
-            None => None,
+            _ if !node.is_valid() => bail!("the key {key} is not allowed"),
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Check that a new key can be inserted in this object
+    pub fn check_new_key(&self, key: &str) -> anyhow::Result<()> {
+        if key.is_empty() {
+            bail!("the key can't be empty");
+        }
+
+        if self.nodes.contains_key(key) {
+            bail!("the key {key} already exist");
+        }
+
+        self.check_key(key)?;
+
+        if self.template(key).is_none() {
+            bail!("the key {key} is not allowed by the schema");
+        }
+
+        Ok(())
+    }
+
+    /// Node to insert under the new key when renaming `prev`.
+    /// The value is applied again because the new key can match another template
+    pub fn renamed_node(&self, prev: &str, new: &str) -> anyhow::Result<NodeContainer> {
+        self.check_new_key(new)?;
+
+        let node = self
+            .nodes
+            .get(prev)
+            .ok_or_else(|| anyhow!("the key {prev} doesn't exist"))?;
+
+        let mut new_node = self.template(new).unwrap();
+
+        if let Some(value) = node.value(&Tag::Default) {
+            new_node
+                .apply_value(value, Origin::Edit)
+                .map_err(|_| anyhow!("the value of {prev} is not valid for the key {new}"))?;
         }
+
+        Ok(new_node)
     }
 }
 
@@ -169,20 +354,85 @@ impl NodeArray {
     pub fn new_any() -> Self {
         Self {
             values: None,
-            template: NodeArrayTemplate::All(Box::new(NodeContainer::from_node(Node::Any))),
+            template: NodeArrayTemplate::All(Box::new(NodeContainer::from_node(Node::Any(
+                NodeAny::new(),
+            )))),
             min: None,
             max: None,
+            unique: false,
         }
     }
 
-    pub fn template(&self, n: Option<usize>) -> NodeContainer {
-        match &self.template {
-            NodeArrayTemplate::All(new_node) => {
-                let mut new_node = (**new_node).clone();
-                new_node.removable = true;
-                new_node
-            }
-            NodeArrayTemplate::FirstN(vec) => {
+    fn len(&self) -> usize {
+        self.values.as_ref().map_or(0, |values| values.len())
+    }
+
+    /// Return true if a new item can be pushed
+    pub fn can_add(&self) -> bool {
+        let len = self.len();
+
+        if let NodeArrayTemplate::FirstN(vec, None) = &self.template
+            && len >= vec.len()
+        {
+            return false;
+        }
+
+        self.max.is_none_or(|max| len < max as usize)
+    }
+
+    /// Return true if an item can be removed
+    pub fn can_remove(&self) -> bool {
+        self.min.is_none_or(|min| self.len() > min as usize)
+    }
+
+    pub fn check_constraints(&self) -> Result<(), Violation> {
+        let len = self.len();
+
+        if let Some(min) = self.min
+            && len < min as usize
+        {
+            return Err(Violation::new(
+                "minItems",
+                format!("must contain at least {} items", min),
+            ));
+        }
+
+        if let Some(max) = self.max
+            && len > max as usize
+        {
+            return Err(Violation::new(
+                "maxItems",
+                format!("must contain at most {} items", max),
+            ));
+        }
+
+        if self.unique && self.has_duplicates() {
+            return Err(Violation::new("uniqueItems", "items must be unique"));
+        }
+
+        Ok(())
+    }
+
+    /// Return true if two items have the same value
+    pub fn has_duplicates(&self) -> bool {
+        let Some(values) = &self.values else {
+            return false;
+        };
+
+        let values: Vec<_> = values.iter().map(|n| n.value(&Tag::Default)).collect();
+
+        values
+            .iter()
+            .enumerate()
+            .any(|(pos, value)| values[..pos].contains(value))
+    }
+
+    /// Node of the item at `n`, or of a new item when None.
+    /// None when the array can't have this item.
+    pub fn template(&self, n: Option<usize>) -> Option<NodeContainer> {
+        let additional = match &self.template {
+            NodeArrayTemplate::All(new_node) => new_node,
+            NodeArrayTemplate::FirstN(vec, additional) => {
                 let n = match n {
                     Some(n) => n,
                     None => match &self.values {
@@ -191,41 +441,55 @@ impl NodeArray {
                     },
                 };
 
-                vec[n].clone()
+                match (vec.get(n), additional) {
+                    (Some(node), _) => return Some(node.clone()),
+                    (None, Some(additional)) => additional,
+                    (None, None) => return None,
+                }
             }
-        }
+        };
+
+        let mut new_node = (**additional).clone();
+        new_node.removable = true;
+        Some(new_node)
     }
 }
 
 impl NodeContainer {
     /// Return true if all active note have a value
-    pub fn is_valid(&self) -> bool {
+    /// Return true if a value was set on this node
+    pub fn has_value(&self) -> bool {
         match &self.node {
             Node::Null => true,
             Node::Bool(node_bool) => node_bool.value.is_some(),
             Node::String(node_string) => node_string.value.is_some(),
             Node::Number(node_number) => node_number.value.is_some(),
-            Node::Object(node_object) => node_object.nodes.values().all(|n| n.is_valid()),
-            Node::Enum(node_enum) => node_enum
-                .value
-                .is_some_and(|pos| node_enum.nodes[pos].is_valid()),
-            Node::Array(node_array) => node_array
-                .values
-                .as_ref()
-                .is_some_and(|values| values.iter().all(|n| n.is_valid())),
+            Node::Object(node_object) => node_object.nodes.values().any(|n| n.has_value()),
+            Node::Enum(node_enum) => node_enum.value.is_some(),
+            Node::Array(node_array) => node_array.values.is_some(),
             Node::Value(node_value) => true,
-            Node::Any => true,
+            Node::Any(node_any) => node_any.value.is_some(),
+            Node::Lazy(node_lazy) => false,
         }
     }
 
+    /// Metadata defined on the schema itself have the priority
+    /// over the one that were merged into this node (`$ref`, `allOf`, ...)
     pub fn metadata(self, metadata: &Option<Box<schemars::schema::Metadata>>) -> Self {
         Self {
             default: metadata
                 .as_ref()
                 .and_then(|m| m.default.as_ref())
-                .map(json_value_to_figment_value),
-            title: metadata.as_ref().and_then(|m| m.title.clone()),
-            desc: metadata.as_ref().and_then(|m| m.description.clone()),
+                .map(json_value_to_figment_value)
+                .or(self.default),
+            title: metadata
+                .as_ref()
+                .and_then(|m| m.title.clone())
+                .or(self.title),
+            desc: metadata
+                .as_ref()
+                .and_then(|m| m.description.clone())
+                .or(self.desc),
             ..self
         }
     }
@@ -240,18 +504,38 @@ impl NodeContainer {
             Node::Enum(node_enum) => None,
             Node::Array(node_array) => None,
             Node::Value(node_value) => node_value.value.as_str().map(Cow::Borrowed),
-            Node::Any => Some(Cow::Borrowed("Any")),
+            Node::Any(_) => Some(Cow::Borrowed("Any")),
+            Node::Lazy(node_lazy) => None,
         }
     }
 }
 
 impl NodeEnum {
-    pub fn unwrap_value(&self) -> (usize, &NodeContainer) {
-        let pos = match self.value {
-            Some(pos) => pos,
-            None => panic!(),
-        };
+    /// The selected variant, if any
+    pub fn selected(&self) -> Option<(usize, &NodeContainer)> {
+        self.value
+            .and_then(|pos| self.nodes.get(pos).map(|node| (pos, node)))
+    }
 
-        (pos, &self.nodes[pos])
+    /// Position of the variant named `name` in a tagged enum
+    pub fn position_of(&self, name: &str) -> Option<usize> {
+        let tag = self.repr.tag()?;
+
+        self.nodes.iter().position(|node| match &node.node {
+            Node::Object(node_object) => node_object.nodes.get(tag).is_some_and(
+                |node| matches!(&node.node, Node::Value(node_value) if node_value.value == name),
+            ),
+            _ => false,
+        })
+    }
+}
+
+impl EnumRepr {
+    /// Key holding the name of the variant
+    pub fn tag(&self) -> Option<&str> {
+        match self {
+            EnumRepr::Internal { tag } | EnumRepr::Adjacent { tag, .. } => Some(tag),
+            EnumRepr::External | EnumRepr::Untagged => None,
+        }
     }
 }
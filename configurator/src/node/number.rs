@@ -1,10 +1,10 @@
-use std::fmt::Display;
+use std::{cmp::Ordering, fmt::Display};
 
 use anyhow::bail;
-use figment::value::Num;
+use figment::{error::Actual, value::Num};
 use light_enum::LightEnum;
 
-use super::NodeNumber;
+use super::{NodeNumber, Violation};
 
 #[derive(Debug, Clone, LightEnum)]
 pub enum NumberValue {
@@ -42,7 +42,7 @@ impl Display for NumberValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             NumberValue::I128(n) => write!(f, "{}", n),
-            NumberValue::F64(n) => write!(f, "{:.3}", n),
+            NumberValue::F64(n) => write!(f, "{}", n),
             NumberValue::U8(n) => write!(f, "{}", n),
             NumberValue::U16(n) => write!(f, "{}", n),
             NumberValue::U32(n) => write!(f, "{}", n),
@@ -54,7 +54,7 @@ impl Display for NumberValue {
             NumberValue::I32(n) => write!(f, "{}", n),
             NumberValue::I64(n) => write!(f, "{}", n),
             NumberValue::ISize(n) => write!(f, "{}", n),
-            NumberValue::F32(n) => write!(f, "{:.3}", n),
+            NumberValue::F32(n) => write!(f, "{}", n),
         }
     }
 }
@@ -69,15 +69,15 @@ impl NumberValue {
             "uint32" => NumberValueLight::U32,
             "uint64" => NumberValueLight::U64,
             "uint128" => NumberValueLight::U128,
-            "usize" => NumberValueLight::USize,
+            "uint" | "usize" => NumberValueLight::USize,
             "int8" => NumberValueLight::I8,
             "int16" => NumberValueLight::I16,
             "int32" => NumberValueLight::I32,
             "int64" => NumberValueLight::I64,
             "int128" => NumberValueLight::I128,
-            "isize" => NumberValueLight::ISize,
+            "int" | "isize" => NumberValueLight::ISize,
             "float" => NumberValueLight::F32,
-            "float64" => NumberValueLight::F64,
+            "double" | "float64" => NumberValueLight::F64,
 
             _ => return None,
         };
@@ -85,6 +85,52 @@ impl NumberValue {
         Some(v)
     }
 
+    pub fn to_f64(&self) -> f64 {
+        match *self {
+            NumberValue::U8(v) => v as f64,
+            NumberValue::U16(v) => v as f64,
+            NumberValue::U32(v) => v as f64,
+            NumberValue::U64(v) => v as f64,
+            NumberValue::U128(v) => v as f64,
+            NumberValue::USize(v) => v as f64,
+            NumberValue::I8(v) => v as f64,
+            NumberValue::I16(v) => v as f64,
+            NumberValue::I32(v) => v as f64,
+            NumberValue::I64(v) => v as f64,
+            NumberValue::I128(v) => v as f64,
+            NumberValue::ISize(v) => v as f64,
+            NumberValue::F32(v) => v as f64,
+            NumberValue::F64(v) => v,
+        }
+    }
+
+    /// None for floats, and the `u128` that don't fit
+    pub fn to_i128(&self) -> Option<i128> {
+        match *self {
+            NumberValue::U8(v) => Some(v.into()),
+            NumberValue::U16(v) => Some(v.into()),
+            NumberValue::U32(v) => Some(v.into()),
+            NumberValue::U64(v) => Some(v.into()),
+            NumberValue::U128(v) => i128::try_from(v).ok(),
+            NumberValue::USize(v) => i128::try_from(v).ok(),
+            NumberValue::I8(v) => Some(v.into()),
+            NumberValue::I16(v) => Some(v.into()),
+            NumberValue::I32(v) => Some(v.into()),
+            NumberValue::I64(v) => Some(v.into()),
+            NumberValue::I128(v) => Some(v),
+            NumberValue::ISize(v) => i128::try_from(v).ok(),
+            NumberValue::F32(_) | NumberValue::F64(_) => None,
+        }
+    }
+
+    /// Compared as integers when both are, so no digit is lost in a f64
+    pub fn compare(&self, bound: f64) -> Ordering {
+        match (self.to_i128(), integer_bound(bound)) {
+            (Some(v), Some(bound)) => v.cmp(&bound),
+            _ => self.to_f64().partial_cmp(&bound).unwrap_or(Ordering::Equal),
+        }
+    }
+
     pub fn into_num(self) -> Num {
         match self {
             NumberValue::U8(v) => Num::U8(v),
@@ -105,37 +151,154 @@ impl NumberValue {
     }
 }
 
+/// The bound as an integer, if it is one. The bounds of the schema are f64, so a bound
+/// above 2^53 may already be rounded, but the value is not
+fn integer_bound(bound: f64) -> Option<i128> {
+    (bound.fract() == 0.0 && bound.abs() < 2f64.powi(127)).then_some(bound as i128)
+}
+
 impl NodeNumber {
     pub fn new(kind: NumberValueLight) -> Self {
         Self {
             value: None,
             value_string: String::new(),
             kind,
+            minimum: None,
+            maximum: None,
+            exclusive_minimum: None,
+            exclusive_maximum: None,
+            multiple_of: None,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        !matches!(self.kind, NumberValueLight::F32 | NumberValueLight::F64)
+    }
+
+    /// Return the range of the value, if it is bounded on both side
+    pub fn range(&self) -> Option<(f64, f64)> {
+        match (self.minimum, self.maximum) {
+            (Some(min), Some(max)) if min <= max => Some((min, max)),
+            _ => None,
+        }
+    }
+
+    pub fn check_constraints(&self, value: &NumberValue) -> Result<(), Violation> {
+        if let Some(min) = self.minimum
+            && value.compare(min).is_lt()
+        {
+            return Err(Violation::new(
+                "minimum",
+                format!("{} is less than the minimum ({})", value, min),
+            ));
+        }
+
+        if let Some(max) = self.maximum
+            && value.compare(max).is_gt()
+        {
+            return Err(Violation::new(
+                "maximum",
+                format!("{} is greater than the maximum ({})", value, max),
+            ));
+        }
+
+        if let Some(min) = self.exclusive_minimum
+            && value.compare(min).is_le()
+        {
+            return Err(Violation::new(
+                "exclusiveMinimum",
+                format!("{} must be greater than {}", value, min),
+            ));
+        }
+
+        if let Some(max) = self.exclusive_maximum
+            && value.compare(max).is_ge()
+        {
+            return Err(Violation::new(
+                "exclusiveMaximum",
+                format!("{} must be less than {}", value, max),
+            ));
+        }
+
+        if let Some(multiple_of) = self.multiple_of {
+            let is_multiple = match (value.to_i128(), integer_bound(multiple_of)) {
+                (Some(v), Some(m)) if m != 0 => v % m == 0,
+                _ => {
+                    let quotient = value.to_f64() / multiple_of;
+
+                    // tolerate float imprecision, e.g 0.3 / 0.1
+                    (quotient - quotient.round()).abs() <= 1e-9 * quotient.abs().max(1.0)
+                }
+            };
+
+            if !is_multiple {
+                return Err(Violation::new(
+                    "multipleOf",
+                    format!("{} is not a multiple of {}", value, multiple_of),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keep the most restrictive constraints of both nodes
+    pub fn merge_constraints(&mut self, other: &NodeNumber) {
+        fn keep(a: Option<f64>, b: Option<f64>, f: fn(f64, f64) -> f64) -> Option<f64> {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(f(a, b)),
+                (a, b) => a.or(b),
+            }
         }
+
+        self.minimum = keep(self.minimum, other.minimum, f64::max);
+        self.maximum = keep(self.maximum, other.maximum, f64::min);
+        self.exclusive_minimum = keep(self.exclusive_minimum, other.exclusive_minimum, f64::max);
+        self.exclusive_maximum = keep(self.exclusive_maximum, other.exclusive_maximum, f64::min);
+        // the values that are a multiple of both
+        self.multiple_of = keep(self.multiple_of, other.multiple_of, lcm);
     }
 
-    pub fn try_from_figment_num(&self, value: figment::value::Num) -> anyhow::Result<NumberValue> {
-        let s = match value {
-            Num::U8(v) => v.to_string(),
-            Num::U16(v) => v.to_string(),
-            Num::U32(v) => v.to_string(),
-            Num::U64(v) => v.to_string(),
-            Num::U128(v) => v.to_string(),
-            Num::USize(v) => v.to_string(),
-            Num::I8(v) => v.to_string(),
-            Num::I16(v) => v.to_string(),
-            Num::I32(v) => v.to_string(),
-            Num::I64(v) => v.to_string(),
-            Num::I128(v) => v.to_string(),
-            Num::ISize(v) => v.to_string(),
-            Num::F32(v) => v.to_string(),
-            Num::F64(v) => v.to_string(),
+    /// Convert without going through a string, so no digit is lost.
+    /// Floats are only accepted for an integer if they have no fractional part.
+    /// The constraints are not checked, so a value out of range can still be loaded
+    /// and reported by the diagnostics.
+    pub fn try_from_figment_num(&self, value: Num) -> anyhow::Result<NumberValue> {
+        let actual = value.to_actual();
+
+        let v = match self.kind {
+            NumberValueLight::U8 if let Some(v) = integer(&actual) => NumberValue::U8(v),
+            NumberValueLight::U16 if let Some(v) = integer(&actual) => NumberValue::U16(v),
+            NumberValueLight::U32 if let Some(v) = integer(&actual) => NumberValue::U32(v),
+            NumberValueLight::U64 if let Some(v) = integer(&actual) => NumberValue::U64(v),
+            NumberValueLight::U128 if let Some(v) = integer(&actual) => NumberValue::U128(v),
+            NumberValueLight::USize if let Some(v) = integer(&actual) => NumberValue::USize(v),
+            NumberValueLight::I8 if let Some(v) = integer(&actual) => NumberValue::I8(v),
+            NumberValueLight::I16 if let Some(v) = integer(&actual) => NumberValue::I16(v),
+            NumberValueLight::I32 if let Some(v) = integer(&actual) => NumberValue::I32(v),
+            NumberValueLight::I64 if let Some(v) = integer(&actual) => NumberValue::I64(v),
+            NumberValueLight::I128 if let Some(v) = integer(&actual) => NumberValue::I128(v),
+            NumberValueLight::ISize if let Some(v) = integer(&actual) => NumberValue::ISize(v),
+            // the nearest f32, like the parser of any format would do
+            NumberValueLight::F32 if let Some(v) = float(&actual) => NumberValue::F32(v as f32),
+            NumberValueLight::F64 if let Some(v) = float(&actual) => NumberValue::F64(v),
+            _ => bail!("{} doesn't fit in {:?}", actual, self.kind),
         };
 
-        self.try_parse_from_str(&s)
+        Ok(v)
     }
 
+    /// Parse the input of the user, which must respect the constraints
     pub fn try_parse_from_str(&self, str: &str) -> anyhow::Result<NumberValue> {
+        let v = self.parse_from_str(str)?;
+
+        self.check_constraints(&v)?;
+
+        Ok(v)
+    }
+
+    /// Like [`Self::try_from_figment_num`], for the formats that only have strings
+    pub fn parse_from_str(&self, str: &str) -> anyhow::Result<NumberValue> {
         let v = match self.kind {
             NumberValueLight::U8 if let Ok(v) = str.parse::<u8>() => NumberValue::U8(v),
             NumberValueLight::U16 if let Ok(v) = str.parse::<u16>() => NumberValue::U16(v),
@@ -157,3 +320,53 @@ impl NodeNumber {
         Ok(v)
     }
 }
+
+/// `multipleOf` is a decimal in practice, so both are scaled to integers
+fn lcm(a: f64, b: f64) -> f64 {
+    fn gcd(a: u128, b: u128) -> u128 {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+
+    let is_integer = |v: f64| (v - v.round()).abs() < 1e-9;
+
+    let scale = (0..=9)
+        .map(|exp| 10f64.powi(exp))
+        .find(|scale| is_integer(a * scale) && is_integer(b * scale))
+        .unwrap_or(1e9);
+
+    let (a_int, b_int) = ((a * scale).round() as u128, (b * scale).round() as u128);
+
+    if a_int == 0 || b_int == 0 {
+        return a.max(b);
+    }
+
+    (a_int / gcd(a_int, b_int) * b_int) as f64 / scale
+}
+
+fn integer<T: TryFrom<u128> + TryFrom<i128>>(actual: &Actual) -> Option<T> {
+    match *actual {
+        Actual::Unsigned(n) => T::try_from(n).ok(),
+        Actual::Signed(n) => T::try_from(n).ok(),
+        // `as` saturates, so the bounds are checked before
+        Actual::Float(f) if f.fract() == 0.0 && f >= 0.0 && f < u128::MAX as f64 => {
+            T::try_from(f as u128).ok()
+        }
+        Actual::Float(f) if f.fract() == 0.0 && f < 0.0 && f >= i128::MIN as f64 => {
+            T::try_from(f as i128).ok()
+        }
+        _ => None,
+    }
+}
+
+fn float(actual: &Actual) -> Option<f64> {
+    match *actual {
+        Actual::Unsigned(n) => Some(n as f64),
+        Actual::Signed(n) => Some(n as f64),
+        Actual::Float(f) => Some(f),
+        _ => None,
+    }
+}
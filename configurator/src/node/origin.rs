@@ -0,0 +1,66 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use figment::{value::Tag, Figment};
+
+/// Where the value of a node comes from.
+/// Only the values of the user layer are written to disk.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Origin {
+    /// No value, or the `default` of the schema
+    #[default]
+    Default,
+    /// A file of `X_CONFIGURATOR_SOURCE_PATHS`
+    System(PathBuf),
+    /// The file of the user
+    User,
+    /// Changed in the UI
+    Edit,
+}
+
+impl Origin {
+    pub fn is_user(&self) -> bool {
+        matches!(self, Origin::User | Origin::Edit)
+    }
+
+    /// Containers take the origin of their most specific child
+    fn rank(&self) -> u8 {
+        match self {
+            Origin::Default => 0,
+            Origin::System(_) => 1,
+            Origin::User => 2,
+            Origin::Edit => 3,
+        }
+    }
+
+    pub(super) fn most_specific<'a>(origins: impl Iterator<Item = &'a Origin>) -> Option<Origin> {
+        origins.max_by_key(|origin| origin.rank()).cloned()
+    }
+
+    /// Figment tags each value with the provider it comes from
+    pub fn from_tag(figment: &Figment, user_path: &Path, tag: Tag) -> Self {
+        let path = figment
+            .get_metadata(tag)
+            .and_then(|metadata| metadata.source.as_ref())
+            .and_then(|source| source.file_path());
+
+        match path {
+            Some(path) if path == user_path => Origin::User,
+            Some(path) => Origin::System(path.to_path_buf()),
+            None => Origin::Default,
+        }
+    }
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origin::Default => write!(f, "Default"),
+            Origin::System(path) => write!(f, "System ({})", path.display()),
+            Origin::User => write!(f, "User"),
+            Origin::Edit => write!(f, "User (not saved)"),
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, bail};
+use schemars::schema::Schema;
+
+use super::{
+    draft::Draft,
+    from_json_schema::{schema_object_to_node, ToSchemaObject},
+    Node, NodeAny, NodeContainer, NodeLazy, Violation,
+};
+
+/// A `$ref` resolved against the document that contains it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    /// None is the root document
+    document: Option<PathBuf>,
+    /// Json pointer inside the document
+    pointer: String,
+}
+
+impl Display for Reference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(document) = &self.document {
+            write!(f, "{}", document.display())?;
+        }
+        write!(f, "#{}", self.pointer)
+    }
+}
+
+/// Hold the documents needed to resolve `$ref`
+#[derive(Debug)]
+pub struct Resolver {
+    root: json::Value,
+    draft: Draft,
+    /// Used to resolve references to other files
+    base_dir: Option<PathBuf>,
+    /// Other files, loaded on demand
+    documents: Mutex<HashMap<PathBuf, (Arc<json::Value>, Draft)>>,
+}
+
+impl Resolver {
+    pub fn new(root: json::Value, base_dir: Option<PathBuf>) -> Self {
+        Self {
+            draft: Draft::detect(&root),
+            root,
+            base_dir,
+            documents: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `current` is the document where the `$ref` was found
+    fn reference(&self, reference: &str, current: Option<&PathBuf>) -> anyhow::Result<Reference> {
+        let (path, fragment) = reference.split_once('#').unwrap_or((reference, ""));
+
+        let document = if path.is_empty() {
+            current.cloned()
+        } else if path.contains("://") {
+            bail!("remote references are not supported: {reference}");
+        } else {
+            let base_dir = match current {
+                Some(current) => current.parent().map(|p| p.to_path_buf()),
+                None => self.base_dir.clone(),
+            };
+
+            match base_dir {
+                Some(base_dir) => Some(base_dir.join(path)),
+                None => bail!("no base directory to resolve {reference}"),
+            }
+        };
+
+        if !fragment.is_empty() && !fragment.starts_with('/') {
+            bail!("anchors are not supported: {reference}");
+        }
+
+        Ok(Reference {
+            document,
+            pointer: fragment.replace("%25", "%"),
+        })
+    }
+
+    fn document(&self, path: &PathBuf) -> anyhow::Result<(Arc<json::Value>, Draft)> {
+        let mut documents = self.documents.lock().unwrap();
+
+        if let Some(document) = documents.get(path) {
+            return Ok(document.clone());
+        }
+
+        let content = fs::read_to_string(path)?;
+        let document: json::Value = json::from_str(&content)?;
+        let draft = Draft::detect(&document);
+
+        let document = (Arc::new(document), draft);
+        documents.insert(path.clone(), document.clone());
+        Ok(document)
+    }
+
+    /// The schema is normalized according to the draft of its document
+    fn schema(&self, reference: &Reference) -> anyhow::Result<Schema> {
+        let other;
+
+        let (document, draft) = match &reference.document {
+            Some(path) => {
+                other = self.document(path)?;
+                (&*other.0, other.1)
+            }
+            None => (&self.root, self.draft),
+        };
+
+        let mut value = document
+            .pointer(&reference.pointer)
+            .ok_or_else(|| anyhow!("can't resolve {reference}"))?
+            .clone();
+
+        draft.normalize(&mut value);
+
+        Ok(json::from_value(value)?)
+    }
+}
+
+/// State of a conversion from a schema
+#[derive(Debug)]
+pub struct Scope<'a> {
+    resolver: &'a Arc<Resolver>,
+    /// References being expanded, used to detect recursion
+    stack: RefCell<Vec<Reference>>,
+    /// References whose target is missing or invalid. The conversion continues to report the
+    /// first one. The references that are not supported are ignored on their node instead
+    errors: RefCell<Vec<anyhow::Error>>,
+}
+
+impl<'a> Scope<'a> {
+    pub fn new(resolver: &'a Arc<Resolver>) -> Self {
+        Self {
+            resolver,
+            stack: RefCell::new(Vec::new()),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Fail if a reference couldn't be resolved during the conversion
+    pub fn check(&self) -> anyhow::Result<()> {
+        match self.errors.borrow_mut().drain(..).next() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Convert the schema pointed by `reference`.
+    /// A reference that is already being expanded produce a lazy node.
+    pub fn resolve(&self, reference: &str) -> Option<NodeContainer> {
+        let current = self.stack.borrow().last().and_then(|r| r.document.clone());
+
+        let reference = match self.resolver.reference(reference, current.as_ref()) {
+            Ok(reference) => reference,
+            // the rest of the schema can still be used
+            Err(e) => {
+                let mut node = NodeContainer::from_node(Node::Any(NodeAny::new()));
+                node.ignored.push(Violation::new("$ref", e.to_string()));
+                return Some(node);
+            }
+        };
+
+        if self.stack.borrow().contains(&reference) {
+            return Some(NodeContainer::from_node(Node::Lazy(NodeLazy {
+                references: vec![reference],
+                resolver: self.resolver.clone(),
+            })));
+        }
+
+        self.expand(reference)
+    }
+
+    pub fn expand_root(&self) -> Option<NodeContainer> {
+        self.expand(Reference {
+            document: None,
+            pointer: String::new(),
+        })
+    }
+
+    fn expand(&self, reference: Reference) -> Option<NodeContainer> {
+        let schema = match self.resolver.schema(&reference) {
+            Ok(schema) => schema,
+            Err(e) => {
+                self.errors
+                    .borrow_mut()
+                    .push(e.context(format!("invalid schema at {reference}")));
+                return Some(NodeContainer::from_node(Node::Any(NodeAny::new())));
+            }
+        };
+
+        self.stack.borrow_mut().push(reference);
+        let node = schema_object_to_node("ref", self, &schema.to_object());
+        self.stack.borrow_mut().pop();
+
+        node
+    }
+}
+
+impl NodeLazy {
+    /// Expand one level of the recursive definition.
+    /// Nested references to the same definition stay lazy.
+    pub fn expand(&self) -> anyhow::Result<NodeContainer> {
+        self.references.iter().try_fold(
+            NodeContainer::from_node(Node::Any(NodeAny::new())),
+            |acc, reference| {
+                let scope = Scope::new(&self.resolver);
+                let node = scope.expand(reference.clone());
+                scope.check()?;
+                let node = node.ok_or_else(|| anyhow!("no value can match {reference}"))?;
+
+                acc.merge(&node)
+                    .ok_or_else(|| anyhow!("incompatible definitions {:?}", self.references))
+            },
+        )
+    }
+
+    pub fn merge(&self, other: &NodeLazy) -> NodeLazy {
+        let mut references = self.references.clone();
+
+        for reference in &other.references {
+            if !references.contains(reference) {
+                references.push(reference.clone());
+            }
+        }
+
+        NodeLazy {
+            references,
+            resolver: self.resolver.clone(),
+        }
+    }
+}
+
+impl NodeContainer {
+    /// Replace a lazy node by its definition
+    pub fn expand(&mut self) -> anyhow::Result<()> {
+        if let Node::Lazy(node_lazy) = &self.node {
+            let expanded = node_lazy.expand()?;
+
+            self.node = expanded.node;
+            self.conditions.extend(expanded.conditions);
+            self.ignored.extend(expanded.ignored);
+            self.default = self.default.take().or(expanded.default);
+            self.title = self.title.take().or(expanded.title);
+            self.desc = self.desc.take().or(expanded.desc);
+        }
+
+        Ok(())
+    }
+
+    /// Clone of this node, with one level of lazy expansion
+    pub fn expanded(&self) -> anyhow::Result<NodeContainer> {
+        let mut node = self.clone();
+        node.expand()?;
+        Ok(node)
+    }
+}
@@ -4,11 +4,134 @@ use std::{
 };
 
 use serde::{
-    ser::{SerializeMap, SerializeStruct, SerializeTuple},
+    ser::{Error, SerializeMap, SerializeSeq, SerializeStruct, SerializeTuple},
     Serialize,
 };
 
-use super::NodeContainer;
+use super::{EnumRepr, Node, NodeArrayTemplate, NodeContainer, NodeEnum, NodeObject, NumberValue};
+
+/// Serde needs `&'static str` for field and variant names.
+/// Only the names of the schema are leaked, once, so the keys of the user can't grow the
+/// memory. The others are written as the keys of a map.
+static NAMES: LazyLock<Mutex<HashSet<&'static str>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// A property or a constant of the schema
+pub(super) fn register_name(name: &str) {
+    let mut names = NAMES.lock().unwrap();
+
+    if !names.contains(name) {
+        names.insert(Box::leak(name.into()));
+    }
+}
+
+fn static_name(name: &str) -> Option<&'static str> {
+    NAMES.lock().unwrap().get(name).copied()
+}
+
+impl NodeContainer {
+    /// Same rule as `value`: nodes that can't produce a value are skipped
+    pub(super) fn is_serializable(&self) -> bool {
+        match &self.node {
+            Node::Null | Node::Object(_) | Node::Value(_) => true,
+            Node::Bool(node_bool) => node_bool.value.is_some(),
+            Node::String(node_string) => node_string.value.is_some(),
+            Node::Number(node_number) => node_number.value.is_some(),
+            Node::Enum(node_enum) => node_enum
+                .selected()
+                .is_some_and(|(_, node)| node.is_serializable()),
+            Node::Array(node_array) => node_array
+                .values
+                .as_ref()
+                .is_some_and(|values| values.iter().all(|n| n.is_serializable())),
+            Node::Any(node_any) => node_any.value.is_some(),
+            Node::Lazy(_) => false,
+        }
+    }
+}
+
+impl Serialize for NumberValue {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match *self {
+            NumberValue::U8(n) => ser.serialize_u8(n),
+            NumberValue::U16(n) => ser.serialize_u16(n),
+            NumberValue::U32(n) => ser.serialize_u32(n),
+            NumberValue::U64(n) => ser.serialize_u64(n),
+            NumberValue::U128(n) => ser.serialize_u128(n),
+            NumberValue::USize(n) => match usize::BITS {
+                32 => ser.serialize_u32(n as u32),
+                _ => ser.serialize_u64(n as u64),
+            },
+            NumberValue::I8(n) => ser.serialize_i8(n),
+            NumberValue::I16(n) => ser.serialize_i16(n),
+            NumberValue::I32(n) => ser.serialize_i32(n),
+            NumberValue::I64(n) => ser.serialize_i64(n),
+            NumberValue::I128(n) => ser.serialize_i128(n),
+            NumberValue::ISize(n) => match isize::BITS {
+                32 => ser.serialize_i32(n as i32),
+                _ => ser.serialize_i64(n as i64),
+            },
+            NumberValue::F32(n) => ser.serialize_f32(n),
+            NumberValue::F64(n) => ser.serialize_f64(n),
+        }
+    }
+}
+
+/// `{ "Variant": ... }`, with nothing else allowed
+pub(super) fn external_tag(node_object: &NodeObject) -> Option<(&String, &NodeContainer)> {
+    if node_object.nodes.len() != 1 || !node_object.deny_unknown || node_object.can_add() {
+        return None;
+    }
+
+    node_object
+        .nodes
+        .first()
+        .filter(|(key, _)| node_object.is_required(key))
+}
+
+/// `T | null`
+pub(super) fn is_option(node_enum: &NodeEnum) -> bool {
+    node_enum.nodes.len() == 2 && node_enum.nodes.iter().any(|n| matches!(n.node, Node::Null))
+}
+
+/// How the selected variant is written:
+/// - `T | null` is an `Option`
+/// - for externally tagged enums, a string constant is a unit variant
+///   and `{ "Variant": ... }` a newtype variant
+///
+/// Variants of the other representations (see `EnumRepr`) already
+/// contain their tag, so they are written as is.
+fn serialize_enum<S>(node_enum: &NodeEnum, ser: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let (pos, variant) = node_enum
+        .selected()
+        .ok_or_else(|| S::Error::custom("no variant is selected"))?;
+
+    let is_option = is_option(node_enum);
+    let is_external = node_enum.repr == EnumRepr::External;
+
+    match &variant.node {
+        Node::Null if is_option => ser.serialize_none(),
+        _ if is_option => ser.serialize_some(variant),
+        Node::Value(node_value)
+            if is_external && let Some(name) = node_value.value.as_str().and_then(static_name) =>
+        {
+            ser.serialize_unit_variant("", pos as u32, name)
+        }
+        Node::Object(node_object)
+            if is_external
+                && let Some((name, inner)) = external_tag(node_object)
+                && let Some(name) = static_name(name) =>
+        {
+            ser.serialize_newtype_variant("", pos as u32, name, inner)
+        }
+        _ => variant.serialize(ser),
+    }
+}
 
 impl Serialize for NodeContainer {
     fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
@@ -16,25 +139,81 @@ impl Serialize for NodeContainer {
         S: serde::Serializer,
     {
         match &self.node {
-            super::Node::Null => todo!(),
-            super::Node::Bool(node_bool) => ser.serialize_bool(node_bool.value.unwrap()),
-            super::Node::String(node_string) => todo!(),
-            super::Node::Number(node_number) => todo!(),
-            super::Node::Object(node_object) => {
-                let mut map = ser.serialize_struct("", node_object.nodes.len())?;
+            Node::Null => ser.serialize_unit(),
+            Node::Bool(node_bool) => match node_bool.value {
+                Some(value) => ser.serialize_bool(value),
+                None => Err(S::Error::custom("no bool value is defined")),
+            },
+            Node::String(node_string) => match &node_string.value {
+                Some(value) => ser.serialize_str(value),
+                None => Err(S::Error::custom("no string value is defined")),
+            },
+            Node::Number(node_number) => match &node_number.value {
+                Some(value) => value.serialize(ser),
+                None => Err(S::Error::custom("no number value is defined")),
+            },
+            Node::Object(node_object) => {
+                let hidden = self.hidden_keys();
+
+                let nodes: Vec<_> = node_object
+                    .nodes
+                    .iter()
+                    .filter(|(key, node)| !hidden.contains(key.as_str()) && node.is_serializable())
+                    .collect();
 
-                for (key, val) in &node_object.nodes {
-                    let key: &'static str = Box::leak(key.to_string().into_boxed_str());
+                let fields: Option<Vec<_>> = nodes
+                    .iter()
+                    .map(|(key, node)| static_name(key).map(|key| (key, node)))
+                    .collect();
 
-                    map.serialize_field(key, val)?;
+                match fields {
+                    // keys added by the user are only known at runtime
+                    Some(fields) if !node_object.can_add() => {
+                        let mut map = ser.serialize_struct("", fields.len())?;
+                        for (key, node) in fields {
+                            map.serialize_field(key, node)?;
+                        }
+                        map.end()
+                    }
+                    _ => {
+                        let mut map = ser.serialize_map(Some(nodes.len()))?;
+                        for (key, node) in nodes {
+                            map.serialize_entry(key, node)?;
+                        }
+                        map.end()
+                    }
                 }
+            }
+            Node::Enum(node_enum) => serialize_enum(node_enum, ser),
+            Node::Array(node_array) => {
+                let values = node_array
+                    .values
+                    .as_ref()
+                    .ok_or_else(|| S::Error::custom("no array value is defined"))?;
 
-                map.end()
+                match &node_array.template {
+                    NodeArrayTemplate::FirstN(..) => {
+                        let mut tuple = ser.serialize_tuple(values.len())?;
+                        for node in values {
+                            tuple.serialize_element(node)?;
+                        }
+                        tuple.end()
+                    }
+                    NodeArrayTemplate::All(_) => {
+                        let mut seq = ser.serialize_seq(Some(values.len()))?;
+                        for node in values {
+                            seq.serialize_element(node)?;
+                        }
+                        seq.end()
+                    }
+                }
             }
-            super::Node::Enum(node_enum) => todo!(),
-            super::Node::Array(node_array) => todo!(),
-            super::Node::Value(node_value) => todo!(),
-            super::Node::Any => todo!(),
+            Node::Value(node_value) => node_value.value.serialize(ser),
+            Node::Any(node_any) => match &node_any.value {
+                Some(value) => value.serialize(ser),
+                None => Err(S::Error::custom("no value is defined")),
+            },
+            Node::Lazy(_) => Err(S::Error::custom("the definition is not loaded")),
         }
     }
 }
@@ -47,9 +226,9 @@ mod test {
     use super::NodeContainer;
     use figment::{providers, value::Tag, Figment, Profile};
     use schemars::{schema_for, JsonSchema};
-    use serde::{Deserialize, Serialize};
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-    fn test_schema<S: JsonSchema + Default + Serialize>() {
+    fn tree_with_default<S: JsonSchema + Default + Serialize>() -> (S, NodeContainer) {
         let schema = schema_for!(S);
 
         let mut tree = NodeContainer::from_json_schema(&schema);
@@ -60,15 +239,102 @@ mod test {
 
         tree.apply_figment(&figment).unwrap();
 
+        (config1, tree)
+    }
+
+    /// The tree must deserialize into the original type
+    fn test_schema<S: JsonSchema + Default + Serialize + DeserializeOwned>() {
+        let (config1, tree) = tree_with_default::<S>();
+
+        let value = json::to_value(&tree).unwrap();
+
+        let config2: S = json::from_value(value.clone()).unwrap();
+
+        assert_eq!(json::to_value(&config1).unwrap(), value);
+        assert_eq!(json::to_value(&config2).unwrap(), value);
+    }
+
+    /// RON distinguish structs, maps, tuples and enums
+    fn test_schema_ron<S: JsonSchema + Default + Serialize + DeserializeOwned>() {
+        test_schema::<S>();
+
+        let (config1, tree) = tree_with_default::<S>();
+
         let str1 = ron::ser::to_string_pretty(&config1, ron::ser::PrettyConfig::new()).unwrap();
 
         let str2 = ron::ser::to_string_pretty(&tree, ron::ser::PrettyConfig::new()).unwrap();
 
         assert_eq!(str1, str2);
+
+        let _: S = ron::from_str(&str2).unwrap();
     }
 
     #[test]
     fn test_bool_ron() {
-        test_schema::<TestBool>();
+        test_schema_ron::<TestBool>();
+    }
+
+    #[test]
+    fn test_string_ron() {
+        test_schema_ron::<TestString>();
+    }
+
+    #[test]
+    fn test_number_ron() {
+        test_schema_ron::<TestNumber>();
+        test_schema_ron::<TestFloat>();
+    }
+
+    #[test]
+    fn test_enum_ron() {
+        test_schema_ron::<TestEnumSimple>();
+        test_schema_ron::<TestEnumComplex>();
+    }
+
+    #[test]
+    fn test_option_ron() {
+        test_schema_ron::<TestOption>();
+        test_schema_ron::<TestOptionComplex>();
+        test_schema_ron::<TestOptionEnum>();
+    }
+
+    #[test]
+    fn test_array_ron() {
+        test_schema_ron::<TestTuple>();
+        test_schema_ron::<TestVec>();
+        test_schema_ron::<TestHashMap>();
+    }
+
+    #[test]
+    fn test_rec() {
+        test_schema_ron::<Rec>();
+    }
+
+    // struct variants can't be told apart from newtype variants,
+    // so only the self-describing formats round-trip
+    #[test]
+    fn test_very_complex() {
+        test_schema::<TestVeryComplex>();
+        test_schema::<Complex>();
+    }
+
+    #[test]
+    fn test_user_keys_not_leaked() {
+        let (config1, tree) = tree_with_default::<TestVeryComplex>();
+
+        assert_eq!(
+            json::to_value(&tree).unwrap(),
+            json::to_value(&config1).unwrap()
+        );
+
+        // a key of a `HashMap`
+        assert!(super::static_name("x").is_some());
+        assert!(super::static_name("hello").is_none());
+    }
+
+    // RON has no notion of tagged enums
+    #[test]
+    fn test_enum_tagged() {
+        test_schema::<TestEnumTagged>();
     }
 }
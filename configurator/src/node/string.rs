@@ -0,0 +1,114 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use super::{NodeString, Violation};
+
+static DATE_TIME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\d{4}-\d{2}-\d{2}[Tt ]\d{2}:\d{2}:\d{2}(\.\d+)?([Zz]|[+-]\d{2}:\d{2})$").unwrap()
+});
+
+static DATE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d{4}-\d{2}-\d{2}$").unwrap());
+
+static TIME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\d{2}:\d{2}:\d{2}(\.\d+)?([Zz]|[+-]\d{2}:\d{2})?$").unwrap());
+
+static URI: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*:[^\s]*$").unwrap());
+
+static EMAIL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[^\s@]+@[^\s@]+\.[^\s@]+$").unwrap());
+
+static COLOR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^#([0-9A-Fa-f]{3}|[0-9A-Fa-f]{4}|[0-9A-Fa-f]{6}|[0-9A-Fa-f]{8})$").unwrap()
+});
+
+impl NodeString {
+    pub fn new() -> Self {
+        Self {
+            value: None,
+            min_length: None,
+            max_length: None,
+            patterns: Vec::new(),
+            format: None,
+        }
+    }
+
+    /// Return an error describing why this value is rejected
+    pub fn validate(&self, value: &str) -> Result<(), Violation> {
+        // json schema count unicode code points
+        let len = value.chars().count();
+
+        if let Some(min_length) = self.min_length
+            && len < min_length as usize
+        {
+            return Err(Violation::new(
+                "minLength",
+                format!("must contain at least {} characters", min_length),
+            ));
+        }
+
+        if let Some(max_length) = self.max_length
+            && len > max_length as usize
+        {
+            return Err(Violation::new(
+                "maxLength",
+                format!("must contain at most {} characters", max_length),
+            ));
+        }
+
+        for pattern in &self.patterns {
+            if !pattern.is_match(value) {
+                return Err(Violation::new(
+                    "pattern",
+                    format!("must match the pattern {}", pattern.as_str()),
+                ));
+            }
+        }
+
+        if let Some(format) = &self.format {
+            let regex = match format.as_str() {
+                "date-time" => &DATE_TIME,
+                "date" => &DATE,
+                "time" => &TIME,
+                "uri" => &URI,
+                "email" => &EMAIL,
+                "color" => &COLOR,
+                // unknown formats are only annotations
+                _ => return Ok(()),
+            };
+
+            if !regex.is_match(value) {
+                return Err(Violation::new(
+                    "format",
+                    format!("is not a valid {}", format),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keep the most restrictive constraints of both nodes
+    pub fn merge_constraints(&mut self, other: &NodeString) {
+        self.min_length = match (self.min_length, other.min_length) {
+            (Some(a), Some(b)) => Some(std::cmp::max(a, b)),
+            (a, b) => a.or(b),
+        };
+
+        self.max_length = match (self.max_length, other.max_length) {
+            (Some(a), Some(b)) => Some(std::cmp::min(a, b)),
+            (a, b) => a.or(b),
+        };
+
+        for pattern in &other.patterns {
+            if !self.patterns.iter().any(|p| p.as_str() == pattern.as_str()) {
+                self.patterns.push(pattern.clone());
+            }
+        }
+
+        if self.format.is_none() {
+            self.format.clone_from(&other.format);
+        }
+    }
+}
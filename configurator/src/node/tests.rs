@@ -1,10 +1,17 @@
-use crate::{node::NodeContainer, test_common::*};
+use crate::{
+    node::{
+        data_path::DataPathType, from_json_schema::ToSchemaObject, EnumRepr, Lenient, Node,
+        NodeAny, NodeArrayTemplate, NodeContainer, NodeError, Origin, Severity,
+    },
+    test_common::*,
+    utils::data_default_profile_figment,
+};
 
 use std::collections::HashMap;
 
 use cosmic::iced_futures::backend::default;
 use figment::{
-    providers,
+    providers::{self, Format},
     value::{Tag, Value},
     Figment, Profile,
 };
@@ -80,6 +87,11 @@ fn test_option_complex() {
     test_schema::<TestOptionComplex>(true);
 }
 
+#[test]
+fn test_option_enum() {
+    test_schema::<TestOptionEnum>(true);
+}
+
 #[test]
 fn test_tuple() {
     test_schema::<TestTuple>(true);
@@ -99,3 +111,1443 @@ fn test_hash_map() {
 fn test_very_complex() {
     test_schema::<TestVeryComplex>(true);
 }
+
+#[test]
+fn test_number_kinds() {
+    test_schema::<TestNumberKinds>(true);
+
+    let mut tree = NodeContainer::from_json_schema(&schema_for!(TestNumberKinds));
+    let config = TestNumberKinds::default();
+    tree.apply_figment(
+        &Figment::new().join(providers::Serialized::from(&config, Profile::Default)),
+    )
+    .unwrap();
+
+    // the text shown in the UI gives back the same value
+    let Node::Object(node_object) = &tree.node else {
+        panic!("not an object");
+    };
+    for (key, node) in &node_object.nodes {
+        let Node::Number(node_number) = &node.node else {
+            panic!("{key} is not a number");
+        };
+        let value = node_number.value.clone().unwrap();
+        let parsed = node_number
+            .try_parse_from_str(&node_number.value_string)
+            .unwrap();
+        assert_eq!(value.into_num(), parsed.into_num(), "{key}");
+    }
+    assert_eq!(
+        tree.get_at(&[DataPathType::Name("f64".into())])
+            .unwrap()
+            .node
+            .unwrap_number_ref()
+            .value_string,
+        "0.30000000000000004"
+    );
+}
+
+#[test]
+fn test_enum_tagged() {
+    test_schema::<TestEnumTagged>(true);
+}
+
+fn tree_from_json_schema(schema: json::Value) -> NodeContainer {
+    NodeContainer::from_json_value(schema, None).unwrap()
+}
+
+#[test]
+fn test_all_of_merge_objects() {
+    let schema = json::json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "allOf": [
+            {
+                "type": "object",
+                "properties": {
+                    "a": { "type": "integer", "format": "int32", "default": 1 }
+                }
+            },
+            {
+                "description": "flattened",
+                "properties": {
+                    "b": { "type": "string", "default": "b" }
+                }
+            }
+        ]
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    assert_eq!(tree.desc.as_deref(), Some("flattened"));
+
+    let node_object = tree.node.clone().unwrap_object();
+    assert_eq!(
+        node_object.nodes.keys().collect::<Vec<_>>(),
+        vec![&"a".to_string(), &"b".to_string()]
+    );
+
+    let value = json::json!({ "a": 5, "b": "hello" });
+
+    let figment = Figment::new().join(providers::Serialized::from(&value, Profile::Default));
+    tree.apply_figment(&figment).unwrap();
+
+    assert!(tree.is_valid());
+    assert_eq!(
+        json::to_value(tree.to_value(&Tag::Default).unwrap()).unwrap(),
+        value
+    );
+}
+
+#[test]
+fn test_all_of_merge_enum() {
+    let schema = json::json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "allOf": [
+            {
+                "anyOf": [
+                    { "type": "string" },
+                    { "type": "integer", "format": "int32" }
+                ]
+            },
+            { "type": "integer", "minimum": 0 }
+        ]
+    });
+
+    let tree = tree_from_json_schema(schema);
+
+    let node_enum = tree.node.unwrap_enum();
+    assert_eq!(node_enum.nodes.len(), 1);
+    assert!(matches!(node_enum.nodes[0].node, Node::Number(_)));
+}
+
+#[test]
+fn test_all_of_ref_keep_metadata() {
+    let schema = json::json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "type": "object",
+        "properties": {
+            "x": {
+                "description": "documented",
+                "allOf": [
+                    { "$ref": "#/definitions/Inner" },
+                    { "properties": { "c": { "type": "boolean" } } }
+                ]
+            }
+        },
+        "definitions": {
+            "Inner": {
+                "description": "inner",
+                "type": "object",
+                "properties": {
+                    "a": { "type": "integer" }
+                }
+            }
+        }
+    });
+
+    let tree = tree_from_json_schema(schema);
+
+    let node_object = tree.node.unwrap_object();
+    let x = node_object.nodes.get("x").unwrap();
+
+    assert_eq!(x.desc.as_deref(), Some("documented"));
+    assert_eq!(x.node.clone().unwrap_object().nodes.len(), 2);
+}
+
+#[test]
+fn test_unresolvable_ref() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "a": { "$ref": "#/definitions/Missing" }
+        },
+        "definitions": {}
+    });
+    assert!(NodeContainer::from_json_value(schema, None).is_err());
+
+    // not a schema
+    let schema = json::json!({
+        "$ref": "#/definitions/A",
+        "definitions": { "A": 5 }
+    });
+    assert!(NodeContainer::from_json_value(schema, None).is_err());
+}
+
+#[test]
+fn test_unsupported_ref() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "a": { "$ref": "http://example.com/schema.json" },
+            "b": { "$ref": "#name" },
+            "c": { "type": "integer" }
+        },
+        "required": ["a", "b"],
+        "$defs": {
+            "B": { "$anchor": "name", "type": "string" }
+        }
+    });
+
+    let tree = tree_from_json_schema(schema);
+
+    let keywords: Vec<_> = tree
+        .diagnostics()
+        .into_iter()
+        .filter(|d| d.severity == Severity::Warning)
+        .map(|d| (d.data_path, d.keyword))
+        .collect();
+    assert_eq!(
+        keywords,
+        vec![
+            (vec![DataPathType::Name("a".into())], "$ref"),
+            (vec![DataPathType::Name("b".into())], "$ref"),
+        ]
+    );
+
+    let node_object = tree.node.unwrap_object();
+    assert!(matches!(node_object.nodes["a"].node, Node::Any(_)));
+    assert!(matches!(node_object.nodes["c"].node, Node::Number(_)));
+}
+
+#[test]
+fn test_all_of_incompatible() {
+    let schema = json::json!({
+        "allOf": [
+            { "type": "string" },
+            { "type": "boolean" }
+        ]
+    });
+
+    assert!(NodeContainer::from_json_value(schema, None).is_err());
+}
+
+#[test]
+fn test_enum_product() {
+    // what schemars generate for an inlined `Option<EnumSimple>`
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "x": {
+                "type": ["string", "null"],
+                "enum": ["A", "B", null]
+            }
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let node_enum = tree
+        .node
+        .clone()
+        .unwrap_object()
+        .nodes
+        .get("x")
+        .unwrap()
+        .node
+        .clone()
+        .unwrap_enum();
+
+    assert_eq!(node_enum.nodes.len(), 3);
+
+    for value in [json::json!({ "x": "B" }), json::json!({ "x": null })] {
+        let figment = Figment::new().join(providers::Serialized::from(&value, Profile::Default));
+        tree.apply_figment(&figment).unwrap();
+
+        assert_eq!(
+            json::to_value(tree.to_value(&Tag::Default).unwrap()).unwrap(),
+            value
+        );
+    }
+}
+
+#[test]
+fn test_enum_product_ref() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "x": {
+                "$ref": "#/definitions/Choice",
+                "oneOf": [
+                    { "type": "string" },
+                    { "type": "integer" }
+                ]
+            }
+        },
+        "definitions": {
+            "Choice": {
+                "oneOf": [
+                    { "enum": ["A"] },
+                    { "type": "boolean" }
+                ]
+            }
+        }
+    });
+
+    let tree = tree_from_json_schema(schema);
+
+    let x = tree.node.unwrap_object().nodes.get("x").unwrap().clone();
+
+    // only ("A", string) is compatible
+    assert_eq!(x.name().as_deref(), Some("A"));
+}
+
+#[test]
+fn test_number_constraints_integer() {
+    // 2^53 + 1 is 2^53 as a f64
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "x": { "type": "integer", "format": "int64", "maximum": 9007199254740992_i64 },
+            "y": { "type": "integer", "format": "int64", "multipleOf": 2 }
+        }
+    });
+
+    let tree = tree_from_json_schema(schema);
+    let node_object = tree.node.unwrap_object();
+
+    let x = node_object.nodes["x"].node.clone().unwrap_number();
+    assert!(x.try_parse_from_str("9007199254740992").is_ok());
+    assert!(x.try_parse_from_str("9007199254740993").is_err());
+
+    let y = node_object.nodes["y"].node.clone().unwrap_number();
+    assert!(y.try_parse_from_str("9007199254740994").is_ok());
+    assert!(y.try_parse_from_str("9007199254740993").is_err());
+}
+
+#[test]
+fn test_number_constraints() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "x": {
+                "type": "integer",
+                "format": "uint32",
+                "minimum": 2,
+                "maximum": 10,
+                "multipleOf": 2
+            },
+            "y": {
+                "allOf": [
+                    { "type": "number", "exclusiveMinimum": 0 },
+                    { "type": "number", "exclusiveMaximum": 1 }
+                ]
+            },
+            "z": {
+                "allOf": [
+                    { "type": "number", "multipleOf": 0.5 },
+                    { "type": "number", "multipleOf": 0.2 }
+                ]
+            }
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let node_object = tree.node.clone().unwrap_object();
+    let x = node_object
+        .nodes
+        .get("x")
+        .unwrap()
+        .node
+        .clone()
+        .unwrap_number();
+    let y = node_object
+        .nodes
+        .get("y")
+        .unwrap()
+        .node
+        .clone()
+        .unwrap_number();
+
+    assert_eq!(x.range(), Some((2., 10.)));
+    assert!(x.try_parse_from_str("4").is_ok());
+    assert!(x.try_parse_from_str("0").is_err());
+    assert!(x.try_parse_from_str("12").is_err());
+    assert!(x.try_parse_from_str("5").is_err());
+
+    assert_eq!(y.range(), None);
+    assert!(y.try_parse_from_str("0.5").is_ok());
+    assert!(y.try_parse_from_str("0").is_err());
+    assert!(y.try_parse_from_str("1").is_err());
+
+    let z = node_object
+        .nodes
+        .get("z")
+        .unwrap()
+        .node
+        .clone()
+        .unwrap_number();
+    assert_eq!(z.multiple_of, Some(1.0));
+
+    let value = json::json!({ "x": 4, "y": 0.5 });
+    let figment = Figment::new().join(providers::Serialized::from(&value, Profile::Default));
+    tree.apply_figment(&figment).unwrap();
+    assert!(tree.is_valid());
+
+    // kept, but reported
+    let value = json::json!({ "x": 11, "y": 0.5 });
+    let figment = Figment::new().join(providers::Serialized::from(&value, Profile::Default));
+    tree.apply_figment(&figment).unwrap();
+    assert!(!tree.is_valid());
+    assert_eq!(tree.diagnostics()[0].keyword, "maximum");
+    assert_eq!(
+        json::to_value(tree.to_value(&Tag::Default).unwrap()).unwrap(),
+        value
+    );
+}
+
+#[test]
+fn test_string_constraints() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "name": {
+                "type": "string",
+                "minLength": 2,
+                "maxLength": 4,
+                "pattern": "^[a-z]+$"
+            },
+            "mail": { "type": "string", "format": "email" },
+            "date": { "type": "string", "format": "date-time" },
+            "color": { "type": "string", "format": "color" },
+            "url": { "type": ["string", "null"], "format": "uri" }
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let node_object = tree.node.clone().unwrap_object();
+    let get = |name: &str| {
+        node_object
+            .nodes
+            .get(name)
+            .unwrap()
+            .node
+            .clone()
+            .unwrap_string()
+    };
+
+    let name = get("name");
+    assert!(name.validate("abc").is_ok());
+    assert!(name.validate("a").is_err());
+    assert!(name.validate("abcde").is_err());
+    assert!(name.validate("aB").is_err());
+
+    let mail = get("mail");
+    assert!(mail.validate("user@example.com").is_ok());
+    assert!(mail.validate("user.example.com").is_err());
+
+    let date = get("date");
+    assert!(date.validate("2024-10-17T12:00:00Z").is_ok());
+    assert!(date.validate("2024-10-17").is_err());
+
+    let color = get("color");
+    assert!(color.validate("#ff00AA").is_ok());
+    assert!(color.validate("red").is_err());
+
+    let url = node_object
+        .nodes
+        .get("url")
+        .unwrap()
+        .node
+        .clone()
+        .unwrap_enum();
+    let url = url.nodes[0].node.clone().unwrap_string();
+    assert!(url.validate("https://example.com").is_ok());
+    assert!(url.validate("not a uri").is_err());
+
+    let value = json::json!({
+        "name": "abc",
+        "mail": "a@b.c",
+        "date": "2024-10-17T12:00:00+02:00",
+        "color": "#fff",
+        "url": null
+    });
+    let figment = Figment::new().join(providers::Serialized::from(&value, Profile::Default));
+    tree.apply_figment(&figment).unwrap();
+    assert!(tree.is_valid());
+
+    let value = json::json!({
+        "name": "ABC",
+        "mail": "a@b.c",
+        "date": "2024-10-17T12:00:00+02:00",
+        "color": "#fff",
+        "url": null
+    });
+    let figment = Figment::new().join(providers::Serialized::from(&value, Profile::Default));
+    tree.apply_figment(&figment).unwrap();
+    assert!(!tree.is_valid());
+}
+
+#[test]
+fn test_array_constraints() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "x": {
+                "type": "array",
+                "items": { "type": "integer" },
+                "minItems": 1,
+                "maxItems": 3,
+                "uniqueItems": true
+            }
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let mut apply = |value: json::Value| {
+        let figment = Figment::new().join(providers::Serialized::from(&value, Profile::Default));
+        tree.apply_figment(&figment).unwrap();
+        let node_array = tree.node.clone().unwrap_object().nodes["x"]
+            .node
+            .clone()
+            .unwrap_array();
+        (tree.is_valid(), node_array)
+    };
+
+    let (is_valid, node_array) = apply(json::json!({ "x": [1] }));
+    assert!(is_valid);
+    assert!(node_array.can_add());
+    assert!(!node_array.can_remove());
+
+    let (is_valid, node_array) = apply(json::json!({ "x": [1, 2, 3] }));
+    assert!(is_valid);
+    assert!(!node_array.can_add());
+    assert!(node_array.can_remove());
+
+    let (is_valid, node_array) = apply(json::json!({ "x": [1, 1] }));
+    assert!(!is_valid);
+    assert!(node_array.check_constraints().is_err());
+
+    let (is_valid, _) = apply(json::json!({ "x": [1, 2, 3, 4] }));
+    assert!(!is_valid);
+
+    let (is_valid, _) = apply(json::json!({ "x": [] }));
+    assert!(!is_valid);
+
+    // an item without value is reported, instead of silently not writing the array
+    apply(json::json!({ "x": [1] }));
+    let x = tree.get_at_mut(&[DataPathType::Name("x".into())]).unwrap();
+    let node_array = x.node.unwrap_array_mut();
+    let item = node_array.template(None).unwrap();
+    node_array.values.as_mut().unwrap().push(item);
+
+    assert!(tree
+        .diagnostics()
+        .iter()
+        .any(|d| d.keyword == "items" && d.data_path == [DataPathType::Name("x".into())]));
+}
+
+#[test]
+fn test_tuple_can_add() {
+    let schema = schema_for!(TestTuple);
+    let mut tree = NodeContainer::from_json_schema(&schema);
+
+    let figment = Figment::new().join(providers::Serialized::from(
+        TestTuple::default(),
+        Profile::Default,
+    ));
+    tree.apply_figment(&figment).unwrap();
+
+    let node_array = tree.node.unwrap_object().nodes["x"]
+        .node
+        .clone()
+        .unwrap_array();
+
+    assert!(!node_array.can_add());
+}
+
+#[test]
+fn test_required_and_additional_properties() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "name": { "type": "string" },
+            "size": { "type": "integer" }
+        },
+        "required": ["name"],
+        "additionalProperties": { "type": "boolean" }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let node_object = tree.node.clone().unwrap_object();
+    assert!(node_object.is_required("name"));
+    assert!(!node_object.is_required("size"));
+    assert!(node_object.template.is_some());
+
+    let mut apply = |value: json::Value| {
+        let figment = Figment::new().join(providers::Serialized::from(&value, Profile::Default));
+        tree.remove_value_rec();
+        tree.apply_figment(&figment).map(|_| tree.is_valid())
+    };
+
+    // optional field can be missing
+    assert!(apply(json::json!({ "name": "a" })).unwrap());
+    // required field is missing
+    assert!(!apply(json::json!({ "size": 1 })).unwrap());
+    // extra keys go to the template
+    assert!(apply(json::json!({ "name": "a", "other": true })).unwrap());
+    assert!(apply(json::json!({ "name": "a", "other": 1 })).is_err());
+}
+
+#[test]
+fn test_deny_additional_properties() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "name": { "type": "string" }
+        },
+        "additionalProperties": false
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let figment = Figment::new().join(providers::Serialized::from(
+        json::json!({ "name": "a", "other": true }),
+        Profile::Default,
+    ));
+
+    let err = tree.apply_figment(&figment).unwrap_err();
+    assert!(err.to_string().contains("other"));
+}
+
+#[test]
+fn test_pattern_properties() {
+    let schema = json::json!({
+        "type": "object",
+        "patternProperties": {
+            "^DP-[0-9]+$": { "type": "integer" },
+            "^HDMI-": { "type": "boolean" }
+        },
+        "propertyNames": { "type": "string", "maxLength": 8 },
+        "additionalProperties": false
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let node_object = tree.node.clone().unwrap_object();
+    assert!(node_object.can_add());
+    assert!(matches!(
+        node_object.template("DP-1").unwrap().node,
+        Node::Number(_)
+    ));
+    assert!(matches!(
+        node_object.template("HDMI-A-1").unwrap().node,
+        Node::Bool(_)
+    ));
+    assert!(node_object.template("eDP").is_none());
+
+    assert!(node_object.check_new_key("DP-2").is_ok());
+    assert!(node_object.check_new_key("eDP").is_err());
+    assert!(node_object.check_new_key("HDMI-A-1-long").is_err());
+
+    let mut apply = |value: json::Value| {
+        let figment = Figment::new().join(providers::Serialized::from(&value, Profile::Default));
+        tree.remove_value_rec();
+        tree.apply_figment(&figment)
+    };
+
+    assert!(apply(json::json!({ "DP-1": 1, "HDMI-A-1": true })).is_ok());
+    assert!(apply(json::json!({ "DP-1": true })).is_err());
+    assert!(apply(json::json!({ "eDP": 1 })).is_err());
+
+    apply(json::json!({ "DP-1": 1 })).unwrap();
+    let node_object = tree.node.unwrap_object();
+    assert!(node_object.renamed_node("DP-1", "DP-2").is_ok());
+    assert!(node_object.renamed_node("DP-1", "HDMI-A-2").is_err());
+}
+
+#[test]
+fn test_if_then_else() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "autohide": { "type": "boolean" }
+        },
+        "required": ["autohide"],
+        "if": {
+            "properties": { "autohide": { "const": true } }
+        },
+        "then": {
+            "properties": { "wait_time": { "type": "integer" } },
+            "required": ["wait_time"]
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let mut apply = |value: json::Value| {
+        let figment = Figment::new().join(providers::Serialized::from(&value, Profile::Default));
+        tree.remove_value_rec();
+        tree.apply_figment(&figment).unwrap();
+        (tree.is_valid(), tree.hidden_keys().contains("wait_time"))
+    };
+
+    assert_eq!(apply(json::json!({ "autohide": false })), (true, true));
+    assert_eq!(apply(json::json!({ "autohide": true })), (false, false));
+    assert_eq!(
+        apply(json::json!({ "autohide": true, "wait_time": 5 })),
+        (true, false)
+    );
+}
+
+#[test]
+fn test_not_and_dependencies() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "size": { "type": "integer", "not": { "const": 0 } },
+            "width": { "type": "integer" },
+            "height": { "type": "integer" }
+        },
+        "dependentRequired": {
+            "width": ["height"]
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let mut apply = |value: json::Value| {
+        let figment = Figment::new().join(providers::Serialized::from(&value, Profile::Default));
+        tree.remove_value_rec();
+        tree.apply_figment(&figment).unwrap();
+        tree.is_valid()
+    };
+
+    assert!(apply(json::json!({ "size": 1 })));
+    assert!(!apply(json::json!({ "size": 0 })));
+    assert!(!apply(json::json!({ "width": 1 })));
+    assert!(apply(json::json!({ "width": 1, "height": 1 })));
+
+    // a false schema accepts nothing
+    let schema = json::json!({ "type": "object", "properties": { "x": false } });
+    assert!(NodeContainer::from_json_value(schema, None).is_err());
+}
+
+#[test]
+fn test_rec() {
+    test_schema::<Rec>(true);
+}
+
+#[test]
+fn test_rec_is_lazy() {
+    let schema = schema_for!(Rec);
+    let tree = NodeContainer::from_json_schema(&schema);
+
+    fn variant(node: &NodeContainer, f: fn(&Node) -> bool) -> NodeContainer {
+        let y = node.node.clone().unwrap_object().nodes["y"]
+            .node
+            .clone()
+            .unwrap_enum();
+
+        y.nodes.into_iter().find(|n| f(&n.node)).unwrap()
+    }
+
+    // the root is not a definition, so the first level is expanded
+    let rec = variant(&tree, |n| matches!(n, Node::Object(_)));
+    let rec = variant(&rec, |n| matches!(n, Node::Lazy(_)));
+
+    let rec = rec.expanded().unwrap().node.unwrap_object();
+    assert!(rec.nodes.contains_key("x"));
+    assert!(rec.nodes.contains_key("y"));
+}
+
+#[test]
+fn test_ref_pointers() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "a": { "$ref": "#/$defs/A" },
+            "b": { "$ref": "#/$defs/A/properties/b" }
+        },
+        "$defs": {
+            "A": {
+                "type": "object",
+                "properties": {
+                    "b": { "type": "string", "maxLength": 2 }
+                }
+            }
+        }
+    });
+
+    let tree = tree_from_json_schema(schema);
+    let node_object = tree.node.unwrap_object();
+
+    assert!(matches!(node_object.nodes["a"].node, Node::Object(_)));
+
+    let b = node_object.nodes["b"].node.clone().unwrap_string();
+    assert_eq!(b.max_length, Some(2));
+}
+
+#[test]
+fn test_ref_other_file() {
+    let dir = std::env::temp_dir().join("configurator_test_ref_other_file");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let other = json::json!({
+        "definitions": {
+            "Size": { "type": "integer", "minimum": 1 }
+        }
+    });
+    std::fs::write(dir.join("other.json"), other.to_string()).unwrap();
+
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "size": { "$ref": "other.json#/definitions/Size" }
+        }
+    });
+
+    let tree = NodeContainer::from_json_value(schema, Some(dir)).unwrap();
+    let size = tree.node.unwrap_object().nodes["size"]
+        .node
+        .clone()
+        .unwrap_number();
+
+    assert_eq!(size.minimum, Some(1.));
+}
+
+#[test]
+fn test_draft_2020_12() {
+    let schema = json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "type": "object",
+        "properties": {
+            "pos": {
+                "type": "array",
+                "prefixItems": [
+                    { "type": "integer" },
+                    { "$ref": "#/$defs/Name" }
+                ],
+                "items": false
+            }
+        },
+        "unevaluatedProperties": false,
+        "$defs": {
+            "Name": { "type": "string", "const": "a" }
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let node_object = tree.node.clone().unwrap_object();
+    assert!(node_object.deny_unknown);
+
+    let pos = node_object.nodes["pos"].node.clone().unwrap_array();
+    let NodeArrayTemplate::FirstN(items, None) = pos.template else {
+        panic!("prefixItems should be a closed tuple");
+    };
+    assert!(matches!(items[0].node, Node::Number(_)));
+    assert!(matches!(items[1].node, Node::Value(_)));
+
+    let figment = Figment::new().join(providers::Serialized::from(
+        json::json!({ "pos": [1, "a"] }),
+        Profile::Default,
+    ));
+    tree.apply_figment(&figment).unwrap();
+    assert!(tree.is_valid());
+
+    let figment = Figment::new().join(providers::Serialized::from(
+        json::json!({ "pos": [1, "b"] }),
+        Profile::Default,
+    ));
+    assert!(tree.apply_figment(&figment).is_err());
+}
+
+#[test]
+fn test_draft_2020_12_unevaluated() {
+    // the properties of the subschemas can't be told apart once merged
+    let schema = json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "type": "object",
+        "allOf": [{ "$ref": "#/$defs/Base" }],
+        "properties": {
+            "b": {
+                "type": "array",
+                "unevaluatedItems": { "type": "integer" }
+            }
+        },
+        "unevaluatedProperties": false,
+        "$defs": {
+            "Base": { "properties": { "a": { "type": "string" } } }
+        }
+    });
+
+    let tree = tree_from_json_schema(schema);
+
+    let node_object = tree.node.clone().unwrap_object();
+    assert!(!node_object.deny_unknown);
+    assert!(node_object.nodes.contains_key("a"));
+    assert_eq!(tree.ignored.len(), 1);
+    assert_eq!(tree.ignored[0].keyword, "unevaluatedProperties");
+
+    // without subschemas, all the items are unevaluated
+    let b = node_object.nodes["b"].node.clone().unwrap_array();
+    let NodeArrayTemplate::All(template) = b.template else {
+        panic!("unevaluatedItems should apply to all the items");
+    };
+    assert!(matches!(template.node, Node::Number(_)));
+
+    let diagnostics = tree.diagnostics();
+    assert!(diagnostics
+        .iter()
+        .any(|d| d.keyword == "unevaluatedProperties" && d.severity == Severity::Warning));
+}
+
+#[test]
+fn test_dynamic_ref() {
+    let schema = json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "type": "object",
+        "properties": {
+            "a": { "$dynamicRef": "#/$defs/A" },
+            "b": { "$dynamicRef": "#meta" }
+        },
+        "$defs": {
+            "A": { "type": "string" }
+        }
+    });
+
+    let tree = tree_from_json_schema(schema);
+    let node_object = tree.node.unwrap_object();
+
+    assert!(matches!(node_object.nodes["a"].node, Node::String(_)));
+
+    let b = &node_object.nodes["b"];
+    assert!(matches!(b.node, Node::Any(_)));
+    assert_eq!(b.ignored[0].keyword, "$dynamicRef");
+}
+
+#[test]
+fn test_additional_items() {
+    let schema = json::json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "type": "object",
+        "properties": {
+            "open": {
+                "type": "array",
+                "items": [{ "type": "integer" }]
+            },
+            "rest": {
+                "type": "array",
+                "items": [{ "type": "integer" }],
+                "additionalItems": { "type": "string" }
+            }
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let value = json::json!({ "open": [1, true, "a"], "rest": [1, "a", "b"] });
+    let figment = Figment::new().join(providers::Serialized::from(&value, Profile::Default));
+    tree.apply_figment(&figment).unwrap();
+    assert!(tree.is_valid());
+    assert_eq!(
+        json::to_value(tree.to_value(&Tag::Default).unwrap()).unwrap(),
+        value
+    );
+
+    let rest = tree.get_at(&[DataPathType::Name("rest".into())]).unwrap();
+    let node_array = rest.node.unwrap_array_ref();
+    assert!(node_array.can_add());
+    assert!(matches!(
+        node_array.template(None).unwrap().node,
+        Node::String(_)
+    ));
+
+    let value = json::json!({ "open": [1], "rest": [1, 2] });
+    let figment = Figment::new().join(providers::Serialized::from(&value, Profile::Default));
+    assert!(tree.apply_figment(&figment).is_err());
+}
+
+#[test]
+fn test_draft4_exclusive_bounds() {
+    let schema = json::json!({
+        "$schema": "http://json-schema.org/draft-04/schema#",
+        "type": "object",
+        "properties": {
+            "x": { "type": "number", "minimum": 0, "exclusiveMinimum": true },
+            "y": { "type": "number", "maximum": 1, "exclusiveMaximum": false }
+        }
+    });
+
+    let tree = tree_from_json_schema(schema);
+
+    let node_object = tree.node.unwrap_object();
+    let x = node_object.nodes["x"].node.clone().unwrap_number();
+    assert_eq!(x.minimum, None);
+    assert_eq!(x.exclusive_minimum, Some(0.));
+
+    let y = node_object.nodes["y"].node.clone().unwrap_number();
+    assert_eq!(y.maximum, Some(1.));
+    assert_eq!(y.exclusive_maximum, None);
+}
+
+#[test]
+fn test_stale_data_path() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "a": { "type": "object", "properties": { "b": { "type": "string" } } },
+            "c": { "type": "array", "items": { "type": "string" } }
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    // nothing is marked when the path is stale
+    let path = [DataPathType::Name("c".into()), DataPathType::Indice(3)];
+    assert!(tree.set_edited(&path).is_err());
+    assert_eq!(tree.origin, Origin::Default);
+    assert_eq!(tree.get_at(&path[..1]).unwrap().origin, Origin::Default);
+
+    let path = [
+        DataPathType::Name("a".into()),
+        DataPathType::Name("b".into()),
+    ];
+    assert!(tree.get_at(&path).is_ok());
+    assert!(tree.set_edited(&path).is_ok());
+
+    let path = [DataPathType::Name("a".into()), DataPathType::Indice(0)];
+    assert_eq!(
+        tree.get_at(&path).unwrap_err(),
+        NodeError::NotFound {
+            data_path: path.to_vec(),
+            kind: "object"
+        }
+    );
+
+    // the array has no value yet
+    let path = [DataPathType::Name("c".into()), DataPathType::Indice(3)];
+    assert!(tree.get_at_mut(&path).is_err());
+    assert!(tree.set_edited(&path).is_err());
+
+    let path = [DataPathType::Name("x".into())];
+    assert!(tree.get_at(&path).is_err());
+}
+
+#[test]
+fn test_diagnostics() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "a": { "type": "string", "minLength": 3 },
+            "b": { "type": "array", "items": { "type": "integer" }, "maxItems": 1 },
+            "c": { "type": "boolean" }
+        },
+        "required": ["a", "d"]
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let config = json::json!({ "a": "x", "b": [1, 2] });
+    tree.apply_figment(
+        &Figment::new().merge(providers::Serialized::from(config, Profile::Default)),
+    )
+    .unwrap();
+
+    let diagnostics = tree.diagnostics();
+
+    let keywords: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.data_path.clone(), d.keyword))
+        .collect();
+
+    assert_eq!(
+        keywords,
+        vec![
+            (vec![], "required"),
+            (vec![DataPathType::Name("a".into())], "minLength"),
+            (vec![DataPathType::Name("b".into())], "maxItems"),
+        ]
+    );
+    assert!(diagnostics.iter().all(|d| d.severity == Severity::Error));
+    assert!(!tree.is_valid());
+}
+
+#[test]
+fn test_any() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "a": {},
+            "b": { "type": "array" }
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let config = json::json!({ "a": { "x": [1, "y", null] }, "b": [true, 1.5] });
+    tree.apply_figment(
+        &Figment::new().merge(providers::Serialized::from(&config, Profile::Default)),
+    )
+    .unwrap();
+
+    assert!(tree.is_valid());
+
+    let value = tree.value(&Tag::Default).unwrap();
+    assert_eq!(json::to_value(&value).unwrap(), config);
+
+    let a = tree.node.unwrap_object_ref().nodes["a"]
+        .node
+        .unwrap_any_ref();
+    assert_eq!(
+        json::to_value(NodeAny::parse(&a.value_string).unwrap()).unwrap(),
+        config["a"]
+    );
+
+    // RON is accepted too
+    let value = NodeAny::parse("{\"x\": [1, \"y\"], \"z\": Some(true)}").unwrap();
+    assert_eq!(value.find_ref("z").and_then(|v| v.to_bool()), Some(true));
+    assert!(NodeAny::parse("{ x: ").is_err());
+}
+
+#[test]
+fn test_enum_repr() {
+    fn repr(schema: schemars::schema::RootSchema) -> EnumRepr {
+        match NodeContainer::from_json_schema(&schema).node {
+            Node::Enum(node_enum) => node_enum.repr,
+            node => panic!("{} is not an enum", node.kind()),
+        }
+    }
+
+    assert_eq!(repr(schema_for!(EnumComplex)), EnumRepr::External);
+    assert_eq!(
+        repr(schema_for!(EnumInternal)),
+        EnumRepr::Internal { tag: "type".into() }
+    );
+    assert_eq!(
+        repr(schema_for!(EnumAdjacent)),
+        EnumRepr::Adjacent {
+            tag: "t".into(),
+            content: "c".into()
+        }
+    );
+    assert_eq!(repr(schema_for!(EnumUntagged)), EnumRepr::Untagged);
+
+    // a single field in total, like the content of an adjacently tagged enum
+    #[derive(JsonSchema)]
+    #[serde(tag = "type")]
+    #[allow(dead_code)]
+    enum E {
+        A,
+        B { a: i32 },
+    }
+    assert_eq!(
+        repr(schema_for!(E)),
+        EnumRepr::Internal { tag: "type".into() }
+    );
+
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "x": {
+                "oneOf": [
+                    {
+                        "type": "object",
+                        "properties": { "type": { "const": "A" } },
+                        "required": ["type"]
+                    },
+                    {
+                        "type": "object",
+                        "properties": {
+                            "type": { "type": "string", "enum": ["B"] },
+                            "a": { "type": "integer" }
+                        },
+                        "required": ["type", "a"]
+                    }
+                ]
+            }
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let config = json::json!({ "x": { "type": "B", "a": 1 } });
+    tree.apply_figment(
+        &Figment::new().join(providers::Serialized::from(&config, Profile::Default)),
+    )
+    .unwrap();
+
+    let path = [DataPathType::Name("x".into())];
+    let Node::Enum(node_enum) = &mut tree.get_at_mut(&path).unwrap().node else {
+        panic!("x should be an enum");
+    };
+    assert_eq!(node_enum.value, Some(1));
+
+    // same as selecting the variant in the UI
+    node_enum.value = Some(0);
+    node_enum.nodes[0].origin = Origin::Edit;
+    tree.set_edited(&path).unwrap();
+
+    assert_eq!(
+        tree.to_value(&Tag::Default).unwrap(),
+        Value::serialize(json::json!({ "x": { "type": "A" } })).unwrap()
+    );
+}
+
+#[test]
+fn test_origin() {
+    let dir = std::env::temp_dir().join("configurator_test_origin");
+    std::fs::create_dir_all(&dir).unwrap();
+    let system_path = dir.join("system.json");
+    let user_path = dir.join("user.json");
+    std::fs::write(
+        &system_path,
+        r#"{ "a": 1, "b": 2, "c": { "x": 1, "y": 2 } }"#,
+    )
+    .unwrap();
+    std::fs::write(&user_path, r#"{ "b": 3, "c": { "y": 3 } }"#).unwrap();
+
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "a": { "type": "integer" },
+            "b": { "type": "integer" },
+            "c": {
+                "type": "object",
+                "properties": { "x": { "type": "integer" }, "y": { "type": "integer" } }
+            },
+            "d": { "type": "integer", "default": 4 }
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let figment = Figment::new()
+        .merge(providers::Json::file(&system_path))
+        .merge(providers::Json::file(&user_path));
+    tree.apply_layers(
+        &figment,
+        &|tag| Origin::from_tag(&figment, &user_path, tag),
+        Lenient::default(),
+    )
+    .unwrap();
+
+    let origin = |path: &[&str]| {
+        let path: Vec<_> = path
+            .iter()
+            .map(|name| DataPathType::Name(name.to_string()))
+            .collect();
+        tree.get_at(&path).unwrap().origin.clone()
+    };
+
+    assert_eq!(origin(&["a"]), Origin::System(system_path.clone()));
+    assert_eq!(origin(&["b"]), Origin::User);
+    assert_eq!(origin(&["c"]), Origin::User);
+    assert_eq!(origin(&["c", "x"]), Origin::System(system_path.clone()));
+    assert_eq!(origin(&["d"]), Origin::Default);
+
+    // the values of the system stay in the system file
+    assert_eq!(
+        json::to_value(tree.to_value(&Tag::Default).unwrap()).unwrap(),
+        json::json!({ "b": 3, "c": { "y": 3 } })
+    );
+
+    tree.set_edited(&[DataPathType::Name("a".into())]).unwrap();
+    assert_eq!(
+        json::to_value(tree.to_value(&Tag::Default).unwrap()).unwrap(),
+        json::json!({ "a": 1, "b": 3, "c": { "y": 3 } })
+    );
+}
+
+#[test]
+fn test_edit_system_array() {
+    let dir = std::env::temp_dir().join("configurator_test_edit_system_array");
+    std::fs::create_dir_all(&dir).unwrap();
+    let system_path = dir.join("system.json");
+    let user_path = dir.join("user.json");
+    std::fs::write(&system_path, r#"{ "a": [1, 2, 3], "b": [{ "x": 1 }] }"#).unwrap();
+    std::fs::write(&user_path, r#"{}"#).unwrap();
+
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "a": { "type": "array", "items": { "type": "integer" } },
+            "b": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": { "x": { "type": "integer" }, "y": { "type": "integer" } }
+                }
+            }
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let figment = Figment::new()
+        .merge(providers::Json::file(&system_path))
+        .merge(providers::Json::file(&user_path));
+    tree.apply_layers(
+        &figment,
+        &|tag| Origin::from_tag(&figment, &user_path, tag),
+        Lenient::default(),
+    )
+    .unwrap();
+
+    // same as editing an item in the UI
+    let path = [DataPathType::Name("a".into()), DataPathType::Indice(1)];
+    tree.get_at_mut(&path)
+        .unwrap()
+        .apply_value(Value::from(5), Origin::Edit)
+        .unwrap();
+    tree.set_edited(&path).unwrap();
+
+    let path = [
+        DataPathType::Name("b".into()),
+        DataPathType::Indice(0),
+        DataPathType::Name("y".into()),
+    ];
+    tree.get_at_mut(&path)
+        .unwrap()
+        .apply_value(Value::from(2), Origin::Edit)
+        .unwrap();
+    tree.set_edited(&path).unwrap();
+
+    // the whole arrays, or the items of the system would be lost
+    assert_eq!(
+        json::to_value(tree.to_value(&Tag::Default).unwrap()).unwrap(),
+        json::json!({ "a": [1, 5, 3], "b": [{ "x": 1, "y": 2 }] })
+    );
+}
+
+#[test]
+fn test_reset_to_inherited() {
+    let dir = std::env::temp_dir().join("configurator_test_reset_to_inherited");
+    std::fs::create_dir_all(&dir).unwrap();
+    let system_path = dir.join("system.json");
+    let user_path = dir.join("user.json");
+    std::fs::write(&system_path, r#"{ "a": 1, "c": { "x": 1 } }"#).unwrap();
+    std::fs::write(&user_path, r#"{ "a": 2, "b": 3, "c": { "x": 2 } }"#).unwrap();
+
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "a": { "type": "integer" },
+            "b": { "type": "integer", "default": 4 },
+            "c": {
+                "type": "object",
+                "properties": { "x": { "type": "integer" } }
+            }
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let figment = Figment::new()
+        .merge(providers::Json::file(&system_path))
+        .merge(providers::Json::file(&user_path));
+    tree.apply_layers(
+        &figment,
+        &|tag| Origin::from_tag(&figment, &user_path, tag),
+        Lenient::default(),
+    )
+    .unwrap();
+
+    let system = Figment::new().merge(providers::Json::file(&system_path));
+    let layer = Value::Dict(Tag::Default, data_default_profile_figment(&system).unwrap());
+    let origin_of = |tag| Origin::from_tag(&system, &user_path, tag);
+
+    let path = |path: &[&str]| -> Vec<_> {
+        path.iter()
+            .map(|name| DataPathType::Name(name.to_string()))
+            .collect()
+    };
+
+    // the system value is used
+    tree.reset_to_inherited(&path(&["a"]), Some(&layer), &origin_of, Lenient::default())
+        .unwrap();
+    let node = tree.get_at(&path(&["a"])).unwrap();
+    assert_eq!(node.origin, Origin::System(system_path.clone()));
+    assert_eq!(node.value(&Tag::Default).and_then(|v| v.to_i128()), Some(1));
+
+    // only the default is left
+    tree.reset_to_inherited(&path(&["b"]), Some(&layer), &origin_of, Lenient::default())
+        .unwrap();
+    let node = tree.get_at(&path(&["b"])).unwrap();
+    assert_eq!(node.origin, Origin::Default);
+    assert_eq!(node.value(&Tag::Default).and_then(|v| v.to_i128()), Some(4));
+
+    assert_eq!(
+        json::to_value(tree.to_value(&Tag::Default).unwrap()).unwrap(),
+        json::json!({ "c": { "x": 2 } })
+    );
+
+    // the parent has no user value anymore
+    tree.reset_to_inherited(
+        &path(&["c", "x"]),
+        Some(&layer),
+        &origin_of,
+        Lenient::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        tree.get_at(&path(&["c"])).unwrap().origin,
+        Origin::System(system_path.clone())
+    );
+    assert_eq!(tree.to_value(&Tag::Default), None);
+}
+
+#[test]
+fn test_single_value_in_array() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "x": { "type": "array", "items": { "type": "string" } }
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let figment = Figment::new().merge(providers::Serialized::defaults(json::json!({ "x": "a" })));
+
+    // only for the formats that need it
+    assert!(tree.apply_figment(&figment).is_err());
+
+    let lenient = Lenient {
+        value_to_array: true,
+        ..Default::default()
+    };
+    tree.apply_layers(&figment, &|_| Origin::User, lenient)
+        .unwrap();
+
+    assert_eq!(
+        json::to_value(tree.to_value(&Tag::Default).unwrap()).unwrap(),
+        json::json!({ "x": ["a"] })
+    );
+}
+
+#[test]
+fn test_untyped_values() {
+    let schema = json::json!({
+        "type": "object",
+        "properties": {
+            "enabled": { "type": "boolean" },
+            "port": { "type": "integer" }
+        }
+    });
+
+    let mut tree = tree_from_json_schema(schema);
+
+    let figment = Figment::new().merge(providers::Serialized::defaults(
+        json::json!({ "enabled": "yes", "port": "80" }),
+    ));
+
+    // only for the formats that need it
+    assert!(tree.apply_figment(&figment).is_err());
+
+    let lenient = Lenient {
+        string_to_scalar: true,
+        ..Default::default()
+    };
+    tree.apply_layers(&figment, &|_| Origin::User, lenient)
+        .unwrap();
+
+    assert_eq!(
+        json::to_value(tree.to_value(&Tag::Default).unwrap()).unwrap(),
+        json::json!({ "enabled": true, "port": 80 })
+    );
+}
@@ -8,9 +8,20 @@ use crate::node::Node;
 use super::{from_json_schema::json_value_to_figment_value, NodeContainer, NumberValue};
 
 impl NodeContainer {
-    // todo: return a result with info about the node missing a value
+    /// Value of the user layer, e.g. what should be written to disk.
+    /// See `diagnostics` to know why a value is missing.
     pub fn to_value(&self, tag: &Tag) -> Option<Value> {
-        if !self.modified {
+        self.to_value_impl(tag, true, true)
+    }
+
+    /// Current value of the node, whatever its origin
+    pub fn value(&self, tag: &Tag) -> Option<Value> {
+        self.to_value_impl(tag, false, false)
+    }
+
+    /// `written` removes what must not be written, like inactive conditional fields
+    fn to_value_impl(&self, tag: &Tag, only_user: bool, written: bool) -> Option<Value> {
+        if only_user && !self.origin.is_user() {
             return None;
         }
 
@@ -28,27 +39,52 @@ impl NodeContainer {
             Node::Object(node_object) => {
                 let mut dict = Dict::new();
 
-                for (key, node) in &node_object.nodes {
-                    if let Some(value) = node.to_value(tag) {
+                // the values of inactive conditional fields are not written
+                let hidden = if written {
+                    self.hidden_keys()
+                } else {
+                    Default::default()
+                };
+
+                for (key, node) in node_object
+                    .nodes
+                    .iter()
+                    .filter(|(key, _)| !hidden.contains(key.as_str()))
+                {
+                    if let Some(value) = node.to_value_impl(tag, only_user, written) {
                         dict.insert(key.clone(), value);
                     }
                 }
                 Some(Value::Dict(*tag, dict))
             }
-            Node::Enum(node_enum) => node_enum.value.and_then(|pos| {
-                node_enum.nodes[pos].to_value(tag)
+            Node::Enum(node_enum) => node_enum.selected().and_then(|(_, node)| {
+                let mut value = node.to_value_impl(tag, only_user, written)?;
+
+                // the tag is a constant, so it is not edited when the variant is selected
+                if let Some(key) = node_enum.repr.tag()
+                    && let Value::Dict(_, dict) = &mut value
+                    && !dict.contains_key(key)
+                    && let Node::Object(node_object) = &node.node
+                    && let Some(name) = node_object.nodes.get(key).and_then(|n| n.value(tag))
+                {
+                    dict.insert(key.to_string(), name);
+                }
 
-                // Value::Dict(tag.clone(), Dict::new());
-                // todo!()
+                Some(value)
             }),
-            Node::Array(node_array) => node_array.values.as_ref().map(|values| {
-                Value::Array(
-                    *tag,
-                    values.iter().map(|n| n.to_value(tag).unwrap()).collect(),
-                )
+            // figment replaces a whole array, so an edited array is written with the items
+            // that come from other layers
+            Node::Array(node_array) => node_array.values.as_ref().and_then(|values| {
+                values
+                    .iter()
+                    .map(|n| n.to_value_impl(tag, false, written))
+                    .collect::<Option<Vec<_>>>()
+                    .map(|values| Value::Array(*tag, values))
             }),
             Node::Value(node_value) => Some(json_value_to_figment_value(&node_value.value)),
-            Node::Any => todo!(),
+            Node::Any(node_any) => node_any.value.clone(),
+            // not expanded, so no value
+            Node::Lazy(node_lazy) => None,
         }
     }
 }
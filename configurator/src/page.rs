@@ -22,7 +22,11 @@ use crate::{
     app::{self, Dialog},
     config::Config,
     message::{ChangeMsg, PageMsg},
-    node::{data_path::DataPath, Node, NodeContainer, NumberValue},
+    node::{
+        data_path::{DataPath, DataPathType},
+        Diagnostic, Node, NodeAny, NodeContainer, NodeError, NumberValue, Origin,
+    },
+    utils::data_default_profile_figment,
 };
 
 use configurator_utils::ConfigFormat;
@@ -42,7 +46,16 @@ pub struct Page {
     pub full_config: Figment,
 
     pub tree: NodeContainer,
+    /// The user layer as the tree knows it, after the last reload or write.
+    /// Only these keys can be removed from the file
+    pub tracked: Option<Value>,
+    /// The value of the tree without the user layer: the system config and the defaults
+    pub inherited: Option<Value>,
     pub data_path: DataPath,
+    /// Last error that happened while applying a change
+    pub error: Option<String>,
+    /// Problems of the current tree. The config is not written while it contains errors
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 pub fn create_pages(config: &Config) -> impl Iterator<Item = Page> + use<'_> {
@@ -70,7 +83,7 @@ pub fn create_pages(config: &Config) -> impl Iterator<Item = Page> + use<'_> {
                 let appid = appid_from_schema_path(file.path());
 
                 if !config.masked.contains(&appid) {
-                    Some(Page::from_str(&appid, content).unwrap())
+                    Some(Page::from_str(&appid, content, None).unwrap())
                 } else {
                     None
                 }
@@ -106,7 +119,11 @@ pub fn create_pages(config: &Config) -> impl Iterator<Item = Page> + use<'_> {
 
             if !config.masked.contains(&appid) {
                 match fs::read_to_string(&schema_path) {
-                    Ok(content) => match Page::from_str(&appid, &content) {
+                    Ok(content) => match Page::from_str(
+                        &appid,
+                        &content,
+                        schema_path.parent().map(Path::to_path_buf),
+                    ) {
                         Ok(page) => Some(page),
                         Err(e) => {
                             error!("{}", e);
@@ -134,8 +151,9 @@ fn appid_from_schema_path(schema_path: &Path) -> String {
 
 impl Page {
     // need &str for appid: https://github.com/tokio-rs/tracing/issues/1181
+    /// `base_dir` is used to resolve references to other schema files
     #[instrument(skip(content))]
-    fn from_str(appid: &str, content: &str) -> anyhow::Result<Self> {
+    fn from_str(appid: &str, content: &str, base_dir: Option<PathBuf>) -> anyhow::Result<Self> {
         let json_value = json::Value::from_str(content)?;
 
         let Some(json_obj) = json_value.as_object() else {
@@ -190,7 +208,7 @@ impl Page {
         }
 
         info!("start generating node from schema");
-        let tree = NodeContainer::from_json_schema(&json::from_value(json_value)?);
+        let tree = NodeContainer::from_json_value(json_value, base_dir)?;
 
         let title = appid.split('.').last().unwrap().to_string();
 
@@ -201,7 +219,11 @@ impl Page {
             user_config: Figment::new(),
             full_config: Figment::new(),
             tree,
+            tracked: None,
+            inherited: None,
             data_path: DataPath::new(),
+            error: None,
+            diagnostics: Vec::new(),
             source_paths,
             source_home_path,
             write_path,
@@ -241,21 +263,57 @@ impl Page {
 
         self.tree.remove_value_rec();
 
-        self.tree.apply_figment(&self.full_config)?;
+        let (full_config, user_path) = (&self.full_config, &self.source_home_path);
+        self.tree.apply_layers(
+            full_config,
+            &|tag| Origin::from_tag(full_config, user_path, tag),
+            crate::providers::lenient(&self.format),
+        )?;
+
+        self.tracked = self.tree.to_value(&Tag::Default);
+
+        let mut inherited = self.tree.clone();
+        inherited.remove_value_rec();
+        let (system_config, user_path) = (&self.system_config, &self.source_home_path);
+        inherited.apply_layers(
+            system_config,
+            &|tag| Origin::from_tag(system_config, user_path, tag),
+            crate::providers::lenient(&self.format),
+        )?;
+        self.inherited = inherited.value(&Tag::Default);
 
         self.data_path.sanitize_path(&self.tree);
 
+        self.diagnostics = self.tree.diagnostics();
+
         Ok(())
     }
 
-    pub fn write(&self) -> anyhow::Result<()> {
-        match self.tree.to_value(&Tag::Default) {
-            Some(value) => {
-                crate::providers::write(&self.write_path, &self.format, &value)?;
-            }
-            None => bail!("no value to write"),
+    /// The first problem that prevents writing the config
+    pub fn first_error(&self) -> Option<&Diagnostic> {
+        self.diagnostics.iter().find(|d| d.is_error())
+    }
+
+    /// Only the user layer is written, so updates of the system files still apply
+    pub fn write(&mut self) -> anyhow::Result<()> {
+        if let Some(diagnostic) = self.first_error() {
+            bail!("the configuration is not valid: {diagnostic}");
         }
 
+        // everything is inherited
+        let value = self
+            .tree
+            .to_value(&Tag::Default)
+            .unwrap_or_else(|| Value::Dict(Tag::Default, Dict::new()));
+
+        let context = crate::providers::WriteContext {
+            tracked: self.tracked.as_ref(),
+            inherited: self.inherited.as_ref(),
+        };
+        crate::providers::write(&self.write_path, &self.format, &value, context)?;
+
+        self.tracked = Some(value);
+
         Ok(())
     }
 }
@@ -268,156 +326,321 @@ pub enum Action {
 }
 
 impl Page {
-    pub fn update(&mut self, message: PageMsg, page_id: Entity) -> Action {
+    /// Validate the key entered in the add/rename dialogs
+    pub fn check_dialog_key(
+        &self,
+        data_path: &[DataPathType],
+        key: &str,
+        previous: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let node = self.tree.get_at(data_path)?;
+
+        let Node::Object(node_object) = &node.node else {
+            bail!(NodeError::unsupported(data_path, &node.node, "edit a key"));
+        };
+
+        match previous {
+            Some(previous) => node_object.renamed_node(previous, key).map(|_| ()),
+            None => node_object.check_new_key(key),
+        }
+    }
+
+    /// Apply a change on the node at `data_path`.
+    /// None means the tree should not be written, e.g. the input is not a valid number yet.
+    fn apply_change(
+        &mut self,
+        data_path: &[DataPathType],
+        change_msg: ChangeMsg,
+    ) -> anyhow::Result<Option<Action>> {
+        let node = self.tree.get_at_mut(data_path)?;
+
         let mut action = Action::None;
 
-        match message {
-            PageMsg::SelectDataPath(pos) => {
-                self.data_path.change_to(pos);
+        match change_msg {
+            ChangeMsg::ApplyDefault => {
+                let default = node.default.clone().ok_or_else(|| {
+                    NodeError::unsupported(data_path, &node.node, "apply default")
+                })?;
+
+                // an explicit choice of the user, so it is written even if the system
+                // config has another value
+                node.remove_value_rec();
+                node.apply_value(default, Origin::Edit)?;
+
+                self.tree.set_edited(data_path)?;
             }
-            PageMsg::OpenDataPath(data_path_type) => {
-                self.data_path.open(data_path_type);
+            ChangeMsg::ResetToInherited => {
+                let system = data_default_profile_figment(&self.system_config)
+                    .map(|data| Value::Dict(Tag::Default, data));
+
+                let (system_config, user_path) = (&self.system_config, &self.source_home_path);
+                self.tree.reset_to_inherited(
+                    data_path,
+                    system.as_ref(),
+                    &|tag| Origin::from_tag(system_config, user_path, tag),
+                    crate::providers::lenient(&self.format),
+                )?;
             }
-            PageMsg::ChangeMsg(data_path, change_msg) => {
-                let node = self.tree.get_at_mut(data_path.iter()).unwrap();
+            ChangeMsg::ChangeBool(value) => {
+                let Node::Bool(node_bool) = &mut node.node else {
+                    bail!(NodeError::unsupported(data_path, &node.node, "change bool"));
+                };
 
-                match change_msg {
-                    ChangeMsg::ApplyDefault => {
-                        node.remove_value_rec();
-                        node.apply_value(node.default.clone().unwrap(), false)
-                            .unwrap();
+                node_bool.value = Some(value);
+                self.tree.set_edited(data_path)?;
+            }
+            ChangeMsg::ChangeString(value) => {
+                let Node::String(node_string) = &mut node.node else {
+                    bail!(NodeError::unsupported(
+                        data_path,
+                        &node.node,
+                        "change string"
+                    ));
+                };
+
+                // keep the value for the text input, but don't write it
+                let is_valid = node_string.validate(&value).is_ok();
+                node_string.value = Some(value);
+
+                if !is_valid {
+                    return Ok(None);
+                }
 
-                        self.tree
-                            .set_modified(data_path[..data_path.len() - 1].iter());
-                    }
-                    ChangeMsg::ChangeBool(value) => {
-                        let node_bool = node.node.unwrap_bool_mut();
-                        node_bool.value = Some(value);
-                        self.tree.set_modified(data_path.iter());
+                self.tree.set_edited(data_path)?;
+            }
+            ChangeMsg::ChangeNumber(value) => {
+                let Node::Number(node_number) = &mut node.node else {
+                    bail!(NodeError::unsupported(
+                        data_path,
+                        &node.node,
+                        "change number"
+                    ));
+                };
+
+                node_number.value_string = value;
+
+                match node_number.try_parse_from_str(&node_number.value_string) {
+                    Ok(v) => {
+                        node_number.value = Some(v);
                     }
-                    ChangeMsg::ChangeString(value) => {
-                        let node_string = node.node.unwrap_string_mut();
-                        node_string.value = Some(value);
-                        self.tree.set_modified(data_path.iter());
+                    Err(_) => {
+                        return Ok(None);
                     }
-                    ChangeMsg::ChangeNumber(value) => {
-                        let node_number = node.node.unwrap_number_mut();
-                        node_number.value_string = value;
-
-                        match node_number.try_parse_from_str(&node_number.value_string) {
-                            Ok(v) => {
-                                node_number.value = Some(v);
-                            }
-                            Err(_) => {
-                                return Action::None;
-                            }
-                        }
+                }
 
-                        self.tree.set_modified(data_path.iter());
+                self.tree.set_edited(data_path)?;
+            }
+            ChangeMsg::ChangeAny(value) => {
+                let Node::Any(node_any) = &mut node.node else {
+                    bail!(NodeError::unsupported(
+                        data_path,
+                        &node.node,
+                        "change value"
+                    ));
+                };
+
+                // keep the input for the text editor, the error is shown next to it
+                let parsed = NodeAny::parse(&value);
+                node_any.value_string = value;
+
+                match parsed {
+                    Ok(v) => {
+                        node_any.value = Some(v);
                     }
-                    ChangeMsg::ChangeEnum(value) => {
-                        let node_enum = node.node.unwrap_enum_mut();
-                        node_enum.value = Some(value);
+                    Err(_) => {
+                        return Ok(None);
+                    }
+                }
 
-                        node_enum.nodes[value].modified = true;
-                        self.tree.set_modified(data_path.iter());
+                self.tree.set_edited(data_path)?;
+            }
+            ChangeMsg::ChangeEnum(value) => {
+                let Node::Enum(node_enum) = &mut node.node else {
+                    bail!(NodeError::unsupported(data_path, &node.node, "change enum"));
+                };
+
+                let Some(variant) = node_enum.nodes.get_mut(value) else {
+                    bail!(NodeError::NotFound {
+                        data_path: [data_path, &[DataPathType::Indice(value)]].concat(),
+                        kind: "enum",
+                    });
+                };
+
+                variant.expand()?;
+                variant.origin = Origin::Edit;
+
+                node_enum.value = Some(value);
+                self.tree.set_edited(data_path)?;
+            }
+            ChangeMsg::Remove(field) => {
+                match (&mut node.node, &field) {
+                    (Node::Object(node_object), DataPathType::Name(name)) => {
+                        node_object.nodes.shift_remove(name);
                     }
-                    ChangeMsg::Remove(field) => {
-                        match &mut node.node {
-                            Node::Object(node_object) => {
-                                node_object.nodes.shift_remove(field.unwrap_name_ref());
-
-                                for n in node_object.nodes.values_mut() {
-                                    n.modified = true;
-                                }
-                            }
-                            Node::Array(node_array) => {
-                                node_array
-                                    .values
-                                    .as_mut()
-                                    .unwrap()
-                                    .remove(field.unwrap_indice());
-
-                                for n in node_array.values.as_mut().unwrap() {
-                                    n.modified = true;
-                                }
-                            }
-                            _ => panic!(),
+                    (Node::Array(node_array), DataPathType::Indice(pos)) => {
+                        if !node_array.can_remove() {
+                            return Ok(None);
                         }
-                        // dbg!(&self.data_path);
 
-                        self.tree.set_modified(data_path.iter());
-                    }
-                    ChangeMsg::AddNewNodeToObject(name) => {
-                        let node_object = node.node.unwrap_object_mut();
+                        let Some(values) = node_array.values.as_mut().filter(|v| *pos < v.len())
+                        else {
+                            bail!(NodeError::NotFound {
+                                data_path: [data_path, std::slice::from_ref(&field)].concat(),
+                                kind: "array",
+                            });
+                        };
 
-                        if node_object.nodes.contains_key(&name) {
-                            return Action::None;
+                        values.remove(*pos);
+
+                        // figment replaces a whole array
+                        for n in values {
+                            n.origin = Origin::Edit;
                         }
+                    }
+                    _ => bail!(NodeError::unsupported(data_path, &node.node, "remove")),
+                }
 
-                        let mut new_node = node_object.template().unwrap();
+                self.tree.set_edited(data_path)?;
+            }
+            ChangeMsg::AddNewNodeToObject(name) => {
+                let Node::Object(node_object) = &mut node.node else {
+                    bail!(NodeError::unsupported(data_path, &node.node, "add a key"));
+                };
 
-                        if let Some(default) = &new_node.default {
-                            new_node.apply_value(default.clone(), false).unwrap();
-                        } else {
-                            new_node
-                                .apply_value(Value::Dict(Tag::Default, Dict::new()), false)
-                                .unwrap();
-                        }
+                node_object.check_new_key(&name)?;
 
-                        node_object.nodes.insert(name, new_node);
+                let mut new_node = node_object
+                    .template(&name)
+                    .ok_or_else(|| anyhow!("no template for {name}"))?;
 
-                        for n in node_object.nodes.values_mut() {
-                            n.modified = true;
-                        }
+                if let Some(default) = &new_node.default {
+                    new_node.apply_value(default.clone(), Origin::Default)?;
+                } else {
+                    new_node
+                        .apply_value(Value::Dict(Tag::Default, Dict::new()), Origin::Default)?;
+                }
 
-                        self.tree.set_modified(data_path.iter());
+                node_object.nodes.insert(name.clone(), new_node);
 
-                        action = Action::RemoveDialog;
-                    }
-                    ChangeMsg::AddNewNodeToArray => {
-                        let node_array = node.node.unwrap_array_mut();
+                self.tree
+                    .set_edited(&[data_path, &[DataPathType::Name(name)]].concat())?;
 
-                        let mut new_node = node_array.template(None);
+                action = Action::RemoveDialog;
+            }
+            ChangeMsg::AddNewNodeToArray => {
+                let Node::Array(node_array) = &mut node.node else {
+                    bail!(NodeError::unsupported(data_path, &node.node, "add an item"));
+                };
 
-                        if let Some(default) = &new_node.default {
-                            new_node.apply_value(default.clone(), false).unwrap();
-                        }
-                        new_node.modified = true;
-
-                        match &mut node_array.values {
-                            Some(values) => {
-                                for n in &mut *values {
-                                    n.modified = true;
-                                }
-                                values.push(new_node);
-                            }
-                            None => {
-                                node_array.values = Some(vec![new_node]);
-                            }
+                if !node_array.can_add() {
+                    return Ok(None);
+                }
+
+                let Some(mut new_node) = node_array.template(None) else {
+                    return Ok(None);
+                };
+
+                if let Some(default) = &new_node.default {
+                    new_node.apply_value(default.clone(), Origin::Default)?;
+                }
+                new_node.origin = Origin::Edit;
+
+                if node_array.unique
+                    && let Some(values) = &node_array.values
+                    && let Some(new_value) = new_node.value(&Tag::Default)
+                    && values
+                        .iter()
+                        .any(|n| n.value(&Tag::Default).as_ref() == Some(&new_value))
+                {
+                    bail!("{new_value:?} is already in the array, and items must be unique");
+                }
+
+                match &mut node_array.values {
+                    Some(values) => {
+                        for n in &mut *values {
+                            n.origin = Origin::Edit;
                         }
-                        self.tree.set_modified(data_path.iter());
+                        values.push(new_node);
+                    }
+                    None => {
+                        node_array.values = Some(vec![new_node]);
                     }
+                }
+                self.tree.set_edited(data_path)?;
+            }
 
-                    ChangeMsg::RenameKey { prev, new } => {
-                        let node_object = node.node.unwrap_object_mut();
+            ChangeMsg::RenameKey { prev, new } => {
+                let Node::Object(node_object) = &mut node.node else {
+                    bail!(NodeError::unsupported(
+                        data_path,
+                        &node.node,
+                        "rename a key"
+                    ));
+                };
+
+                let node = node_object.renamed_node(&prev, &new)?;
+
+                node_object.nodes.insert(new, node);
+                node_object.nodes.swap_remove(&prev);
+                self.tree.set_edited(data_path)?;
+                action = Action::RemoveDialog;
+            }
+        }
 
-                        if node_object.nodes.contains_key(&new) {
-                            return Action::None;
-                        }
+        Ok(Some(action))
+    }
+
+    pub fn update(&mut self, message: PageMsg, page_id: Entity) -> Action {
+        let mut action = Action::None;
+
+        match message {
+            PageMsg::SelectDataPath(pos) => {
+                self.data_path.change_to(pos);
+            }
+            PageMsg::GoTo(data_path) => {
+                self.data_path.go_to(data_path);
+
+                if let Ok(node) = self.tree.get_at_mut(self.data_path.current())
+                    && let Err(e) = node.expand()
+                {
+                    error!("{e}");
+                }
+            }
+            PageMsg::OpenDataPath(data_path_type) => {
+                self.data_path.open(data_path_type);
 
-                        let node = node_object.nodes.get(&prev).unwrap().clone();
-                        node_object.nodes.insert(new, node);
-                        node_object.nodes.swap_remove(&prev);
-                        self.tree.set_modified(data_path.iter());
-                        action = Action::RemoveDialog;
+                // recursive definitions are expanded when the user navigate into them
+                if let Ok(node) = self.tree.get_at_mut(self.data_path.current())
+                    && let Err(e) = node.expand()
+                {
+                    error!("{e}");
+                }
+            }
+            PageMsg::ChangeMsg(data_path, change_msg) => {
+                // the message can be stale, e.g. the node was removed after a reload
+                match self.apply_change(&data_path, change_msg) {
+                    Ok(Some(change_action)) => {
+                        self.error = None;
+                        action = change_action;
+                    }
+                    Ok(None) => return Action::None,
+                    Err(e) => {
+                        error!("{e}");
+                        self.error = Some(e.to_string());
+                        return Action::None;
                     }
                 }
 
                 self.data_path.sanitize_path(&self.tree);
 
-                if self.tree.is_valid() {
-                    self.write().unwrap();
+                self.diagnostics = self.tree.diagnostics();
+
+                // errors are shown in the header, nothing to write
+                if self.first_error().is_none()
+                    && let Err(e) = self.write()
+                {
+                    error!("can't write the config: {e}");
+                    self.error = Some(format!("can't write the config: {e}"));
                 }
             }
             PageMsg::None => {
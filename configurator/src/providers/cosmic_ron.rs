@@ -4,10 +4,14 @@ use std::{
 };
 
 use anyhow::{anyhow, bail};
-use figment::{value::Dict, Figment, Metadata, Profile, Provider};
-use ron::Map;
+use figment::{
+    value::{Dict, Empty, Tag, Value},
+    Figment, Metadata, Profile, Provider,
+};
 use serde::de::Error;
 
+use super::json::{Member, Spanned, SpannedKind};
+
 pub struct CosmicRonProvider {
     path: PathBuf,
 }
@@ -22,7 +26,7 @@ impl CosmicRonProvider {
 
 impl Provider for CosmicRonProvider {
     fn metadata(&self) -> figment::Metadata {
-        Metadata::named("cosmic ron provider")
+        Metadata::from("cosmic ron provider", self.path.as_path())
     }
 
     fn data(
@@ -64,7 +68,7 @@ impl CosmicRonProvider {
 
         // let path = self.path.join(format!("v{}", version));
 
-        let mut ron_map = ron::Map::new();
+        let mut dict = Dict::new();
 
         for dir_entry in fs::read_dir(&self.path)? {
             let dir_entry = dir_entry?;
@@ -77,22 +81,319 @@ impl CosmicRonProvider {
 
             debug!("{}", content);
 
-            let value: ron::Value = ron::from_str(&content)?;
+            // ron can't read structs in a figment value, the names of their fields are
+            // identifiers
+            let value = parse_spanned(&content)?.to_value();
 
             debug!("{:?}", value);
 
-            ron_map.insert(ron::Value::String(filename.to_string()), value);
+            dict.insert(filename.to_string(), value);
+        }
+
+        debug!("{:?}", dict);
+
+        // the values are tagged by the figment that uses this provider
+        Ok(figment::value::Map::from([(Profile::Default, dict)]))
+    }
+}
+
+/// Parse the value of a file with its position, so the writer can patch it and keep
+/// the comments. Structs and maps are dicts, tuples and lists are arrays, and unit
+/// variants are strings. The scalars are read by ron.
+pub(super) fn parse_spanned(content: &str) -> anyhow::Result<Spanned> {
+    let mut parser = Parser { content, pos: 0 };
+
+    parser.skip_whitespace();
+    // `#![enable(..)]` extensions
+    while parser.content[parser.pos..].starts_with("#!") {
+        parser.pos += parser.content[parser.pos..]
+            .find(']')
+            .ok_or_else(|| parser.error("unterminated attribute"))?
+            + 1;
+        parser.skip_whitespace();
+    }
+
+    let value = parser.value()?;
+    parser.skip_whitespace();
+
+    if parser.pos != content.len() {
+        return Err(parser.error("trailing characters"));
+    }
+
+    Ok(value)
+}
+
+struct Parser<'a> {
+    content: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> anyhow::Error {
+        let before = &self.content[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |pos| pos + 1) + 1;
+
+        anyhow!("{message} at line {line} column {column}")
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.content.as_bytes().get(self.pos).copied()
+    }
+
+    /// Also skips the comments
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = &self.content[self.pos..];
+
+            if rest.starts_with("//") {
+                self.pos += rest.find('\n').unwrap_or(rest.len());
+            } else if rest.starts_with("/*") {
+                self.pos += rest.find("*/").map_or(rest.len(), |end| end + 2);
+            } else if self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> anyhow::Result<()> {
+        self.skip_whitespace();
+
+        if self.peek() != Some(c) {
+            return Err(self.error(&format!("expected `{}`", c as char)));
+        }
+
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn ident(&mut self) -> &str {
+        let start = self.pos;
+
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == b'_')
+        {
+            self.pos += 1;
+        }
+
+        &self.content[start..self.pos]
+    }
+
+    fn value(&mut self) -> anyhow::Result<Spanned> {
+        self.skip_whitespace();
+
+        let start = self.pos;
+
+        let kind = match self.peek() {
+            Some(b'(') => self.struct_or_tuple()?,
+            Some(b'[') => SpannedKind::Array(self.list(b'[', b']')?),
+            Some(b'{') => SpannedKind::Object(self.map()?),
+            _ if self.is_quoted() => {
+                self.quoted()?;
+                self.scalar(start)?
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == b'_' => {
+                let ident = self.ident().to_string();
+
+                let before_args = self.pos;
+                self.skip_whitespace();
+                let has_args = self.peek() == Some(b'(');
+
+                match ident.as_str() {
+                    "Some" if has_args => {
+                        self.pos += 1;
+                        let value = self.value()?;
+                        self.expect(b')')?;
+                        SpannedKind::Some(Box::new(value))
+                    }
+                    // named struct, or tuple variant
+                    _ if has_args => self.struct_or_tuple()?,
+                    _ => {
+                        self.pos = before_args;
+                        self.scalar(start)?
+                    }
+                }
+            }
+            Some(_) => {
+                // numbers
+                while self.peek().is_some_and(|c| {
+                    c.is_ascii_alphanumeric() || matches!(c, b'_' | b'.' | b'+' | b'-')
+                }) {
+                    self.pos += 1;
+                }
+                self.scalar(start)?
+            }
+            None => return Err(self.error("expected a value")),
+        };
+
+        Ok(Spanned {
+            start,
+            end: self.pos,
+            kind,
+        })
+    }
+
+    /// The token from `start` is read by ron
+    fn scalar(&mut self, start: usize) -> anyhow::Result<SpannedKind> {
+        let token = &self.content[start..self.pos];
+
+        match ron::from_str::<Value>(token) {
+            Ok(value) => Ok(SpannedKind::Scalar(value)),
+            // unit variants
+            Err(_) if token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => Ok(
+                SpannedKind::Scalar(Value::String(Tag::Default, token.to_string())),
+            ),
+            Err(e) => {
+                self.pos = start;
+                Err(self.error(&e.to_string()))
+            }
+        }
+    }
+
+    /// `"`, `'`, `r#"`, `b"`, ...
+    fn is_quoted(&self) -> bool {
+        let rest = &self.content[self.pos..];
+        let rest = rest.strip_prefix('b').unwrap_or(rest);
+
+        match rest.strip_prefix('r') {
+            Some(rest) => rest.trim_start_matches('#').starts_with('"'),
+            None => rest.starts_with(['"', '\'']),
         }
+    }
+
+    /// Strings, raw strings and chars
+    fn quoted(&mut self) -> anyhow::Result<()> {
+        let rest = &self.content[self.pos..];
+        let prefix = rest.len() - rest.trim_start_matches(['r', 'b']).len();
+        let hashes = rest[prefix..].len() - rest[prefix..].trim_start_matches('#').len();
+        let raw = rest[..prefix].contains('r');
+
+        self.pos += prefix + hashes;
+        let quote = self.peek().ok_or_else(|| self.error("expected a string"))?;
+        self.pos += 1;
+
+        loop {
+            match self.peek() {
+                Some(b'\\') if !raw => self.pos += 2,
+                Some(c)
+                    if c == quote
+                        && self.content[self.pos + 1..]
+                            .bytes()
+                            .take_while(|c| *c == b'#')
+                            .count()
+                            >= hashes =>
+                {
+                    self.pos += 1 + hashes;
+                    return Ok(());
+                }
+                Some(_) => self.pos += 1,
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    /// `()`, `(a: 1)` or `(1, 2)`
+    fn struct_or_tuple(&mut self) -> anyhow::Result<SpannedKind> {
+        let open = self.pos;
+        self.expect(b'(')?;
+        self.skip_whitespace();
 
-        debug!("{:?}", ron_map);
+        let is_struct = {
+            let before = self.pos;
+            let ident = !self.ident().is_empty();
+            self.skip_whitespace();
+            let is_struct = ident && self.peek() == Some(b':');
+            self.pos = before;
+            is_struct
+        };
 
-        let data = Figment::new()
-            .merge(figment::providers::Serialized::from(
-                ron_map,
-                Profile::Default,
-            ))
-            .data()?;
+        if is_struct {
+            self.pos = open;
+            return Ok(SpannedKind::Object(self.members(b'(', b')', true)?));
+        }
 
-        Ok(data)
+        self.pos = open;
+        let values = self.list(b'(', b')')?;
+
+        Ok(match values.is_empty() {
+            true => SpannedKind::Scalar(Value::Empty(Tag::Default, Empty::Unit)),
+            false => SpannedKind::Array(values),
+        })
+    }
+
+    fn map(&mut self) -> anyhow::Result<Vec<Member>> {
+        self.members(b'{', b'}', false)
+    }
+
+    /// The keys of structs are identifiers, the keys of maps are values.
+    /// A trailing `,` is accepted.
+    fn members(&mut self, open: u8, close: u8, is_struct: bool) -> anyhow::Result<Vec<Member>> {
+        let mut members = Vec::new();
+
+        self.expect(open)?;
+
+        loop {
+            self.skip_whitespace();
+
+            if self.peek() == Some(close) {
+                self.pos += 1;
+                return Ok(members);
+            }
+
+            let start = self.pos;
+            let key = if is_struct {
+                self.ident().to_string()
+            } else {
+                match self.value()?.to_value() {
+                    Value::String(_, key) => key,
+                    _ => self.content[start..self.pos].to_string(),
+                }
+            };
+            self.expect(b':')?;
+            let value = self.value()?;
+
+            self.skip_whitespace();
+            let comma = (self.peek() == Some(b',')).then_some(self.pos);
+
+            members.push(Member {
+                key,
+                start,
+                value,
+                comma,
+            });
+
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(c) if c == close => {}
+                _ => return Err(self.error(&format!("expected `,` or `{}`", close as char))),
+            }
+        }
+    }
+
+    fn list(&mut self, open: u8, close: u8) -> anyhow::Result<Vec<Spanned>> {
+        let mut values = Vec::new();
+
+        self.expect(open)?;
+
+        loop {
+            self.skip_whitespace();
+
+            if self.peek() == Some(close) {
+                self.pos += 1;
+                return Ok(values);
+            }
+
+            values.push(self.value()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(c) if c == close => {}
+                _ => return Err(self.error(&format!("expected `,` or `{}`", close as char))),
+            }
+        }
     }
 }
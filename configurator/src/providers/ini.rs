@@ -0,0 +1,423 @@
+//! INI-like files: GTK `settings.ini`, desktop entries, systemd units, `kdeglobals`, ...
+//!
+//! Sections are dicts, and the keys before the first section are at the root.
+//! All values are strings, the tree converts them to the type of the schema.
+//! A key repeated in a section is an array, e.g. `ExecStart=` then `ExecStart=/bin/x`
+//! in a systemd drop-in.
+//! Lines starting with `#` or `;` are comments.
+//!
+//! An existing file is patched line by line, so the order of the sections and the comments
+//! are kept. The lines of a repeated key are patched in order. Only the keys and sections
+//! the tree tracked are removed. Nested dicts and arrays can't be written.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail};
+use figment::{
+    error::Actual,
+    value::{Dict, Tag, Value},
+    Metadata, Profile, Provider,
+};
+use serde::de::Error;
+
+use super::patch::has_untracked;
+use crate::utils::bool_from_str;
+
+pub struct IniProvider {
+    path: PathBuf,
+}
+
+impl IniProvider {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl Provider for IniProvider {
+    fn metadata(&self) -> figment::Metadata {
+        Metadata::from("ini provider", self.path.as_path())
+    }
+
+    fn data(
+        &self,
+    ) -> Result<figment::value::Map<figment::Profile, figment::value::Dict>, figment::Error> {
+        self.data_impl().map_err(figment::Error::custom)
+    }
+}
+
+impl IniProvider {
+    fn data_impl(
+        &self,
+    ) -> anyhow::Result<figment::value::Map<figment::Profile, figment::value::Dict>> {
+        // like the file providers of figment
+        if !self.path.exists() {
+            return Ok(figment::value::Map::new());
+        }
+
+        let content = fs::read_to_string(&self.path)?;
+
+        Ok(figment::value::Map::from([(
+            Profile::Default,
+            parse(&content)?,
+        )]))
+    }
+}
+
+enum Line<'a> {
+    Section(&'a str),
+    Entry {
+        key: &'a str,
+        value: &'a str,
+    },
+    /// Comments and blank lines
+    Other,
+}
+
+fn parse_line(line: &str) -> Option<Line<'_>> {
+    let trimmed = line.trim();
+
+    if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+        return Some(Line::Other);
+    }
+
+    if let Some(section) = trimmed.strip_prefix('[') {
+        return section.strip_suffix(']').map(|s| Line::Section(s.trim()));
+    }
+
+    trimmed.split_once('=').map(|(key, value)| Line::Entry {
+        key: key.trim(),
+        value: value.trim(),
+    })
+}
+
+fn parse(content: &str) -> anyhow::Result<Dict> {
+    let mut root = Dict::new();
+    let mut section: Option<&str> = None;
+
+    for (pos, line) in content.lines().enumerate() {
+        match parse_line(line).ok_or(anyhow!("line {}: invalid ini: {line}", pos + 1))? {
+            Line::Section(name) => {
+                section = Some(name);
+                root.entry(name.to_string())
+                    .or_insert_with(|| Value::from(Dict::new()));
+            }
+            Line::Entry { key, value } => {
+                let dict = match section {
+                    Some(name) => root
+                        .get_mut(name)
+                        .and_then(|section| match section {
+                            Value::Dict(_, dict) => Some(dict),
+                            _ => None,
+                        })
+                        .ok_or(anyhow!("line {}: {name} is also a key", pos + 1))?,
+                    None => &mut root,
+                };
+
+                let value = Value::from(value.to_string());
+
+                match dict.get_mut(key) {
+                    Some(Value::Array(_, values)) => values.push(value),
+                    Some(previous) => {
+                        *previous = Value::Array(Tag::Default, vec![previous.clone(), value]);
+                    }
+                    None => {
+                        dict.insert(key.to_string(), value);
+                    }
+                }
+            }
+            Line::Other => {}
+        }
+    }
+
+    Ok(root)
+}
+
+/// `None` when the key must not be written
+fn ini_value(key: &str, value: &Value) -> anyhow::Result<Option<String>> {
+    let value = match value {
+        Value::String(_, s) => s.clone(),
+        Value::Char(_, c) => c.to_string(),
+        Value::Bool(_, b) => b.to_string(),
+        Value::Num(_, num) => match num.to_actual() {
+            Actual::Unsigned(n) => n.to_string(),
+            Actual::Signed(n) => n.to_string(),
+            _ => num
+                .to_f64()
+                .ok_or(anyhow!("{key}: not a number {num:?}"))?
+                .to_string(),
+        },
+        Value::Empty(..) => return Ok(None),
+        Value::Dict(..) | Value::Array(..) => bail!("{key} can't be written in ini"),
+    };
+
+    Ok(Some(value))
+}
+
+/// The value of the file is kept if it is equivalent, e.g. `yes` for `true`
+fn same_value(raw: &str, value: &Value) -> bool {
+    match value {
+        Value::Bool(_, b) => bool_from_str(raw) == Some(*b),
+        Value::Num(_, num) => raw.parse::<f64>().ok() == num.to_f64(),
+        value => ini_value("", value).ok().flatten().as_deref() == Some(raw),
+    }
+}
+
+/// A key whose values are not all written yet
+struct Entry<'a> {
+    key: &'a String,
+    /// one line for each value
+    values: VecDeque<&'a Value>,
+    /// after the last line of the key in the file
+    insert_at: Option<usize>,
+}
+
+type Entries<'a> = Vec<Entry<'a>>;
+
+fn entries<'a>(keys: impl IntoIterator<Item = (&'a String, &'a Value)>) -> Entries<'a> {
+    keys.into_iter()
+        .map(|(key, value)| Entry {
+            key,
+            values: match value {
+                Value::Array(_, values) => values.iter().collect(),
+                value => VecDeque::from([value]),
+            },
+            insert_at: None,
+        })
+        .collect()
+}
+
+/// The keys of a section that are not written yet. A section can appear several times in
+/// the file, e.g. in a systemd unit.
+struct Section<'a> {
+    /// `None` for the root
+    name: Option<&'a String>,
+    entries: Entries<'a>,
+    in_file: bool,
+    /// occurrences of the section left in the file
+    remaining: usize,
+    /// after the last entry of the section
+    insert_at: usize,
+}
+
+/// The root and the sections
+fn split(dict: &Dict) -> Vec<Section<'_>> {
+    let mut keys = Vec::new();
+    let mut sections = Vec::new();
+
+    for (key, value) in dict {
+        match value {
+            Value::Dict(_, section) => sections.push((key, section)),
+            value => keys.push((key, value)),
+        }
+    }
+
+    let root = (None, entries(keys));
+    let sections = sections
+        .into_iter()
+        .map(|(name, section)| (Some(name), entries(section)));
+
+    std::iter::once(root)
+        .chain(sections)
+        .map(|(name, entries)| Section {
+            name,
+            entries,
+            in_file: false,
+            remaining: 0,
+            insert_at: 0,
+        })
+        .collect()
+}
+
+/// See `WriteContext` for `tracked`
+pub(super) fn to_string(
+    value: &Value,
+    previous: Option<&str>,
+    tracked: Option<&Value>,
+) -> anyhow::Result<String> {
+    let dict = value
+        .as_dict()
+        .ok_or(anyhow!("the root of an ini file is a dict"))?;
+
+    let mut sections = split(dict);
+    let previous = previous.unwrap_or_default();
+    let previous_dict = parse(previous)?;
+    let tracked = tracked.and_then(Value::as_dict);
+
+    let position = |sections: &[Section<'_>], name: &str| {
+        sections
+            .iter()
+            .position(|section| section.name.is_some_and(|key| key.as_str() == name))
+    };
+
+    for line in previous.lines() {
+        if let Some(Line::Section(name)) = parse_line(line)
+            && let Some(pos) = position(&sections, name)
+        {
+            sections[pos].in_file = true;
+            sections[pos].remaining += 1;
+        }
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+    // the remaining values, inserted at the end so the positions stay valid
+    let mut inserts = BTreeMap::new();
+
+    // the section of the value, `None` if it is not in the value
+    let mut current = Some(0);
+    // the section has been reset
+    let mut skip = false;
+    // the keys of the current section that the tree knows
+    let mut section_tracked = tracked;
+
+    for line in previous.lines() {
+        match parse_line(line) {
+            Some(Line::Section(name)) => {
+                // the keys of a repeated section are written at its last occurrence
+                if let Some(section) = current.map(|pos| &mut sections[pos])
+                    && section.remaining == 0
+                {
+                    pending_lines(&mut inserts, &mut section.entries, section.insert_at)?;
+                }
+
+                section_tracked = tracked
+                    .and_then(|tracked| tracked.get(name))
+                    .and_then(Value::as_dict);
+
+                current = position(&sections, name);
+
+                match current {
+                    Some(pos) => sections[pos].remaining -= 1,
+                    // the keys the tree doesn't know are kept
+                    None => match tracked.and_then(|tracked| tracked.get(name)) {
+                        Some(tracked)
+                            if !previous_dict
+                                .get(name)
+                                .is_some_and(|section| has_untracked(section, tracked)) =>
+                        {
+                            skip = true;
+                            continue;
+                        }
+                        _ => {}
+                    },
+                }
+
+                skip = false;
+                lines.push(line.to_string());
+
+                if let Some(pos) = current {
+                    sections[pos].insert_at = lines.len();
+                }
+            }
+            Some(Line::Entry { key, value }) if !skip => {
+                let entry = current.and_then(|pos| {
+                    sections[pos]
+                        .entries
+                        .iter_mut()
+                        .find(|entry| entry.key.as_str() == key)
+                });
+
+                let Some(entry) = entry else {
+                    // reset, or unknown
+                    if !section_tracked.is_some_and(|tracked| tracked.contains_key(key)) {
+                        lines.push(line.to_string());
+
+                        if let Some(pos) = current {
+                            sections[pos].insert_at = lines.len();
+                        }
+                    }
+                    continue;
+                };
+
+                // the value of the same line of a repeated key
+                let Some(new_value) = entry.values.pop_front() else {
+                    continue;
+                };
+
+                if same_value(value, new_value) {
+                    lines.push(line.to_string());
+                } else if let Some(new_value) = ini_value(key, new_value)? {
+                    // keep the spaces around `=`
+                    let prefix = &line[..line.len() - line.trim_start().len()];
+                    let (k, _) = line.trim_start().split_once('=').unwrap_or_default();
+                    let space = if k.ends_with(' ') { " " } else { "" };
+                    lines.push(format!("{prefix}{k}={space}{new_value}"));
+                }
+
+                entry.insert_at = Some(lines.len());
+
+                if let Some(pos) = current {
+                    sections[pos].insert_at = lines.len();
+                }
+            }
+            _ if skip => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    // the blank lines before the last section, that is reset
+    if skip {
+        while lines.last().is_some_and(|line| line.trim().is_empty()) {
+            lines.pop();
+        }
+    }
+
+    if let Some(pos) = current {
+        let section = &mut sections[pos];
+        pending_lines(&mut inserts, &mut section.entries, section.insert_at)?;
+    }
+
+    // from the end, so the positions stay valid
+    for (pos, new_lines) in inserts.into_iter().rev() {
+        let pos = pos.min(lines.len());
+        lines.splice(pos..pos, new_lines);
+    }
+
+    // the root is always at the start of the file
+    for section in &mut sections[1..] {
+        let Some(name) = section.name.filter(|_| !section.in_file) else {
+            continue;
+        };
+
+        if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+            lines.push(String::new());
+        }
+
+        lines.push(format!("[{name}]"));
+
+        let mut inserts = BTreeMap::new();
+        pending_lines(&mut inserts, &mut section.entries, 0)?;
+        lines.extend(inserts.into_values().flatten());
+    }
+
+    let mut content = lines.join("\n");
+    content.push('\n');
+
+    Ok(content)
+}
+
+/// The lines of the values not written yet, by position. The remaining values of a repeated
+/// key go after its last line.
+fn pending_lines(
+    inserts: &mut BTreeMap<usize, Vec<String>>,
+    entries: &mut Entries<'_>,
+    insert_at: usize,
+) -> anyhow::Result<()> {
+    for entry in entries.drain(..) {
+        for value in entry.values {
+            if let Some(value) = ini_value(entry.key, value)? {
+                inserts
+                    .entry(entry.insert_at.unwrap_or(insert_at))
+                    .or_default()
+                    .push(format!("{}={value}", entry.key));
+            }
+        }
+    }
+
+    Ok(())
+}
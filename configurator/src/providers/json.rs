@@ -0,0 +1,359 @@
+//! JSON is parsed here instead of with serde_json, which reads the integers that don't fit
+//! in 64 bits as f64 (`arbitrary_precision` would fix it, but breaks the untagged enums of
+//! schemars). Integers are read in the smallest of u64, i64, u128 and i128 that fits.
+//!
+//! Comments and trailing commas are accepted, so JSONC files can be read. The parser keeps
+//! the position of the values, so the writer can patch the file without losing them.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail};
+use figment::{
+    value::{Dict, Empty, Num, Tag, Value},
+    Metadata, Profile, Provider,
+};
+use serde::de::Error;
+
+use crate::node::data_path::DataPathType;
+
+pub struct JsonProvider {
+    path: PathBuf,
+}
+
+impl JsonProvider {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl Provider for JsonProvider {
+    fn metadata(&self) -> figment::Metadata {
+        Metadata::from("json provider", self.path.as_path())
+    }
+
+    fn data(
+        &self,
+    ) -> Result<figment::value::Map<figment::Profile, figment::value::Dict>, figment::Error> {
+        self.data_impl().map_err(figment::Error::custom)
+    }
+}
+
+impl JsonProvider {
+    fn data_impl(
+        &self,
+    ) -> anyhow::Result<figment::value::Map<figment::Profile, figment::value::Dict>> {
+        // like the file providers of figment
+        if !self.path.exists() {
+            return Ok(figment::value::Map::new());
+        }
+
+        let content = fs::read_to_string(&self.path)?;
+
+        Ok(figment::value::Map::from([(
+            Profile::Default,
+            parse(&content)?,
+        )]))
+    }
+}
+
+pub(super) fn parse(content: &str) -> anyhow::Result<Dict> {
+    match parse_spanned(content)?.to_value() {
+        Value::Dict(_, dict) => Ok(dict),
+        _ => bail!("the root of a json file must be an object"),
+    }
+}
+
+/// A value with its position in the content, used to patch the file
+pub(super) struct Spanned {
+    pub start: usize,
+    pub end: usize,
+    pub kind: SpannedKind,
+}
+
+pub(super) enum SpannedKind {
+    Object(Vec<Member>),
+    Array(Vec<Spanned>),
+    Scalar(Value),
+    /// `Some(value)` of RON, transparent for the paths
+    Some(Box<Spanned>),
+}
+
+pub(super) struct Member {
+    pub key: String,
+    /// Start of the key
+    pub start: usize,
+    pub value: Spanned,
+    /// Position of the `,` after the value
+    pub comma: Option<usize>,
+}
+
+impl Spanned {
+    pub fn to_value(&self) -> Value {
+        match &self.kind {
+            SpannedKind::Object(members) => Value::Dict(
+                Tag::Default,
+                members
+                    .iter()
+                    .map(|member| (member.key.clone(), member.value.to_value()))
+                    .collect(),
+            ),
+            SpannedKind::Array(values) => {
+                Value::Array(Tag::Default, values.iter().map(Spanned::to_value).collect())
+            }
+            SpannedKind::Scalar(value) => value.clone(),
+            SpannedKind::Some(value) => value.to_value(),
+        }
+    }
+
+    /// The value inside `Some(..)`
+    pub fn inner(&self) -> &Spanned {
+        match &self.kind {
+            SpannedKind::Some(value) => value.inner(),
+            _ => self,
+        }
+    }
+
+    /// The last member wins, like in the parsed value
+    pub fn member(&self, key: &str) -> Option<(usize, &Member)> {
+        match &self.inner().kind {
+            SpannedKind::Object(members) => members
+                .iter()
+                .enumerate()
+                .rev()
+                .find(|(_, member)| member.key == key),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, component: &DataPathType) -> Option<&Spanned> {
+        match (component, &self.inner().kind) {
+            (DataPathType::Name(key), _) => self.member(key).map(|(_, member)| &member.value),
+            (DataPathType::Indice(pos), SpannedKind::Array(values)) => values.get(*pos),
+            _ => None,
+        }
+    }
+}
+
+pub(super) fn parse_spanned(content: &str) -> anyhow::Result<Spanned> {
+    let mut parser = Parser { content, pos: 0 };
+
+    let value = parser.value()?;
+    parser.skip_whitespace();
+
+    if parser.pos != content.len() {
+        return Err(parser.error("trailing characters"));
+    }
+
+    Ok(value)
+}
+
+struct Parser<'a> {
+    content: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> anyhow::Error {
+        let before = &self.content[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |pos| pos + 1) + 1;
+
+        anyhow!("{message} at line {line} column {column}")
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.content.as_bytes().get(self.pos).copied()
+    }
+
+    /// Also skips the comments
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = &self.content[self.pos..];
+
+            if rest.starts_with("//") {
+                self.pos += rest.find('\n').unwrap_or(rest.len());
+            } else if rest.starts_with("/*") {
+                self.pos += rest.find("*/").map_or(rest.len(), |end| end + 2);
+            } else if self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> anyhow::Result<()> {
+        self.skip_whitespace();
+
+        if self.peek() != Some(c) {
+            return Err(self.error(&format!("expected `{}`", c as char)));
+        }
+
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> anyhow::Result<Spanned> {
+        self.skip_whitespace();
+
+        let start = self.pos;
+
+        let kind = match self.peek() {
+            Some(b'{') => SpannedKind::Object(self.object()?),
+            Some(b'[') => SpannedKind::Array(self.array()?),
+            Some(b'"') => SpannedKind::Scalar(Value::String(Tag::Default, self.string()?)),
+            Some(b'-' | b'0'..=b'9') => {
+                SpannedKind::Scalar(Value::Num(Tag::Default, self.number()?))
+            }
+            _ if self.literal("true") => SpannedKind::Scalar(Value::Bool(Tag::Default, true)),
+            _ if self.literal("false") => SpannedKind::Scalar(Value::Bool(Tag::Default, false)),
+            _ if self.literal("null") => {
+                SpannedKind::Scalar(Value::Empty(Tag::Default, Empty::None))
+            }
+            _ => return Err(self.error("expected a value")),
+        };
+
+        Ok(Spanned {
+            start,
+            end: self.pos,
+            kind,
+        })
+    }
+
+    fn literal(&mut self, literal: &str) -> bool {
+        let found = self.content[self.pos..].starts_with(literal);
+
+        if found {
+            self.pos += literal.len();
+        }
+
+        found
+    }
+
+    /// A trailing `,` is accepted, like in JSONC
+    fn object(&mut self) -> anyhow::Result<Vec<Member>> {
+        let mut members = Vec::new();
+
+        self.expect(b'{')?;
+
+        loop {
+            self.skip_whitespace();
+
+            if self.peek() == Some(b'}') {
+                self.pos += 1;
+                return Ok(members);
+            }
+
+            let start = self.pos;
+            let key = self.string()?;
+            self.expect(b':')?;
+            let value = self.value()?;
+
+            self.skip_whitespace();
+            let comma = (self.peek() == Some(b',')).then_some(self.pos);
+
+            members.push(Member {
+                key,
+                start,
+                value,
+                comma,
+            });
+
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {}
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> anyhow::Result<Vec<Spanned>> {
+        let mut values = Vec::new();
+
+        self.expect(b'[')?;
+
+        loop {
+            self.skip_whitespace();
+
+            if self.peek() == Some(b']') {
+                self.pos += 1;
+                return Ok(values);
+            }
+
+            values.push(self.value()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {}
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let start = self.pos;
+
+        if self.peek() != Some(b'"') {
+            return Err(self.error("expected a string"));
+        }
+        self.pos += 1;
+
+        loop {
+            match self.peek() {
+                Some(b'"') => break,
+                Some(b'\\') => self.pos += 2,
+                Some(_) => self.pos += 1,
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+        self.pos += 1;
+
+        // the escapes are decoded by serde_json
+        json::from_str(&self.content[start..self.pos]).map_err(|e| {
+            self.pos = start;
+            self.error(&e.to_string())
+        })
+    }
+
+    fn number(&mut self) -> anyhow::Result<Num> {
+        let start = self.pos;
+
+        while self
+            .peek()
+            .is_some_and(|c| matches!(c, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+        {
+            self.pos += 1;
+        }
+
+        let token = &self.content[start..self.pos];
+
+        let num = if token.contains(['.', 'e', 'E']) {
+            token.parse().ok().map(Num::F64)
+        } else if token.starts_with('-') {
+            token
+                .parse()
+                .map(Num::I64)
+                .or_else(|_| token.parse().map(Num::I128))
+                .or_else(|_| token.parse().map(Num::F64))
+                .ok()
+        } else {
+            token
+                .parse()
+                .map(Num::U64)
+                .or_else(|_| token.parse().map(Num::U128))
+                .or_else(|_| token.parse().map(Num::F64))
+                .ok()
+        };
+
+        num.ok_or_else(|| {
+            self.pos = start;
+            self.error(&format!("invalid number {token}"))
+        })
+    }
+}
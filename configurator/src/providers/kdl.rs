@@ -0,0 +1,644 @@
+//! KDL has no direct equivalent of the figment value model, so this mapping is used,
+//! in the style of the niri config:
+//!
+//! - a document is a dict, where each node is a key. Nodes with the same name are
+//!   gathered in an array, e.g. `spawn-at-startup "a"; spawn-at-startup "b"`
+//! - a node without entries and children is `true`, e.g. `prefer-no-csd`
+//! - a node with a single argument is its value, e.g. `width 4`
+//! - a node with several arguments is an array, e.g. `size 1 2`
+//! - a node whose children are all named `-` is an array, e.g. `list { - 1; - { a 1; }; }`
+//! - otherwise, the properties and the children of a node are the keys of a dict.
+//!   Its arguments go in the `arguments` key
+//! - `null` is `None`, and type annotations are ignored
+//!
+//! The writer does the inverse. `true` is a node without entries, and empty arrays are
+//! written by omitting the node. `false` is also omitted, unless the value without the file
+//! isn't `false`: then it is written as an argument, e.g. `prefer-no-csd false`. Arrays of
+//! dicts are written as repeated nodes, and arrays of one element use `-` children. The
+//! `arguments` key is written as the arguments of the node. Integers are limited to i64.
+//!
+//! An existing document is patched, so its comments are kept, and the properties stay
+//! properties. New keys are written as children. Only the nodes of the keys the tree tracked
+//! are removed, the others are kept.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ::kdl::{KdlDocument, KdlEntry, KdlNode, KdlValue};
+use anyhow::{anyhow, bail};
+use figment::{
+    error::Actual,
+    value::{Dict, Empty, Num, Tag, Value},
+    Metadata, Profile, Provider,
+};
+use serde::de::Error;
+
+use super::patch::has_untracked;
+use crate::utils::figment_value_eq;
+
+const ARRAY_ITEM: &str = "-";
+const ARGUMENTS: &str = "arguments";
+
+pub struct KdlProvider {
+    path: PathBuf,
+}
+
+impl KdlProvider {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl Provider for KdlProvider {
+    fn metadata(&self) -> figment::Metadata {
+        Metadata::from("kdl provider", self.path.as_path())
+    }
+
+    fn data(
+        &self,
+    ) -> Result<figment::value::Map<figment::Profile, figment::value::Dict>, figment::Error> {
+        self.data_impl().map_err(figment::Error::custom)
+    }
+}
+
+impl KdlProvider {
+    fn data_impl(
+        &self,
+    ) -> anyhow::Result<figment::value::Map<figment::Profile, figment::value::Dict>> {
+        // like the file providers of figment
+        if !self.path.exists() {
+            return Ok(figment::value::Map::new());
+        }
+
+        let content = fs::read_to_string(&self.path)?;
+
+        let document: KdlDocument = content.parse()?;
+
+        Ok(figment::value::Map::from([(
+            Profile::Default,
+            nodes_to_dict(document.nodes()),
+        )]))
+    }
+}
+
+fn nodes_to_dict(nodes: &[KdlNode]) -> Dict {
+    let mut values: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+
+    for node in nodes {
+        values
+            .entry(node.name().value().to_string())
+            .or_default()
+            .push(node_to_value(node));
+    }
+
+    values
+        .into_iter()
+        .map(|(key, mut values)| {
+            let value = match values.len() {
+                1 => values.remove(0),
+                _ => Value::Array(Tag::Default, values),
+            };
+            (key, value)
+        })
+        .collect()
+}
+
+fn node_to_value(node: &KdlNode) -> Value {
+    let (properties, arguments): (Vec<_>, Vec<_>) =
+        node.entries().iter().partition(|e| e.name().is_some());
+
+    let children = node.children().map(KdlDocument::nodes);
+
+    match (properties.is_empty(), arguments.as_slice(), children) {
+        (true, [], None) => Value::Bool(Tag::Default, true),
+        (true, [argument], None) => kdl_value_to_value(argument.value()),
+        (true, arguments, None) => Value::Array(
+            Tag::Default,
+            arguments
+                .iter()
+                .map(|e| kdl_value_to_value(e.value()))
+                .collect(),
+        ),
+        (true, [], Some(children))
+            if !children.is_empty() && children.iter().all(|n| n.name().value() == ARRAY_ITEM) =>
+        {
+            Value::Array(Tag::Default, children.iter().map(node_to_value).collect())
+        }
+        (_, arguments, children) => {
+            let mut dict = children.map(nodes_to_dict).unwrap_or_default();
+
+            for property in properties {
+                if let Some(name) = property.name() {
+                    dict.insert(
+                        name.value().to_string(),
+                        kdl_value_to_value(property.value()),
+                    );
+                }
+            }
+
+            match arguments {
+                [] => {}
+                [argument] => {
+                    dict.insert(ARGUMENTS.to_string(), kdl_value_to_value(argument.value()));
+                }
+                arguments => {
+                    dict.insert(
+                        ARGUMENTS.to_string(),
+                        Value::Array(
+                            Tag::Default,
+                            arguments
+                                .iter()
+                                .map(|e| kdl_value_to_value(e.value()))
+                                .collect(),
+                        ),
+                    );
+                }
+            }
+
+            Value::Dict(Tag::Default, dict)
+        }
+    }
+}
+
+fn kdl_value_to_value(value: &KdlValue) -> Value {
+    match value {
+        KdlValue::RawString(s) | KdlValue::String(s) => Value::String(Tag::Default, s.clone()),
+        KdlValue::Base2(n) | KdlValue::Base8(n) | KdlValue::Base10(n) | KdlValue::Base16(n) => {
+            Value::Num(Tag::Default, Num::I64(*n))
+        }
+        KdlValue::Base10Float(n) => Value::Num(Tag::Default, Num::F64(*n)),
+        KdlValue::Bool(b) => Value::Bool(Tag::Default, *b),
+        KdlValue::Null => Value::Empty(Tag::Default, Empty::None),
+    }
+}
+
+/// An existing document is patched, so its comments and formatting are kept.
+/// See `WriteContext` for `tracked`
+pub(super) fn to_string(
+    value: &Value,
+    previous: Option<&str>,
+    tracked: Option<&Value>,
+    inherited: Option<&Value>,
+) -> anyhow::Result<String> {
+    let dict = value
+        .as_dict()
+        .ok_or(anyhow!("the root of a kdl document is a dict"))?;
+
+    let document = match previous {
+        Some(previous) => {
+            let mut document: KdlDocument = previous.parse()?;
+            patch_document(
+                &mut document,
+                dict,
+                tracked.and_then(Value::as_dict),
+                inherited.and_then(Value::as_dict),
+            )?;
+            document
+        }
+        None => {
+            let mut document = dict_to_document(dict, inherited.and_then(Value::as_dict))?;
+            document.fmt();
+            document
+        }
+    };
+
+    Ok(document.to_string())
+}
+
+/// The values of the nodes of a key, the inverse of the gathering of nodes with the same name,
+/// with the value each node takes without the file
+fn node_values<'a>(
+    value: &'a Value,
+    inherited: Option<&'a Value>,
+) -> Vec<(&'a Value, Option<&'a Value>)> {
+    match value {
+        // nothing to write, so the default is used
+        Value::Array(_, values) if values.is_empty() => Vec::new(),
+        // otherwise, the missing node would take the inherited value
+        Value::Bool(_, false)
+            if inherited.is_none_or(|inherited| matches!(inherited, Value::Bool(_, false))) =>
+        {
+            Vec::new()
+        }
+        Value::Array(_, values) if values.len() > 1 && !is_scalars(values) => values
+            .iter()
+            .enumerate()
+            .map(|(pos, value)| (value, inherited_item(inherited, pos)))
+            .collect(),
+        value => vec![(value, inherited)],
+    }
+}
+
+fn inherited_item(inherited: Option<&Value>, pos: usize) -> Option<&Value> {
+    match inherited {
+        Some(Value::Array(_, values)) => values.get(pos),
+        _ => None,
+    }
+}
+
+fn inherited_child<'a>(inherited: Option<&'a Dict>, key: &str) -> Option<&'a Value> {
+    inherited.and_then(|inherited| inherited.get(key))
+}
+
+fn dict_to_document(dict: &Dict, inherited: Option<&Dict>) -> anyhow::Result<KdlDocument> {
+    let mut document = KdlDocument::new();
+
+    for (key, value) in dict {
+        for (value, inherited) in node_values(value, inherited_child(inherited, key)) {
+            document
+                .nodes_mut()
+                .push(value_to_node(key, value, inherited)?);
+        }
+    }
+
+    Ok(document)
+}
+
+fn value_to_node(name: &str, value: &Value, inherited: Option<&Value>) -> anyhow::Result<KdlNode> {
+    let mut node = KdlNode::new(name);
+
+    match value {
+        // a flag
+        Value::Bool(_, true) => {}
+        Value::Dict(_, dict) => {
+            let mut dict = dict.clone();
+
+            if let Some(arguments) = dict.remove(ARGUMENTS) {
+                for argument in arguments_of(name, &arguments)? {
+                    node.push(KdlEntry::new(value_to_kdl_value(argument)?));
+                }
+            }
+
+            // even when empty, so a single argument is not read as the value of the node
+            node.set_children(dict_to_document(&dict, inherited.and_then(Value::as_dict))?);
+        }
+        Value::Array(_, values) if values.len() > 1 && is_scalars(values) => {
+            for value in values {
+                node.push(KdlEntry::new(value_to_kdl_value(value)?));
+            }
+        }
+        Value::Array(_, values) if values.is_empty() => {
+            bail!("the empty array {name} can't be written in kdl")
+        }
+        // an array of one element must not be read as the element
+        Value::Array(_, values) => {
+            let mut children = KdlDocument::new();
+
+            for (pos, value) in values.iter().enumerate() {
+                let inherited = inherited_item(inherited, pos);
+                children
+                    .nodes_mut()
+                    .push(value_to_node(ARRAY_ITEM, value, inherited)?);
+            }
+
+            node.set_children(children);
+        }
+        value => node.push(KdlEntry::new(value_to_kdl_value(value)?)),
+    }
+
+    Ok(node)
+}
+
+/// An array of one argument is written as the argument
+fn arguments_of<'a>(name: &str, arguments: &'a Value) -> anyhow::Result<Vec<&'a Value>> {
+    match arguments {
+        Value::Array(_, values) if is_scalars(values) => Ok(values.iter().collect()),
+        Value::Dict(..) | Value::Array(..) => {
+            bail!("the arguments of {name} can't be written in kdl")
+        }
+        value => Ok(vec![value]),
+    }
+}
+
+/// The nodes of a key are patched in place, and the new ones go after them
+fn patch_document(
+    document: &mut KdlDocument,
+    dict: &Dict,
+    tracked: Option<&Dict>,
+    inherited: Option<&Dict>,
+) -> anyhow::Result<()> {
+    // the keys reset by the tree. A dict keeps the children the tree doesn't know
+    let mut reset = Vec::new();
+
+    for (pos, node) in document.nodes_mut().iter_mut().enumerate() {
+        let key = node.name().value().to_string();
+
+        let Some(tracked) = tracked
+            .filter(|_| !dict.contains_key(&key))
+            .and_then(|tracked| tracked.get(&key))
+        else {
+            continue;
+        };
+
+        if has_untracked(&node_to_value(node), tracked) {
+            let empty = Value::Dict(Tag::Default, Dict::new());
+            patch_node(
+                node,
+                &empty,
+                Some(tracked),
+                inherited_child(inherited, &key),
+            )?;
+        } else {
+            reset.push(pos);
+        }
+    }
+
+    for pos in reset.into_iter().rev() {
+        remove_node(document, pos);
+    }
+
+    for (key, value) in dict {
+        let values = node_values(value, inherited_child(inherited, key));
+        let tracked_values = tracked
+            .and_then(|tracked| tracked.get(key))
+            .map(|tracked| node_values(tracked, inherited_child(inherited, key)))
+            .unwrap_or_default();
+
+        let positions: Vec<usize> = document
+            .nodes()
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.name().value() == key)
+            .map(|(pos, _)| pos)
+            .collect();
+
+        for (i, (pos, (value, inherited))) in positions.iter().zip(&values).enumerate() {
+            let tracked = tracked_values.get(i).map(|(tracked, _)| *tracked);
+            patch_node(&mut document.nodes_mut()[*pos], value, tracked, *inherited)?;
+        }
+
+        for pos in positions.iter().skip(values.len()).rev() {
+            remove_node(document, *pos);
+        }
+
+        let at = positions
+            .last()
+            .map_or(document.nodes().len(), |pos| pos + 1);
+
+        for (pos, (value, inherited)) in values.iter().skip(positions.len()).enumerate() {
+            insert_node(document, at + pos, value_to_node(key, value, *inherited)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// The line break before the node is kept, e.g. the one after the `{` of its parent
+fn remove_node(document: &mut KdlDocument, pos: usize) {
+    let node = document.nodes_mut().remove(pos);
+
+    if node
+        .leading()
+        .is_some_and(|leading| leading.starts_with('\n'))
+        && let Some(next) = document.nodes_mut().get_mut(pos)
+        && let Some(leading) = next.leading().filter(|leading| !leading.starts_with('\n'))
+    {
+        next.set_leading(format!("\n{leading}"));
+    }
+}
+
+/// A new node is indented and terminated like the node before it
+fn insert_node(document: &mut KdlDocument, pos: usize, mut node: KdlNode) {
+    if let Some(previous) = pos
+        .checked_sub(1)
+        .and_then(|pos| document.nodes_mut().get_mut(pos))
+        && let Some(trailing) = previous.trailing().map(str::to_string)
+    {
+        let indent = previous
+            .leading()
+            .and_then(|leading| leading.rsplit('\n').next())
+            .filter(|indent| indent.trim().is_empty())
+            .unwrap_or_default()
+            .to_string();
+        node.set_leading(indent);
+
+        if trailing.contains('\n') {
+            node.set_trailing("\n");
+        } else {
+            node.set_trailing(";");
+
+            // otherwise, the new node would be read as its entries
+            if !trailing.contains(';') {
+                previous.set_trailing(format!("{trailing};"));
+            }
+        }
+    }
+
+    document.nodes_mut().insert(pos, node);
+}
+
+/// Only what changed is written: the properties stay properties, and the text around the
+/// entries is kept
+fn patch_node(
+    node: &mut KdlNode,
+    value: &Value,
+    tracked: Option<&Value>,
+    inherited: Option<&Value>,
+) -> anyhow::Result<()> {
+    let old = node_to_value(node);
+
+    // the children of a dict are still patched, a `false` may now be omitted
+    if figment_value_eq(&old, value) && !matches!(value, Value::Dict(..)) {
+        return Ok(());
+    }
+
+    let only_arguments =
+        node.entries().iter().all(|e| e.name().is_none()) && node.children().is_none();
+
+    match (value, old) {
+        (Value::Dict(_, dict), Value::Dict(..)) => {
+            let tracked = tracked.and_then(Value::as_dict);
+            let mut children = Dict::new();
+
+            for (key, value) in dict {
+                if key == ARGUMENTS {
+                    continue;
+                }
+
+                match node
+                    .entries_mut()
+                    .iter_mut()
+                    .find(|e| e.name().is_some_and(|name| name.value() == key))
+                {
+                    Some(entry) if is_scalar(value) => {
+                        if !figment_value_eq(&kdl_value_to_value(entry.value()), value) {
+                            set_entry_value(entry, value)?;
+                        }
+                    }
+                    _ => {
+                        children.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+
+            // properties that are reset, or written as children
+            node.entries_mut().retain(|e| {
+                e.name().is_none_or(|name| {
+                    let key = name.value();
+                    !children.contains_key(key)
+                        && (dict.contains_key(key)
+                            || !tracked.is_some_and(|tracked| tracked.contains_key(key)))
+                })
+            });
+
+            match dict.get(ARGUMENTS) {
+                Some(arguments) => {
+                    let arguments = arguments_of(node.name().value(), arguments)?;
+                    patch_arguments(node, &arguments)?;
+                }
+                None if tracked.is_some_and(|tracked| tracked.contains_key(ARGUMENTS)) => {
+                    patch_arguments(node, &[])?;
+                }
+                None => {}
+            }
+
+            // without properties and children, the node would not be read as a dict
+            let no_properties = node.entries().iter().all(|e| e.name().is_none());
+
+            match node.children_mut() {
+                Some(document) => patch_document(
+                    document,
+                    &children,
+                    tracked,
+                    inherited.and_then(Value::as_dict),
+                )?,
+                None if !children.is_empty() || no_properties => node.set_children(
+                    dict_to_document(&children, inherited.and_then(Value::as_dict))?,
+                ),
+                None => {}
+            }
+        }
+        (Value::Array(_, values), _)
+            if node.children().is_some_and(|children| {
+                children.nodes().len() == values.len()
+                    && children
+                        .nodes()
+                        .iter()
+                        .all(|n| n.name().value() == ARRAY_ITEM)
+            }) =>
+        {
+            if let Some(children) = node.children_mut() {
+                for (pos, (child, value)) in children.nodes_mut().iter_mut().zip(values).enumerate()
+                {
+                    let tracked = match tracked {
+                        Some(Value::Array(_, tracked)) => tracked.get(pos),
+                        _ => None,
+                    };
+                    patch_node(child, value, tracked, inherited_item(inherited, pos))?;
+                }
+            }
+        }
+        (Value::Array(_, values), _)
+            if only_arguments && values.len() > 1 && is_scalars(values) =>
+        {
+            patch_arguments(node, &values.iter().collect::<Vec<_>>())?;
+        }
+        (value, _)
+            if only_arguments
+                && node.entries().len() == 1
+                && is_scalar(value)
+                && !matches!(value, Value::Bool(_, true)) =>
+        {
+            patch_arguments(node, &[value])?;
+        }
+        // rewritten at the same place
+        (value, _) => {
+            let mut new = value_to_node(node.name().value(), value, inherited)?;
+
+            if let Some(leading) = node.leading() {
+                new.set_leading(leading);
+            }
+            if let Some(trailing) = node.trailing() {
+                new.set_trailing(trailing);
+            }
+
+            *node = new;
+        }
+    }
+
+    Ok(())
+}
+
+/// The arguments are patched one by one when their number doesn't change
+fn patch_arguments(node: &mut KdlNode, arguments: &[&Value]) -> anyhow::Result<()> {
+    let positions: Vec<usize> = node
+        .entries()
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.name().is_none())
+        .map(|(pos, _)| pos)
+        .collect();
+
+    if positions.len() == arguments.len() {
+        for (pos, argument) in positions.into_iter().zip(arguments) {
+            let entry = &mut node.entries_mut()[pos];
+
+            if !figment_value_eq(&kdl_value_to_value(entry.value()), argument) {
+                set_entry_value(entry, argument)?;
+            }
+        }
+    } else {
+        node.entries_mut().retain(|e| e.name().is_some());
+
+        for (pos, argument) in arguments.iter().enumerate() {
+            node.insert(pos, KdlEntry::new(value_to_kdl_value(argument)?));
+        }
+    }
+
+    Ok(())
+}
+
+/// Keep the text around the entry, like the comments
+fn set_entry_value(entry: &mut KdlEntry, value: &Value) -> anyhow::Result<()> {
+    let value = value_to_kdl_value(value)?;
+
+    let mut new = match entry.name() {
+        Some(name) => KdlEntry::new_prop(name.clone(), value),
+        None => KdlEntry::new(value),
+    };
+
+    if let Some(leading) = entry.leading() {
+        new.set_leading(leading);
+    }
+    if let Some(trailing) = entry.trailing() {
+        new.set_trailing(trailing);
+    }
+
+    *entry = new;
+
+    Ok(())
+}
+
+fn is_scalar(value: &Value) -> bool {
+    !matches!(value, Value::Dict(..) | Value::Array(..))
+}
+
+fn is_scalars(values: &[Value]) -> bool {
+    values.iter().all(is_scalar)
+}
+
+fn value_to_kdl_value(value: &Value) -> anyhow::Result<KdlValue> {
+    let value = match value {
+        Value::String(_, s) => KdlValue::String(s.clone()),
+        Value::Char(_, c) => KdlValue::String(c.to_string()),
+        Value::Bool(_, b) => KdlValue::Bool(*b),
+        Value::Num(_, num) => match num.to_actual() {
+            Actual::Unsigned(n) => KdlValue::Base10(
+                i64::try_from(n).map_err(|_| anyhow!("{n} doesn't fit in a kdl integer"))?,
+            ),
+            Actual::Signed(n) => KdlValue::Base10(
+                i64::try_from(n).map_err(|_| anyhow!("{n} doesn't fit in a kdl integer"))?,
+            ),
+            _ => KdlValue::Base10Float(num.to_f64().ok_or(anyhow!("not a number: {num:?}"))?),
+        },
+        Value::Empty(..) => KdlValue::Null,
+        value => bail!("{value:?} is not a kdl value"),
+    };
+
+    Ok(value)
+}
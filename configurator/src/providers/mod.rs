@@ -1,17 +1,27 @@
 use anyhow::anyhow;
 use std::{fs, path::Path};
 
+pub use self::json::JsonProvider;
+pub use self::kdl::KdlProvider;
+use crate::{node::Lenient, utils::figment_value_eq};
 use configurator_utils::ConfigFormat;
 pub use cosmic_ron::CosmicRonProvider;
 use figment::{
     providers::{self, Format},
-    value::Value,
+    value::{Dict, Tag, Value},
     Figment, Profile, Provider,
 };
+pub use ini::IniProvider;
+pub use yaml::YamlProvider;
 
 mod cosmic_ron;
+mod ini;
+mod json;
+mod kdl;
+mod patch;
 #[cfg(test)]
 mod tests;
+mod yaml;
 
 pub struct BoxedProvider(Box<dyn Provider>);
 
@@ -36,34 +46,166 @@ pub fn read_from_format<P: AsRef<Path>>(path: P, format: &ConfigFormat) -> Boxed
     debug!("{:?}:{}", path.as_ref(), format);
 
     match format {
-        ConfigFormat::Json => BoxedProvider(Box::new(providers::Json::file(path))),
+        ConfigFormat::Json => {
+            BoxedProvider(Box::new(crate::providers::JsonProvider::new(path.as_ref())))
+        }
         ConfigFormat::CosmicRon => BoxedProvider(Box::new(
             crate::providers::CosmicRonProvider::new(path.as_ref()),
         )),
+        ConfigFormat::Toml => BoxedProvider(Box::new(providers::Toml::file(path))),
+        ConfigFormat::Yaml => {
+            BoxedProvider(Box::new(crate::providers::YamlProvider::new(path.as_ref())))
+        }
+        ConfigFormat::Kdl => {
+            BoxedProvider(Box::new(crate::providers::KdlProvider::new(path.as_ref())))
+        }
+        ConfigFormat::Ini => {
+            BoxedProvider(Box::new(crate::providers::IniProvider::new(path.as_ref())))
+        }
+    }
+}
+
+/// The conversions needed to apply the values of `format` to the tree
+pub fn lenient(format: &ConfigFormat) -> Lenient {
+    match format {
+        ConfigFormat::Ini => Lenient {
+            string_to_scalar: true,
+            value_to_array: true,
+        },
+        ConfigFormat::Kdl => Lenient {
+            string_to_scalar: false,
+            value_to_array: true,
+        },
+        _ => Lenient::default(),
     }
 }
 
-pub fn write<P: AsRef<Path>>(path: P, format: &ConfigFormat, data: &Value) -> anyhow::Result<()> {
+/// What the tree knows about the file, besides the value to write
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriteContext<'a> {
+    /// The user layer as the tree read it, or wrote it last. A key of the file is only
+    /// removed when it is here, so the keys the schema doesn't model are kept.
+    pub tracked: Option<&'a Value>,
+    /// The value the keys take without the file. A format that omits a key for a value,
+    /// like `false` in KDL, only does it when this value is the same.
+    pub inherited: Option<&'a Value>,
+}
+
+/// Only the changes are applied to an existing file, so a config edited by hand keeps
+/// its comments and formatting. YAML files are rewritten.
+pub fn write<P: AsRef<Path>>(
+    path: P,
+    format: &ConfigFormat,
+    data: &Value,
+    context: WriteContext<'_>,
+) -> anyhow::Result<()> {
     // dbg!(&data);
+    let previous = read_previous(path.as_ref(), format);
+    let tracked = context.tracked;
+
     match format {
         ConfigFormat::Json => {
-            let content = json::to_string_pretty(&data)?;
+            let content = match previous {
+                Some((content, previous)) => {
+                    patch::patch_json(&content, &patch::diff(&previous, data, tracked))?
+                }
+                None => ::json::to_string_pretty(&data)?,
+            };
             write_and_create_parent(path, &content)?;
         }
         ConfigFormat::CosmicRon => {
+            let previous = previous
+                .as_ref()
+                .and_then(|(_, previous)| previous.as_dict());
+            let tracked = tracked.and_then(Value::as_dict);
+
             if let Some(dict) = data.as_dict() {
+                // each key is a file, only the files of the modified keys are patched
                 for (key, value) in dict {
-                    let content =
-                        ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::new()).unwrap();
-                    write_and_create_parent(path.as_ref().join(key), &content)?;
+                    let path = path.as_ref().join(key);
+
+                    let content = match previous.and_then(|previous| previous.get(key)) {
+                        Some(previous) if figment_value_eq(previous, value) => continue,
+                        Some(previous) => {
+                            let tracked = tracked.and_then(|tracked| tracked.get(key));
+                            let changes = patch::diff(previous, value, tracked);
+                            patch::patch_ron(&fs::read_to_string(&path)?, &changes)?
+                        }
+                        None => ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::new())?,
+                    };
+                    write_and_create_parent(path, &content)?;
+                }
+
+                // keys reset to inherited
+                for (key, previous) in previous
+                    .iter()
+                    .flat_map(|previous| previous.iter())
+                    .filter(|(key, _)| !dict.contains_key(*key))
+                {
+                    let path = path.as_ref().join(key);
+
+                    match tracked.and_then(|tracked| tracked.get(key)) {
+                        // the fields the tree doesn't know are kept
+                        Some(tracked) if patch::has_untracked(previous, tracked) => {
+                            let empty = Value::Dict(Tag::Default, Dict::new());
+                            let changes = patch::diff(previous, &empty, Some(tracked));
+                            let content = patch::patch_ron(&fs::read_to_string(&path)?, &changes)?;
+                            write_and_create_parent(path, &content)?;
+                        }
+                        Some(_) => fs::remove_file(path)?,
+                        None => {}
+                    }
                 }
             }
         }
+        ConfigFormat::Toml => {
+            // keys are sorted, and the values are written before the tables.
+            // TOML has no null, so `None` fields are omitted
+            let content = match previous {
+                Some((content, previous)) => {
+                    patch::patch_toml(&content, &patch::diff(&previous, data, tracked))?
+                }
+                None => toml::to_string_pretty(&data)?,
+            };
+            write_and_create_parent(path, &content)?;
+        }
+        ConfigFormat::Yaml => {
+            // multi-line strings are written as block scalars
+            let content = serde_yaml::to_string(&data)?;
+            write_and_create_parent(path, &content)?;
+        }
+        ConfigFormat::Kdl => {
+            let previous = previous.as_ref().map(|(content, _)| content.as_str());
+            let content = self::kdl::to_string(data, previous, tracked, context.inherited)?;
+            write_and_create_parent(path, &content)?;
+        }
+        ConfigFormat::Ini => {
+            let previous = previous.as_ref().map(|(content, _)| content.as_str());
+            let content = ini::to_string(data, previous, tracked)?;
+            write_and_create_parent(path, &content)?;
+        }
     }
 
     Ok(())
 }
 
+/// The content of the file on disk and its value, if it can be read
+fn read_previous(path: &Path, format: &ConfigFormat) -> Option<(String, Value)> {
+    let content = match format {
+        ConfigFormat::CosmicRon => String::new(),
+        // rewritten
+        ConfigFormat::Yaml => return None,
+        _ => fs::read_to_string(path).ok()?,
+    };
+
+    let dict = read_from_format(path, format)
+        .data()
+        .ok()?
+        .remove(&Profile::Default)?;
+
+    Some((content, Value::Dict(Tag::Default, dict)))
+}
+
 fn write_and_create_parent<P: AsRef<Path>, C: AsRef<[u8]>>(
     path: P,
     contents: C,
@@ -0,0 +1,462 @@
+//! Apply only what changed to the file on disk, so the comments,
+//! the order of the keys and the formatting of a hand-edited config are kept.
+
+use anyhow::{anyhow, bail};
+use figment::value::{Dict, Tag, Value};
+
+use super::json::{Spanned, SpannedKind};
+use crate::{node::data_path::DataPathType, utils::figment_value_eq};
+
+#[derive(Debug)]
+pub(super) enum Change {
+    Set(Vec<DataPathType>, Value),
+    Remove(Vec<DataPathType>),
+}
+
+/// Changes that turn `old` into `new`.
+/// Arrays are replaced when their length changes. A key missing from `new` is only removed
+/// when it is in `tracked`, so the keys of the file that the tree doesn't know are kept,
+/// even in a dict that is reset.
+pub(super) fn diff(old: &Value, new: &Value, tracked: Option<&Value>) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_rec(old, new, tracked, &mut Vec::new(), &mut changes);
+    changes
+}
+
+fn diff_rec(
+    old: &Value,
+    new: &Value,
+    tracked: Option<&Value>,
+    path: &mut Vec<DataPathType>,
+    changes: &mut Vec<Change>,
+) {
+    match (old, new) {
+        (Value::Dict(_, old), Value::Dict(_, new)) => {
+            let tracked = tracked.and_then(Value::as_dict);
+
+            for (key, old) in old.iter().filter(|(key, _)| !new.contains_key(*key)) {
+                let Some(tracked) = tracked.and_then(|tracked| tracked.get(key)) else {
+                    continue;
+                };
+
+                path.push(DataPathType::Name(key.clone()));
+                if has_untracked(old, tracked) {
+                    let empty = Value::Dict(Tag::Default, Dict::new());
+                    diff_rec(old, &empty, Some(tracked), path, changes);
+                } else {
+                    changes.push(Change::Remove(path.clone()));
+                }
+                path.pop();
+            }
+
+            for (key, new) in new {
+                path.push(DataPathType::Name(key.clone()));
+                match old.get(key) {
+                    Some(old) => {
+                        let tracked = tracked.and_then(|tracked| tracked.get(key));
+                        diff_rec(old, new, tracked, path, changes)
+                    }
+                    None => changes.push(Change::Set(path.clone(), new.clone())),
+                }
+                path.pop();
+            }
+        }
+        (Value::Array(_, old), Value::Array(_, new)) if old.len() == new.len() => {
+            for (pos, (old, new)) in old.iter().zip(new).enumerate() {
+                let tracked = match tracked {
+                    Some(Value::Array(_, tracked)) => tracked.get(pos),
+                    _ => None,
+                };
+
+                path.push(DataPathType::Indice(pos));
+                diff_rec(old, new, tracked, path, changes);
+                path.pop();
+            }
+        }
+        (old, new) => {
+            if !figment_value_eq(old, new) {
+                changes.push(Change::Set(path.clone(), new.clone()));
+            }
+        }
+    }
+}
+
+/// A dict with keys the tree doesn't know
+pub(super) fn has_untracked(value: &Value, tracked: &Value) -> bool {
+    match (value, tracked) {
+        (Value::Dict(_, dict), Value::Dict(_, tracked)) => {
+            dict.keys().any(|key| !tracked.contains_key(key))
+        }
+        _ => false,
+    }
+}
+
+pub(super) fn patch_toml(content: &str, changes: &[Change]) -> anyhow::Result<String> {
+    let mut document: toml_edit::DocumentMut = content.parse()?;
+
+    for change in changes {
+        let (last, path) = match change {
+            Change::Set(path, _) | Change::Remove(path) => path
+                .split_last()
+                .ok_or(anyhow!("the root of a toml file is a table"))?,
+        };
+
+        let mut parent = document.as_item_mut();
+        for component in path {
+            parent = toml_item_mut(parent, component)
+                .ok_or(anyhow!("no toml item at {component} in {path:?}"))?;
+        }
+
+        match change {
+            // toml has no null value
+            Change::Set(_, Value::Empty(..)) | Change::Remove(_) => {
+                if let (Some(table), DataPathType::Name(key)) = (parent.as_table_like_mut(), last) {
+                    table.remove(key);
+                }
+            }
+            Change::Set(_, value) => {
+                let mut item = toml_item(value)?;
+
+                // tables can only be in tables, otherwise they are inline
+                if !parent.is_table() && !parent.is_array_of_tables() {
+                    item = item
+                        .into_value()
+                        .map(toml_edit::Item::Value)
+                        .map_err(|item| anyhow!("can't write {item} in {path:?}"))?;
+                }
+
+                match toml_item_mut(parent, last) {
+                    Some(old) => {
+                        // keep the comments around the value
+                        if let (Some(old), Some(new)) = (old.as_value(), item.as_value_mut()) {
+                            *new.decor_mut() = old.decor().clone();
+                        }
+                        *old = item;
+                    }
+                    None => match (parent.as_table_like_mut(), last) {
+                        (Some(table), DataPathType::Name(key)) => {
+                            table.insert(key, item);
+                        }
+                        _ => bail!("can't insert {last} in {path:?}"),
+                    },
+                }
+            }
+        }
+    }
+
+    Ok(document.to_string())
+}
+
+fn toml_item_mut<'a>(
+    item: &'a mut toml_edit::Item,
+    component: &DataPathType,
+) -> Option<&'a mut toml_edit::Item> {
+    match component {
+        DataPathType::Name(key) => item.get_mut(key.as_str()),
+        DataPathType::Indice(pos) => item.get_mut(*pos),
+    }
+}
+
+/// Use the serializer of the toml crate, so the values are written like a new file
+fn toml_item(value: &Value) -> anyhow::Result<toml_edit::Item> {
+    let key = "value".to_string();
+
+    let content = toml::to_string(&Dict::from([(key.clone(), value.clone())]))?;
+    let mut document: toml_edit::DocumentMut = content.parse()?;
+
+    document
+        .remove(&key)
+        .ok_or(anyhow!("toml has no null value"))
+}
+
+/// A format whose values are patched through their position in the content
+trait SpannedFormat {
+    /// Used when no line of the file is indented
+    const INDENT: &str;
+
+    fn parse(content: &str) -> anyhow::Result<Spanned>;
+
+    /// `old` is the content of the replaced value
+    fn value(value: &Value, old: Option<&str>, indent: &str, base: &str) -> anyhow::Result<String>;
+
+    /// `object` is the content of the object that receives the member
+    fn member(
+        key: &str,
+        value: &Value,
+        object: &str,
+        indent: &str,
+        base: &str,
+    ) -> anyhow::Result<String>;
+}
+
+struct Json;
+
+impl SpannedFormat for Json {
+    const INDENT: &str = "  ";
+
+    fn parse(content: &str) -> anyhow::Result<Spanned> {
+        super::json::parse_spanned(content)
+    }
+
+    fn value(value: &Value, _: Option<&str>, indent: &str, base: &str) -> anyhow::Result<String> {
+        json_string(value, indent, base)
+    }
+
+    fn member(
+        key: &str,
+        value: &Value,
+        _: &str,
+        indent: &str,
+        base: &str,
+    ) -> anyhow::Result<String> {
+        Ok(format!(
+            "{}: {}",
+            json::to_string(key)?,
+            json_string(value, indent, base)?
+        ))
+    }
+}
+
+struct Ron;
+
+impl SpannedFormat for Ron {
+    const INDENT: &str = "    ";
+
+    fn parse(content: &str) -> anyhow::Result<Spanned> {
+        super::cosmic_ron::parse_spanned(content)
+    }
+
+    /// Options and unit variants keep their syntax
+    fn value(value: &Value, old: Option<&str>, indent: &str, base: &str) -> anyhow::Result<String> {
+        let is_option = old.is_some_and(|old| old == "None" || old.starts_with("Some("));
+        let is_ident = |s: &str| {
+            s.starts_with(|c: char| c.is_ascii_alphabetic())
+                && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+
+        Ok(match value {
+            Value::Empty(..) if is_option => "None".to_string(),
+            value if is_option => format!("Some({})", ron_string(value, indent, base)?),
+            Value::String(_, s)
+                if old.is_some_and(|old| is_ident(old) && !["true", "false"].contains(&old))
+                    && is_ident(s) =>
+            {
+                s.clone()
+            }
+            value => ron_string(value, indent, base)?,
+        })
+    }
+
+    /// The keys of structs are identifiers
+    fn member(
+        key: &str,
+        value: &Value,
+        object: &str,
+        indent: &str,
+        base: &str,
+    ) -> anyhow::Result<String> {
+        let key = match object.starts_with('{') {
+            true => ron::to_string(key)?,
+            false => key.to_string(),
+        };
+
+        Ok(format!(
+            "{key}: {}",
+            Self::value(value, None, indent, base)?
+        ))
+    }
+}
+
+/// The spans of the values are replaced in the content, so the comments and the
+/// formatting of JSONC files are kept. New values use the indentation of the file.
+pub(super) fn patch_json(content: &str, changes: &[Change]) -> anyhow::Result<String> {
+    patch_spanned::<Json>(content, changes)
+}
+
+/// Like JSON, for the files of a cosmic config, which contain a single RON value
+pub(super) fn patch_ron(content: &str, changes: &[Change]) -> anyhow::Result<String> {
+    patch_spanned::<Ron>(content, changes)
+}
+
+fn patch_spanned<F: SpannedFormat>(content: &str, changes: &[Change]) -> anyhow::Result<String> {
+    let mut content = content.to_string();
+    let indent = indent_of(&content, F::INDENT);
+
+    for change in changes {
+        // the positions move after each change
+        let document = F::parse(&content)?;
+
+        let (path, last) = match change {
+            Change::Set(path, _) | Change::Remove(path) => match path.split_last() {
+                Some((last, parent)) => (parent, Some(last)),
+                None => (&path[..], None),
+            },
+        };
+
+        let mut parent = &document;
+        for component in path {
+            parent = parent
+                .get(component)
+                .ok_or(anyhow!("no value at {component} in {path:?}"))?;
+        }
+
+        match (change, last) {
+            (Change::Set(_, value), None) => {
+                let old = &content[parent.start..parent.end];
+                let value = F::value(value, Some(old), &indent, "")?;
+                content.replace_range(parent.start..parent.end, &value);
+            }
+            (Change::Set(_, value), Some(last)) => match parent.get(last) {
+                Some(old) => {
+                    let base = line_indent(&content, old.start);
+                    let value = F::value(value, Some(&content[old.start..old.end]), &indent, base)?;
+                    content.replace_range(old.start..old.end, &value);
+                }
+                None => match (&parent.inner().kind, last) {
+                    (SpannedKind::Object(members), DataPathType::Name(key)) => match members.last()
+                    {
+                        Some(previous) => {
+                            let member_indent = line_indent(&content, previous.start).to_string();
+                            let object = &content[parent.inner().start..];
+                            let member = F::member(key, value, object, &indent, &member_indent)?;
+
+                            let after =
+                                previous.comma.map_or(previous.value.end, |comma| comma + 1);
+                            let line_end = content[after..]
+                                .find('\n')
+                                .map_or(content.len(), |pos| after + pos);
+                            let rest = content[after..line_end].trim();
+
+                            if content[parent.start..previous.start].contains('\n')
+                                && (rest.is_empty() || rest.starts_with("//"))
+                            {
+                                // on a new line, after the comment of the previous member.
+                                // The trailing `,` of the last member is kept
+                                let comma = if previous.comma.is_some() { "," } else { "" };
+                                content.insert_str(
+                                    line_end,
+                                    &format!("\n{member_indent}{member}{comma}"),
+                                );
+                                if previous.comma.is_none() {
+                                    content.insert(previous.value.end, ',');
+                                }
+                            } else if previous.comma.is_none() {
+                                content.insert_str(after, &format!(", {member}"));
+                            } else {
+                                content.insert_str(after, &format!(" {member},"));
+                            }
+                        }
+                        None => {
+                            let object = parent.inner();
+                            let object_indent = line_indent(&content, object.start).to_string();
+                            let member_indent = format!("{object_indent}{indent}");
+                            let member = F::member(
+                                key,
+                                value,
+                                &content[object.start..],
+                                &indent,
+                                &member_indent,
+                            )?;
+
+                            // after the name of a RON struct
+                            let open = object.start
+                                + content[object.start..object.end]
+                                    .find(['{', '('])
+                                    .ok_or(anyhow!("no object at {path:?}"))?
+                                + 1;
+
+                            // replace the blank content of `{ }`
+                            let inner = open..object.end - 1;
+                            if content[inner.clone()].trim().is_empty() {
+                                content.replace_range(inner, "");
+                            }
+
+                            content.insert_str(
+                                open,
+                                &format!("\n{member_indent}{member}\n{object_indent}"),
+                            );
+                        }
+                    },
+                    _ => bail!("can't set {last} in {path:?}"),
+                },
+            },
+            (Change::Remove(_), Some(DataPathType::Name(key))) => {
+                if let SpannedKind::Object(members) = &parent.inner().kind
+                    && let Some((pos, member)) = parent.member(key)
+                {
+                    let mut start = member.start;
+                    let mut end = member.comma.map_or(member.value.end, |comma| comma + 1);
+                    let previous_comma = pos.checked_sub(1).and_then(|pos| members[pos].comma);
+
+                    let line_start = content[..start].rfind('\n').map_or(0, |pos| pos + 1);
+                    let line_end = content[end..]
+                        .find('\n')
+                        .map_or(content.len(), |pos| end + pos + 1);
+                    let after = content[end..line_end].trim();
+
+                    if content[line_start..start].trim().is_empty()
+                        && (after.is_empty() || after.starts_with("//"))
+                    {
+                        // the whole line, with the comment after the value
+                        content.replace_range(line_start..line_end, "");
+
+                        // the `,` of the previous member is now trailing
+                        if member.comma.is_none()
+                            && let Some(comma) = previous_comma
+                        {
+                            content.remove(comma);
+                        }
+                    } else {
+                        match (member.comma, previous_comma) {
+                            (None, Some(comma)) => start = comma,
+                            _ => {
+                                end += content[end..].len()
+                                    - content[end..].trim_start_matches(' ').len()
+                            }
+                        }
+                        content.replace_range(start..end, "");
+                    }
+                }
+            }
+            (Change::Remove(_), _) => bail!("can't remove {path:?}"),
+        }
+    }
+
+    Ok(content)
+}
+
+/// The indentation of the first indented line, or `default`
+fn indent_of(content: &str, default: &str) -> String {
+    content
+        .lines()
+        .find_map(|line| {
+            let trimmed = line.trim_start();
+            (!trimmed.is_empty() && trimmed.len() < line.len())
+                .then(|| line[..line.len() - trimmed.len()].to_string())
+        })
+        .unwrap_or(default.to_string())
+}
+
+/// The whitespace at the start of the line of `pos`
+fn line_indent(content: &str, pos: usize) -> &str {
+    let line_start = content[..pos].rfind('\n').map_or(0, |pos| pos + 1);
+    let line = &content[line_start..pos];
+
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// A pretty value, whose lines after the first start at `base`
+fn json_string(value: &Value, indent: &str, base: &str) -> anyhow::Result<String> {
+    let mut buf = Vec::new();
+    let formatter = json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = json::Serializer::with_formatter(&mut buf, formatter);
+    serde::Serialize::serialize(value, &mut serializer)?;
+
+    Ok(String::from_utf8(buf)?.replace('\n', &format!("\n{base}")))
+}
+
+fn ron_string(value: &Value, indent: &str, base: &str) -> anyhow::Result<String> {
+    let config = ron::ser::PrettyConfig::new().indentor(indent.to_string());
+
+    Ok(ron::ser::to_string_pretty(value, config)?.replace('\n', &format!("\n{base}")))
+}
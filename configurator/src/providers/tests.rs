@@ -2,13 +2,19 @@ use std::{collections::HashMap, fs, marker::PhantomData, path::Path, sync::LazyL
 
 use configurator_utils::ConfigFormat;
 use figment::{
-    value::{Tag, Value},
-    Profile, Provider,
+    error::Actual,
+    value::{Empty, Num, Tag, Value},
+    Figment, Profile, Provider,
 };
+use schemars::{schema_for, JsonSchema};
 use serde::Serialize;
 use serial_test::serial;
 
-use crate::test_common::*;
+use super::WriteContext;
+use crate::{
+    node::{NodeContainer, Origin},
+    test_common::*,
+};
 
 use pretty_assertions::assert_eq;
 
@@ -16,8 +22,15 @@ use pretty_assertions::assert_eq;
 /// 2. read the value and assert equal
 fn write_and_read<P: AsRef<Path>>(path: P, format: &ConfigFormat, initial_value: &Value) {
     let _ = fs::remove_dir_all(path.as_ref());
+    let _ = fs::remove_file(path.as_ref());
 
-    super::write(path.as_ref(), format, initial_value).unwrap();
+    super::write(
+        path.as_ref(),
+        format,
+        initial_value,
+        WriteContext::default(),
+    )
+    .unwrap();
 
     let value = super::read_from_format(path.as_ref(), format);
 
@@ -25,12 +38,86 @@ fn write_and_read<P: AsRef<Path>>(path: P, format: &ConfigFormat, initial_value:
 
     let value = Value::Dict(Tag::Default, value);
 
-    assert_eq!(initial_value, &value);
+    let initial_value = match format {
+        // the keys are not written
+        ConfigFormat::Toml => remove_null(initial_value.clone()),
+        ConfigFormat::Kdl => remove_omitted(initial_value.clone()),
+        _ => initial_value.clone(),
+    };
+
+    assert_eq!(normalize(initial_value), normalize(value));
+}
+
+/// The value of the file, like the tree tracks it after a reload
+fn read<P: AsRef<Path>>(path: P, format: &ConfigFormat) -> Value {
+    let dict = super::read_from_format(path.as_ref(), format)
+        .data()
+        .unwrap()
+        .remove(&Profile::Default)
+        .unwrap_or_default();
+
+    Value::Dict(Tag::Default, dict)
+}
+
+fn remove_null(value: Value) -> Value {
+    match value {
+        Value::Dict(tag, dict) => Value::Dict(
+            tag,
+            dict.into_iter()
+                .filter(|(_, v)| !matches!(v, Value::Empty(..)))
+                .map(|(k, v)| (k, remove_null(v)))
+                .collect(),
+        ),
+        Value::Array(tag, array) => Value::Array(tag, array.into_iter().map(remove_null).collect()),
+        value => value,
+    }
+}
+
+/// Formats don't keep the width of numbers, only their value
+fn normalize(value: Value) -> Value {
+    match value {
+        Value::Num(tag, num) => Value::Num(
+            tag,
+            match num.to_actual() {
+                Actual::Unsigned(n) => i128::try_from(n).map_or(Num::U128(n), Num::I128),
+                Actual::Signed(n) => Num::I128(n),
+                _ => Num::F64(num.to_f64().unwrap()),
+            },
+        ),
+        Value::Dict(tag, dict) => Value::Dict(
+            tag,
+            dict.into_iter().map(|(k, v)| (k, normalize(v))).collect(),
+        ),
+        Value::Array(tag, array) => Value::Array(tag, array.into_iter().map(normalize).collect()),
+        // null and unit are the same in the formats
+        Value::Empty(tag, _) => Value::Empty(tag, Empty::None),
+        value => value,
+    }
+}
+
+/// KDL writes empty arrays and `false` by omitting the node
+fn remove_omitted(value: Value) -> Value {
+    match value {
+        Value::Dict(tag, dict) => Value::Dict(
+            tag,
+            dict.into_iter()
+                .filter(|(_, v)| {
+                    !matches!(v, Value::Array(_, array) if array.is_empty())
+                        && !matches!(v, Value::Bool(_, false))
+                })
+                .map(|(k, v)| (k, remove_omitted(v)))
+                .collect(),
+        ),
+        Value::Array(tag, array) => {
+            Value::Array(tag, array.into_iter().map(remove_omitted).collect())
+        }
+        value => value,
+    }
 }
 
 fn write_and_read_common<S: Default + Serialize>(format: &ConfigFormat) {
     write_and_read(
-        "tests/cosmic_ron/config1",
+        format!("tests/{format}/config1"),
         format,
         &Value::serialize(S::default()).unwrap(),
     );
@@ -60,6 +147,12 @@ fn test_float_ron() {
     write_and_read_common::<TestFloat>(&ConfigFormat::CosmicRon);
 }
 
+#[test]
+#[serial]
+fn test_number_kinds_ron() {
+    write_and_read_common::<TestNumberKinds>(&ConfigFormat::CosmicRon);
+}
+
 #[test]
 #[serial]
 fn test_enum_simple_ron() {
@@ -107,3 +200,1053 @@ fn test_hash_map_ron() {
 fn test_very_complex_ron() {
     write_and_read_common::<TestVeryComplex>(&ConfigFormat::CosmicRon);
 }
+
+#[test]
+#[serial]
+fn test_number_kinds_json() {
+    write_and_read_common::<TestNumberKinds>(&ConfigFormat::Json);
+}
+
+#[test]
+#[serial]
+fn test_very_complex_json() {
+    write_and_read_common::<TestVeryComplex>(&ConfigFormat::Json);
+}
+
+#[test]
+#[serial]
+fn test_bool_toml() {
+    write_and_read_common::<TestBool>(&ConfigFormat::Toml);
+}
+
+#[test]
+#[serial]
+fn test_string_toml() {
+    write_and_read_common::<TestString>(&ConfigFormat::Toml);
+}
+
+#[test]
+#[serial]
+fn test_number_toml() {
+    write_and_read_common::<TestNumber>(&ConfigFormat::Toml);
+}
+
+#[test]
+#[serial]
+fn test_float_toml() {
+    write_and_read_common::<TestFloat>(&ConfigFormat::Toml);
+}
+
+#[test]
+#[serial]
+fn test_enum_simple_toml() {
+    write_and_read_common::<TestEnumSimple>(&ConfigFormat::Toml);
+}
+
+#[test]
+#[serial]
+fn test_enum_complex_toml() {
+    write_and_read_common::<TestEnumComplex>(&ConfigFormat::Toml);
+}
+
+#[test]
+#[serial]
+fn test_option_toml() {
+    write_and_read_common::<TestOption>(&ConfigFormat::Toml);
+}
+
+#[test]
+#[serial]
+fn test_option_complex_toml() {
+    write_and_read_common::<TestOptionComplex>(&ConfigFormat::Toml);
+}
+
+#[test]
+#[serial]
+fn test_option_enum_toml() {
+    write_and_read_common::<TestOptionEnum>(&ConfigFormat::Toml);
+}
+
+#[test]
+#[serial]
+fn test_vec_toml() {
+    write_and_read_common::<TestVec>(&ConfigFormat::Toml);
+}
+
+#[test]
+#[serial]
+fn test_hash_map_toml() {
+    write_and_read_common::<TestHashMap>(&ConfigFormat::Toml);
+}
+
+#[test]
+#[serial]
+fn test_very_complex_toml() {
+    write_and_read_common::<TestVeryComplex>(&ConfigFormat::Toml);
+}
+
+#[test]
+#[serial]
+fn test_enum_tagged_toml() {
+    write_and_read_common::<TestEnumTagged>(&ConfigFormat::Toml);
+}
+
+/// TOML has no null in arrays, and its integers are i64
+#[test]
+#[serial]
+fn test_unsupported_toml() {
+    let path = "tests/toml/config1";
+
+    for value in [
+        Value::serialize(TestTuple::default()).unwrap(),
+        Value::serialize(TestNumberKinds::default()).unwrap(),
+    ] {
+        assert!(super::write(path, &ConfigFormat::Toml, &value, WriteContext::default()).is_err());
+    }
+}
+
+#[test]
+#[serial]
+fn test_bool_yaml() {
+    write_and_read_common::<TestBool>(&ConfigFormat::Yaml);
+}
+
+#[test]
+#[serial]
+fn test_string_yaml() {
+    write_and_read_common::<TestString>(&ConfigFormat::Yaml);
+}
+
+#[test]
+#[serial]
+fn test_number_yaml() {
+    write_and_read_common::<TestNumber>(&ConfigFormat::Yaml);
+}
+
+#[test]
+#[serial]
+fn test_float_yaml() {
+    write_and_read_common::<TestFloat>(&ConfigFormat::Yaml);
+}
+
+#[test]
+#[serial]
+fn test_number_kinds_yaml() {
+    write_and_read_common::<TestNumberKinds>(&ConfigFormat::Yaml);
+}
+
+#[test]
+#[serial]
+fn test_enum_simple_yaml() {
+    write_and_read_common::<TestEnumSimple>(&ConfigFormat::Yaml);
+}
+
+#[test]
+#[serial]
+fn test_enum_complex_yaml() {
+    write_and_read_common::<TestEnumComplex>(&ConfigFormat::Yaml);
+}
+
+#[test]
+#[serial]
+fn test_option_yaml() {
+    write_and_read_common::<TestOption>(&ConfigFormat::Yaml);
+}
+
+#[test]
+#[serial]
+fn test_option_complex_yaml() {
+    write_and_read_common::<TestOptionComplex>(&ConfigFormat::Yaml);
+}
+
+#[test]
+#[serial]
+fn test_option_enum_yaml() {
+    write_and_read_common::<TestOptionEnum>(&ConfigFormat::Yaml);
+}
+
+#[test]
+#[serial]
+fn test_tuple_yaml() {
+    write_and_read_common::<TestTuple>(&ConfigFormat::Yaml);
+}
+
+#[test]
+#[serial]
+fn test_vec_yaml() {
+    write_and_read_common::<TestVec>(&ConfigFormat::Yaml);
+}
+
+#[test]
+#[serial]
+fn test_hash_map_yaml() {
+    write_and_read_common::<TestHashMap>(&ConfigFormat::Yaml);
+}
+
+#[test]
+#[serial]
+fn test_very_complex_yaml() {
+    write_and_read_common::<TestVeryComplex>(&ConfigFormat::Yaml);
+}
+
+#[test]
+#[serial]
+fn test_enum_tagged_yaml() {
+    write_and_read_common::<TestEnumTagged>(&ConfigFormat::Yaml);
+}
+
+#[test]
+#[serial]
+fn test_block_scalar_yaml() {
+    let path = "tests/yaml/config1";
+
+    let value = Value::Dict(
+        Tag::Default,
+        [("x".to_string(), Value::from("line 1\nline 2"))].into(),
+    );
+
+    write_and_read(path, &ConfigFormat::Yaml, &value);
+
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        "x: |-\n  line 1\n  line 2\n"
+    );
+}
+
+#[test]
+#[serial]
+fn test_non_string_key_yaml() {
+    let path = "tests/yaml/config1";
+
+    fs::create_dir_all("tests/yaml").unwrap();
+    fs::write(path, "a:\n  1: b\n").unwrap();
+
+    let error = super::read_from_format(path, &ConfigFormat::Yaml)
+        .data()
+        .unwrap_err();
+
+    assert!(error.to_string().contains("expected a string key"));
+}
+
+fn dict(content: json::Value) -> Value {
+    Value::serialize(content).unwrap()
+}
+
+#[test]
+#[serial]
+fn test_patch_toml() {
+    let path = "tests/toml/config1";
+
+    fs::create_dir_all("tests/toml").unwrap();
+    fs::write(
+        path,
+        r#"# my config
+b = 'hello' # inline comment
+a = 1
+
+# the table
+[c]
+y = [1, 2]
+x = true
+"#,
+    )
+    .unwrap();
+
+    let value = dict(json::json!({
+        "a": 2,
+        "b": "hello",
+        "c": { "x": true, "y": [1, 3], "z": "new" },
+    }));
+
+    super::write(path, &ConfigFormat::Toml, &value, WriteContext::default()).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        r#"# my config
+b = 'hello' # inline comment
+a = 2
+
+# the table
+[c]
+y = [1, 3]
+x = true
+z = "new"
+"#
+    );
+
+    let value = dict(json::json!({ "a": 2, "c": { "x": true } }));
+
+    write_and_read(path, &ConfigFormat::Toml, &value);
+}
+
+#[test]
+#[serial]
+fn test_patch_json() {
+    let path = "tests/json/config1";
+
+    fs::create_dir_all("tests/json").unwrap();
+    fs::write(path, r#"{ "b": 1, "a": { "y": 1, "x": 2 } }"#).unwrap();
+
+    let value = dict(json::json!({ "a": { "x": 3, "y": 1 }, "b": 1, "c": 4 }));
+
+    super::write(path, &ConfigFormat::Json, &value, WriteContext::default()).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        r#"{ "b": 1, "a": { "y": 1, "x": 3 }, "c": 4 }"#
+    );
+
+    let value = dict(json::json!({ "a": { "x": 3, "y": 1 } }));
+    let tracked = read(path, &ConfigFormat::Json);
+
+    let context = WriteContext {
+        tracked: Some(&tracked),
+        ..Default::default()
+    };
+    super::write(path, &ConfigFormat::Json, &value, context).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        r#"{ "a": { "y": 1, "x": 3 } }"#
+    );
+}
+
+#[test]
+#[serial]
+fn test_keep_untracked_keys() {
+    let path = "tests/json/config2";
+
+    fs::create_dir_all("tests/json").unwrap();
+    fs::write(
+        path,
+        r#"{ "a": 1, "b": 2, "c": { "x": 1, "by-hand": 2 }, "by-hand": { "x": 1 } }"#,
+    )
+    .unwrap();
+
+    // the `by-hand` keys are not in the schema, and `b` and `c` are reset
+    let tracked = dict(json::json!({ "a": 1, "b": 2, "c": { "x": 1 } }));
+    let value = dict(json::json!({ "a": 3 }));
+
+    let context = WriteContext {
+        tracked: Some(&tracked),
+        ..Default::default()
+    };
+    super::write(path, &ConfigFormat::Json, &value, context).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        r#"{ "a": 3, "c": { "by-hand": 2 }, "by-hand": { "x": 1 } }"#
+    );
+
+    let path = Path::new("tests/cosmic_ron/config3");
+
+    let _ = fs::remove_dir_all(path);
+    fs::create_dir_all(path).unwrap();
+    fs::write(path.join("a"), "1").unwrap();
+    fs::write(path.join("b"), "2").unwrap();
+    fs::write(path.join("c"), "(x: 1, by_hand: 2)").unwrap();
+    fs::write(path.join("by-hand"), "3").unwrap();
+
+    let tracked = dict(json::json!({ "a": 1, "b": 2, "c": { "x": 1 } }));
+    let context = WriteContext {
+        tracked: Some(&tracked),
+        ..Default::default()
+    };
+    super::write(path, &ConfigFormat::CosmicRon, &value, context).unwrap();
+
+    assert_eq!(fs::read_to_string(path.join("a")).unwrap(), "3");
+    assert!(!path.join("b").exists());
+    assert_eq!(fs::read_to_string(path.join("c")).unwrap(), "(by_hand: 2)");
+    assert_eq!(fs::read_to_string(path.join("by-hand")).unwrap(), "3");
+}
+
+#[test]
+#[serial]
+fn test_patch_jsonc() {
+    let path = "tests/json/config1";
+
+    fs::create_dir_all("tests/json").unwrap();
+    fs::write(
+        path,
+        r#"// by hand
+{
+    "a": 1, // the a
+    /* the b */
+    "b": [1, 2],
+    "c": {},
+    "d": true,
+    "e": "x", // removed
+}
+"#,
+    )
+    .unwrap();
+
+    let value = dict(json::json!({
+        "a": 2,
+        "b": [1, 3],
+        "c": { "x": { "y": 1 } },
+        "d": true,
+        "f": [1],
+    }));
+
+    let tracked = read(path, &ConfigFormat::Json);
+
+    let context = WriteContext {
+        tracked: Some(&tracked),
+        ..Default::default()
+    };
+    super::write(path, &ConfigFormat::Json, &value, context).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        r#"// by hand
+{
+    "a": 2, // the a
+    /* the b */
+    "b": [1, 3],
+    "c": {
+        "x": {
+            "y": 1
+        }
+    },
+    "d": true,
+    "f": [
+        1
+    ],
+}
+"#
+    );
+
+    let read = super::read_from_format(path, &ConfigFormat::Json)
+        .data()
+        .unwrap()
+        .remove(&Profile::Default)
+        .unwrap();
+    assert_eq!(Value::Dict(Tag::Default, read), value);
+}
+
+#[test]
+#[serial]
+fn test_patch_ron() {
+    let path = Path::new("tests/cosmic_ron/config1");
+
+    let _ = fs::remove_dir_all(path);
+    fs::create_dir_all(path).unwrap();
+    fs::write(path.join("a"), "// by hand\n1").unwrap();
+    fs::write(path.join("b"), "2").unwrap();
+    fs::write(path.join("c"), "3").unwrap();
+
+    let value = dict(json::json!({ "a": 1, "b": 4 }));
+    let tracked = read(path, &ConfigFormat::CosmicRon);
+
+    let context = WriteContext {
+        tracked: Some(&tracked),
+        ..Default::default()
+    };
+    super::write(path, &ConfigFormat::CosmicRon, &value, context).unwrap();
+
+    assert_eq!(fs::read_to_string(path.join("a")).unwrap(), "// by hand\n1");
+    assert_eq!(fs::read_to_string(path.join("b")).unwrap(), "4");
+    assert!(!path.join("c").exists());
+}
+
+#[test]
+#[serial]
+fn test_patch_ron_comments() {
+    let path = Path::new("tests/cosmic_ron/config2");
+
+    let _ = fs::remove_dir_all(path);
+    fs::create_dir_all(path).unwrap();
+    fs::write(
+        path.join("theme"),
+        r#"// the theme
+(
+    // dark or light
+    mode: Dark,
+    accent: Some((r: 1.0, g: 0.5)),
+    size: 10, // px
+    radius: None,
+)
+"#,
+    )
+    .unwrap();
+
+    let value = dict(json::json!({
+        "theme": {
+            "mode": "Light",
+            "accent": { "r": 1.0, "g": 0.25 },
+            "size": 12,
+            "radius": 4,
+            "name": "x",
+        },
+    }));
+
+    super::write(
+        path,
+        &ConfigFormat::CosmicRon,
+        &value,
+        WriteContext::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(path.join("theme")).unwrap(),
+        r#"// the theme
+(
+    // dark or light
+    mode: Light,
+    accent: Some((r: 1.0, g: 0.25)),
+    size: 12, // px
+    radius: Some(4),
+    name: "x",
+)
+"#
+    );
+}
+
+#[test]
+#[serial]
+fn test_bool_kdl() {
+    write_and_read_common::<TestBool>(&ConfigFormat::Kdl);
+}
+
+#[test]
+#[serial]
+fn test_string_kdl() {
+    write_and_read_common::<TestString>(&ConfigFormat::Kdl);
+}
+
+#[test]
+#[serial]
+fn test_number_kdl() {
+    write_and_read_common::<TestNumber>(&ConfigFormat::Kdl);
+}
+
+#[test]
+#[serial]
+fn test_float_kdl() {
+    write_and_read_common::<TestFloat>(&ConfigFormat::Kdl);
+}
+
+#[test]
+#[serial]
+fn test_enum_simple_kdl() {
+    write_and_read_common::<TestEnumSimple>(&ConfigFormat::Kdl);
+}
+
+#[test]
+#[serial]
+fn test_enum_complex_kdl() {
+    write_and_read_common::<TestEnumComplex>(&ConfigFormat::Kdl);
+}
+
+#[test]
+#[serial]
+fn test_option_kdl() {
+    write_and_read_common::<TestOption>(&ConfigFormat::Kdl);
+}
+
+#[test]
+#[serial]
+fn test_option_complex_kdl() {
+    write_and_read_common::<TestOptionComplex>(&ConfigFormat::Kdl);
+}
+
+#[test]
+#[serial]
+fn test_option_enum_kdl() {
+    write_and_read_common::<TestOptionEnum>(&ConfigFormat::Kdl);
+}
+
+#[test]
+#[serial]
+fn test_tuple_kdl() {
+    write_and_read_common::<TestTuple>(&ConfigFormat::Kdl);
+}
+
+#[test]
+#[serial]
+fn test_vec_kdl() {
+    write_and_read_common::<TestVec>(&ConfigFormat::Kdl);
+}
+
+#[test]
+#[serial]
+fn test_hash_map_kdl() {
+    write_and_read_common::<TestHashMap>(&ConfigFormat::Kdl);
+}
+
+#[test]
+#[serial]
+fn test_very_complex_kdl() {
+    write_and_read_common::<TestVeryComplex>(&ConfigFormat::Kdl);
+}
+
+#[test]
+#[serial]
+fn test_enum_tagged_kdl() {
+    write_and_read_common::<TestEnumTagged>(&ConfigFormat::Kdl);
+}
+
+/// KDL integers are i64
+#[test]
+#[serial]
+fn test_unsupported_kdl() {
+    let value = Value::serialize(TestNumberKinds::default()).unwrap();
+
+    assert!(super::write(
+        "tests/kdl/config1",
+        &ConfigFormat::Kdl,
+        &value,
+        WriteContext::default()
+    )
+    .is_err());
+}
+
+#[test]
+#[serial]
+fn test_mapping_kdl() {
+    let path = "tests/kdl/config1";
+    let content = r##"
+prefer-no-csd
+// startup
+spawn-at-startup "waybar"
+spawn-at-startup "mako" "-c" "config"
+layout {
+    gaps 16 // default
+    focus-ring width=4 { active-color "#7fc8ff"; }
+    presets { - 0.5; }
+}
+output "eDP-1" {
+    scale 2.0
+}
+"##;
+
+    fs::create_dir_all("tests/kdl").unwrap();
+    fs::write(path, content).unwrap();
+
+    let value = super::read_from_format(path, &ConfigFormat::Kdl)
+        .data()
+        .unwrap()
+        .remove(&Profile::Default)
+        .unwrap();
+
+    assert_eq!(
+        json::to_value(&value).unwrap(),
+        json::json!({
+            "prefer-no-csd": true,
+            "spawn-at-startup": ["waybar", ["mako", "-c", "config"]],
+            "layout": {
+                "gaps": 16,
+                "focus-ring": { "width": 4, "active-color": "#7fc8ff" },
+                "presets": [0.5],
+            },
+            "output": { "arguments": "eDP-1", "scale": 2.0 },
+        })
+    );
+
+    // nothing changed
+    super::write(
+        path,
+        &ConfigFormat::Kdl,
+        &Value::Dict(Tag::Default, value),
+        WriteContext::default(),
+    )
+    .unwrap();
+    assert_eq!(fs::read_to_string(path).unwrap(), content);
+
+    let value = dict(json::json!({
+        "prefer-no-csd": false,
+        "spawn-at-startup": ["waybar", ["mako", "-c", "other"]],
+        "layout": {
+            "gaps": 16,
+            "focus-ring": { "width": 6, "active-color": "#7fc8ff", "off": true },
+            "presets": [0.5, 0.75],
+        },
+        "output": { "arguments": "HDMI-1", "scale": 2.0 },
+    }));
+
+    super::write(path, &ConfigFormat::Kdl, &value, WriteContext::default()).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        r##"
+// startup
+spawn-at-startup "waybar"
+spawn-at-startup "mako" "-c" "other"
+layout {
+    gaps 16 // default
+    focus-ring width=6 { active-color "#7fc8ff"; off; }
+    presets 0.5 0.75
+}
+output "HDMI-1" {
+    scale 2.0
+}
+"##
+    );
+
+    let read = super::read_from_format(path, &ConfigFormat::Kdl)
+        .data()
+        .unwrap()
+        .remove(&Profile::Default)
+        .unwrap();
+    assert_eq!(
+        normalize(Value::Dict(Tag::Default, read)),
+        normalize(remove_omitted(value))
+    );
+}
+
+#[test]
+#[serial]
+fn test_keep_untracked_kdl() {
+    let path = "tests/kdl/config2";
+    let content = r##"layout {
+    gaps 16
+    // by hand
+    center-focused-column "never"
+}
+cursor size=24 theme="x"
+window-rule { opacity 0.9; }
+"##;
+
+    fs::create_dir_all("tests/kdl").unwrap();
+    fs::write(path, content).unwrap();
+
+    // `center-focused-column` and `window-rule` are not in the schema
+    let tracked = dict(json::json!({
+        "layout": { "gaps": 16 },
+        "cursor": { "size": 24, "theme": "x" },
+    }));
+    let value = dict(json::json!({ "cursor": { "size": 32 } }));
+
+    let context = WriteContext {
+        tracked: Some(&tracked),
+        ..Default::default()
+    };
+    super::write(path, &ConfigFormat::Kdl, &value, context).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        r##"layout {
+    // by hand
+    center-focused-column "never"
+}
+cursor size=32
+window-rule { opacity 0.9; }
+"##
+    );
+}
+
+#[test]
+#[serial]
+fn test_false_over_true_kdl() {
+    let path = "tests/kdl/config3";
+    let _ = fs::remove_file(path);
+
+    // e.g. a system config with `prefer-no-csd`
+    let inherited = dict(json::json!({ "prefer-no-csd": true, "input": { "warp": true } }));
+    let value = dict(json::json!({ "prefer-no-csd": false, "input": { "warp": false } }));
+
+    let context = WriteContext {
+        inherited: Some(&inherited),
+        ..Default::default()
+    };
+    super::write(path, &ConfigFormat::Kdl, &value, context).unwrap();
+    assert_eq!(
+        json::to_value(read(path, &ConfigFormat::Kdl)).unwrap(),
+        json::to_value(&value).unwrap()
+    );
+
+    // a flag of the file is patched
+    fs::write(path, "prefer-no-csd\n").unwrap();
+    super::write(path, &ConfigFormat::Kdl, &value, context).unwrap();
+    assert_eq!(
+        json::to_value(read(path, &ConfigFormat::Kdl)).unwrap(),
+        json::to_value(&value).unwrap()
+    );
+
+    // omitting the node gives the inherited `false`
+    let inherited = dict(json::json!({ "prefer-no-csd": false, "input": { "warp": false } }));
+    let context = WriteContext {
+        tracked: Some(&value),
+        inherited: Some(&inherited),
+    };
+    super::write(path, &ConfigFormat::Kdl, &value, context).unwrap();
+    assert_eq!(
+        json::to_value(read(path, &ConfigFormat::Kdl)).unwrap(),
+        json::json!({ "input": {} })
+    );
+}
+
+/// INI is untyped, so the values are read through the tree
+fn write_and_apply_common<S: Default + Serialize + JsonSchema>(format: &ConfigFormat) {
+    let path = format!("tests/{format}/config1");
+    let _ = fs::remove_file(&path);
+
+    let initial_value = Value::serialize(S::default()).unwrap();
+
+    super::write(&path, format, &initial_value, WriteContext::default()).unwrap();
+
+    let mut tree = NodeContainer::from_json_schema(&schema_for!(S));
+    tree.apply_layers(
+        &Figment::new().merge(super::read_from_format(&path, format)),
+        &|_| Origin::User,
+        super::lenient(format),
+    )
+    .unwrap();
+
+    assert_eq!(
+        normalize(remove_null(initial_value)),
+        normalize(tree.to_value(&Tag::Default).unwrap())
+    );
+}
+
+#[test]
+#[serial]
+fn test_bool_ini() {
+    write_and_apply_common::<TestBool>(&ConfigFormat::Ini);
+}
+
+#[test]
+#[serial]
+fn test_string_ini() {
+    write_and_apply_common::<TestString>(&ConfigFormat::Ini);
+}
+
+#[test]
+#[serial]
+fn test_number_ini() {
+    write_and_apply_common::<TestNumber>(&ConfigFormat::Ini);
+}
+
+#[test]
+#[serial]
+fn test_float_ini() {
+    write_and_apply_common::<TestFloat>(&ConfigFormat::Ini);
+}
+
+#[test]
+#[serial]
+fn test_enum_simple_ini() {
+    write_and_apply_common::<TestEnumSimple>(&ConfigFormat::Ini);
+}
+
+#[test]
+#[serial]
+fn test_option_ini() {
+    write_and_apply_common::<TestOption>(&ConfigFormat::Ini);
+}
+
+#[test]
+#[serial]
+fn test_sections_ini() {
+    write_and_apply_common::<TestSections>(&ConfigFormat::Ini);
+}
+
+#[test]
+#[serial]
+fn test_patch_ini() {
+    let path = "tests/ini/config1";
+
+    fs::create_dir_all("tests/ini").unwrap();
+    fs::write(
+        path,
+        r#"# my config
+enabled = yes
+
+[Settings]
+; the name
+x = a
+y=1
+old=1
+
+[Removed]
+z=1
+"#,
+    )
+    .unwrap();
+
+    let value = dict(json::json!({
+        "enabled": true,
+        "Settings": { "x": "b", "y": 1, "new": 2 },
+        "Added": { "z": 3 },
+    }));
+
+    let tracked = read(path, &ConfigFormat::Ini);
+
+    let context = WriteContext {
+        tracked: Some(&tracked),
+        ..Default::default()
+    };
+    super::write(path, &ConfigFormat::Ini, &value, context).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        r#"# my config
+enabled = yes
+
+[Settings]
+; the name
+x = b
+y=1
+new=2
+
+[Added]
+z=3
+"#
+    );
+}
+
+#[test]
+#[serial]
+fn test_keep_untracked_ini() {
+    let path = "tests/ini/config2";
+
+    fs::create_dir_all("tests/ini").unwrap();
+    fs::write(
+        path,
+        r#"[Unit]
+Description=x
+# by hand
+After=network.target
+
+[Install]
+WantedBy=default.target
+"#,
+    )
+    .unwrap();
+
+    // `After` is not in the schema, and `Install` is reset
+    let tracked = dict(json::json!({
+        "Unit": { "Description": "x" },
+        "Install": { "WantedBy": "default.target" },
+    }));
+    let value = dict(json::json!({ "Unit": { "Description": "y" } }));
+
+    let context = WriteContext {
+        tracked: Some(&tracked),
+        ..Default::default()
+    };
+    super::write(path, &ConfigFormat::Ini, &value, context).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        r#"[Unit]
+Description=y
+# by hand
+After=network.target
+"#
+    );
+}
+
+#[test]
+#[serial]
+fn test_repeated_keys_ini() {
+    let path = "tests/ini/override.conf";
+    let content = r#"[Service]
+# reset the command of the unit
+ExecStart=
+ExecStart=/bin/x
+Restart=always
+"#;
+
+    fs::create_dir_all("tests/ini").unwrap();
+    fs::write(path, content).unwrap();
+
+    let value = super::read_from_format(path, &ConfigFormat::Ini)
+        .data()
+        .unwrap()
+        .remove(&Profile::Default)
+        .unwrap();
+
+    assert_eq!(
+        json::to_value(&value).unwrap(),
+        json::json!({
+            "Service": { "ExecStart": ["", "/bin/x"], "Restart": "always" },
+        })
+    );
+
+    super::write(
+        path,
+        &ConfigFormat::Ini,
+        &Value::from(value),
+        WriteContext::default(),
+    )
+    .unwrap();
+    assert_eq!(fs::read_to_string(path).unwrap(), content);
+
+    let value = dict(json::json!({
+        "Service": { "ExecStart": ["", "/bin/y", "/bin/z"], "Restart": "always" },
+    }));
+    super::write(path, &ConfigFormat::Ini, &value, WriteContext::default()).unwrap();
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        r#"[Service]
+# reset the command of the unit
+ExecStart=
+ExecStart=/bin/y
+ExecStart=/bin/z
+Restart=always
+"#
+    );
+
+    let value = dict(json::json!({
+        "Service": { "ExecStart": "/bin/x", "Restart": "always" },
+    }));
+    super::write(path, &ConfigFormat::Ini, &value, WriteContext::default()).unwrap();
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        r#"[Service]
+# reset the command of the unit
+ExecStart=/bin/x
+Restart=always
+"#
+    );
+}
+
+#[test]
+#[serial]
+fn test_repeated_sections_ini() {
+    let path = "tests/ini/repeated.service";
+    let content = r#"[A]
+x=1
+
+[B]
+z=3
+
+[A]
+y=2
+"#;
+
+    fs::create_dir_all("tests/ini").unwrap();
+    fs::write(path, content).unwrap();
+
+    let value = read(path, &ConfigFormat::Ini);
+    assert_eq!(
+        json::to_value(&value).unwrap(),
+        json::json!({ "A": { "x": "1", "y": "2" }, "B": { "z": "3" } })
+    );
+
+    super::write(path, &ConfigFormat::Ini, &value, WriteContext::default()).unwrap();
+    assert_eq!(fs::read_to_string(path).unwrap(), content);
+
+    // a new key goes in the last occurrence of the section
+    let value = dict(json::json!({
+        "A": { "x": "4", "y": "2", "w": "5" },
+        "B": { "z": "3" },
+    }));
+    super::write(path, &ConfigFormat::Ini, &value, WriteContext::default()).unwrap();
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        r#"[A]
+x=4
+
+[B]
+z=3
+
+[A]
+y=2
+w=5
+"#
+    );
+}
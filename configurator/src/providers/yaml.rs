@@ -0,0 +1,161 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use figment::{
+    value::{Dict, Value},
+    Metadata, Profile, Provider,
+};
+use serde::de::{
+    self, EnumAccess, Error, IgnoredAny, MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+use serde::{Deserialize, Deserializer};
+
+pub struct YamlProvider {
+    path: PathBuf,
+}
+
+impl YamlProvider {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl Provider for YamlProvider {
+    fn metadata(&self) -> figment::Metadata {
+        Metadata::from("yaml provider", self.path.as_path())
+    }
+
+    fn data(
+        &self,
+    ) -> Result<figment::value::Map<figment::Profile, figment::value::Dict>, figment::Error> {
+        self.data_impl().map_err(figment::Error::custom)
+    }
+}
+
+impl YamlProvider {
+    fn data_impl(
+        &self,
+    ) -> anyhow::Result<figment::value::Map<figment::Profile, figment::value::Dict>> {
+        // like the file providers of figment
+        if !self.path.exists() {
+            return Ok(figment::value::Map::new());
+        }
+
+        let content = fs::read_to_string(&self.path)?;
+
+        // figment would silently turn `1: a` into `"1": a`
+        serde_yaml::from_str::<StringKeys>(&content)?;
+
+        let dict: Option<Dict> = serde_yaml::from_str(&content)?;
+
+        Ok(figment::value::Map::from([(
+            Profile::Default,
+            dict.unwrap_or_default(),
+        )]))
+    }
+}
+
+/// Accept any value whose keys are strings, because only those can be represented in the tree.
+/// serde_yaml::Value can't be used, it only knows i64 and f64
+struct StringKeys;
+
+impl<'de> Deserialize<'de> for StringKeys {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(StringKeysVisitor)
+    }
+}
+
+struct StringKeysVisitor;
+
+impl<'de> Visitor<'de> for StringKeysVisitor {
+    type Value = StringKeys;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any YAML value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(StringKeys)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(StringKeys)
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Self::Value, E> {
+        Ok(StringKeys)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(StringKeys)
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Self::Value, E> {
+        Ok(StringKeys)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(StringKeys)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(StringKeys)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(StringKeys)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(StringKeys)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        StringKeys::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        while seq.next_element::<StringKeys>()?.is_some() {}
+        Ok(StringKeys)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        while map.next_key::<StringKey>()?.is_some() {
+            map.next_value::<StringKeys>()?;
+        }
+        Ok(StringKeys)
+    }
+
+    // tagged values
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        let (_, variant) = data.variant::<IgnoredAny>()?;
+        variant.newtype_variant::<StringKeys>()
+    }
+}
+
+/// `String` would accept any scalar
+struct StringKey;
+
+impl<'de> Deserialize<'de> for StringKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(StringKeyVisitor)
+    }
+}
+
+struct StringKeyVisitor;
+
+impl Visitor<'_> for StringKeyVisitor {
+    type Value = StringKey;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string key, other keys are not supported")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(StringKey)
+    }
+}
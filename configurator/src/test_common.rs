@@ -110,6 +110,47 @@ pub struct TestFloat {
     x: f32,
 }
 
+/// Values that don't survive a conversion to f64 or i64
+#[derive(Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TestNumberKinds {
+    u8: u8,
+    u16: u16,
+    u32: u32,
+    u64: u64,
+    u128: u128,
+    usize: usize,
+    i8: i8,
+    i16: i16,
+    i32: i32,
+    i64: i64,
+    i128: i128,
+    isize: isize,
+    f32: f32,
+    f64: f64,
+}
+
+impl Default for TestNumberKinds {
+    fn default() -> Self {
+        Self {
+            u8: u8::MAX,
+            u16: u16::MAX,
+            u32: u32::MAX,
+            u64: u64::MAX,
+            u128: u128::MAX,
+            usize: usize::MAX,
+            i8: i8::MIN,
+            i16: i16::MIN,
+            i32: i32::MIN,
+            i64: i64::MIN,
+            i128: i128::MIN,
+            isize: isize::MIN,
+            f32: 0.0625,
+            f64: 0.1 + 0.2,
+        }
+    }
+}
+
 #[derive(Clone, Debug, JsonSchema, Serialize, Deserialize, Default)]
 pub enum EnumSimple {
     #[default]
@@ -142,6 +183,12 @@ pub struct TestOptionComplex {
     x: Option<Complex>,
 }
 
+#[derive(Clone, Debug, JsonSchema, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct TestOptionEnum {
+    x: Option<EnumSimple>,
+}
+
 #[derive(Clone, Debug, JsonSchema, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct TestTuple {
@@ -167,3 +214,82 @@ pub struct TestVeryComplex {
     y: EnumComplex,
     v: (Vec<EnumComplex>, HashMap<String, EnumComplex>),
 }
+
+/// Flat enough for INI: keys at the root and a section
+#[derive(Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TestSections {
+    enabled: bool,
+    ratio: Option<f64>,
+    choice: EnumSimple,
+    #[serde(rename = "Settings")]
+    settings: Complex,
+}
+
+impl Default for TestSections {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ratio: Some(0.5),
+            choice: EnumSimple::B,
+            settings: Complex::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum EnumInternal {
+    A,
+    B { a: i32 },
+    C(Complex),
+}
+
+#[derive(Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(tag = "t", content = "c")]
+pub enum EnumAdjacent {
+    A,
+    B(i32),
+    C { a: i32, b: String },
+}
+
+#[derive(Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EnumUntagged {
+    A(bool),
+    B { a: i32 },
+    C { b: String },
+}
+
+#[derive(Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TestEnumTagged {
+    internal: Vec<EnumInternal>,
+    adjacent: Vec<EnumAdjacent>,
+    untagged: Vec<EnumUntagged>,
+}
+
+impl Default for TestEnumTagged {
+    fn default() -> Self {
+        Self {
+            internal: vec![
+                EnumInternal::A,
+                EnumInternal::B { a: 1 },
+                EnumInternal::C(Complex::default()),
+            ],
+            adjacent: vec![
+                EnumAdjacent::A,
+                EnumAdjacent::B(2),
+                EnumAdjacent::C {
+                    a: 3,
+                    b: "c".into(),
+                },
+            ],
+            untagged: vec![
+                EnumUntagged::A(true),
+                EnumUntagged::B { a: 4 },
+                EnumUntagged::C { b: "d".into() },
+            ],
+        }
+    }
+}
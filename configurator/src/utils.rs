@@ -1,4 +1,10 @@
-use figment::{value::Dict, Figment, Profile, Provider};
+use figment::{
+    error::Actual,
+    value::{Dict, Num},
+    Figment, Profile, Provider,
+};
+
+use crate::node::from_json_schema::json_number_to_num;
 
 pub fn data_default_profile_figment(figment: &Figment) -> Option<Dict> {
     // todo: support profile ?
@@ -11,6 +17,53 @@ pub fn data_default_profile_figment(figment: &Figment) -> Option<Dict> {
     }
 }
 
+/// Booleans of untyped formats, like INI or systemd units
+pub fn bool_from_str(s: &str) -> Option<bool> {
+    match s.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Numbers are equal if they have the same value, whatever their type
+pub fn num_eq(a: &Num, b: &Num) -> bool {
+    match (a.to_actual(), b.to_actual()) {
+        (Actual::Unsigned(u), Actual::Signed(i)) | (Actual::Signed(i), Actual::Unsigned(u)) => {
+            i128::try_from(u) == Ok(i)
+        }
+        (Actual::Float(f), Actual::Unsigned(u)) | (Actual::Unsigned(u), Actual::Float(f)) => {
+            f == u as f64 && f as u128 == u
+        }
+        (Actual::Float(f), Actual::Signed(i)) | (Actual::Signed(i), Actual::Float(f)) => {
+            f == i as f64 && f as i128 == i
+        }
+        (a, b) => a == b,
+    }
+}
+
+/// Structural equality, the tags and the width of numbers are ignored
+pub fn figment_value_eq(a: &figment::value::Value, b: &figment::value::Value) -> bool {
+    use figment::value::Value;
+
+    match (a, b) {
+        (Value::Num(_, a), Value::Num(_, b)) => num_eq(a, b),
+        (Value::Dict(_, a), Value::Dict(_, b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(k, a)| b.get(k).is_some_and(|b| figment_value_eq(a, b)))
+        }
+        (Value::Array(_, a), Value::Array(_, b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| figment_value_eq(a, b))
+        }
+        (Value::String(_, a), Value::String(_, b)) => a == b,
+        (Value::Char(_, a), Value::Char(_, b)) => a == b,
+        (Value::Bool(_, a), Value::Bool(_, b)) => a == b,
+        (Value::Empty(..), Value::Empty(..)) => true,
+        _ => false,
+    }
+}
+
 pub fn json_values_eq_figment_value<'a, I>(json_values: I, fig: &figment::value::Value) -> bool
 where
     I: Iterator<Item = &'a json::Value>,
@@ -22,7 +75,7 @@ where
             (json::Value::Bool(j_bool), figment::value::Value::Bool(_, f_bool)) => j_bool == f_bool,
 
             (json::Value::Number(j_num), figment::value::Value::Num(_, f_num)) => {
-                j_num.as_i64() == f_num.to_u128_lossy().map(|e| e as i64)
+                num_eq(&json_number_to_num(j_num), f_num)
             }
 
             (json::Value::String(j_str), figment::value::Value::String(_, f_str)) => j_str == f_str,
@@ -54,7 +107,7 @@ pub fn json_value_eq_figment_value(json_value: &json::Value, fig: &figment::valu
         (json::Value::Bool(j_bool), figment::value::Value::Bool(_, f_bool)) => j_bool == f_bool,
 
         (json::Value::Number(j_num), figment::value::Value::Num(_, f_num)) => {
-            j_num.as_i64() == f_num.to_u128_lossy().map(|e| e as i64)
+            num_eq(&json_number_to_num(j_num), f_num)
         }
 
         (json::Value::String(j_str), figment::value::Value::String(_, f_str)) => j_str == f_str,
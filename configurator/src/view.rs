@@ -2,7 +2,7 @@ use std::borrow::Cow;
 
 use cosmic::{
     iced::{alignment, Alignment, Color, Length},
-    iced_widget::{pick_list, toggler},
+    iced_widget::{pick_list, slider, toggler},
     prelude::CollectionWidget,
     widget::{
         button, column, container, horizontal_space, mouse_area, row, scrollable,
@@ -20,8 +20,8 @@ use crate::{
     message::{AppMsg, ChangeMsg, PageMsg},
     node::{
         data_path::{DataPath, DataPathType},
-        Node, NodeArray, NodeBool, NodeContainer, NodeEnum, NodeNumber, NodeObject, NodeString,
-        NodeValue,
+        Node, NodeAny, NodeArray, NodeBool, NodeContainer, NodeEnum, NodeNumber, NodeObject,
+        NodeString, NodeValue,
     },
     page::Page,
 };
@@ -75,7 +75,16 @@ fn view_data_path(data_path: &DataPath) -> Element<'_, PageMsg> {
 fn view_page(entity: Entity, page: &Page) -> Element<'_, PageMsg> {
     let data_path = page.data_path.current();
 
-    let node = page.tree.get_at(data_path.iter()).unwrap();
+    let node = match page.tree.get_at(data_path) {
+        Ok(node) => node,
+        Err(e) => {
+            return column()
+                .push(view_data_path(&page.data_path))
+                .push(text(e.to_string()))
+                .spacing(10)
+                .into();
+        }
+    };
 
     let content = match &node.node {
         Node::Bool(node_bool) => view_bool(data_path, node, node_bool),
@@ -86,27 +95,96 @@ fn view_page(entity: Entity, page: &Page) -> Element<'_, PageMsg> {
         Node::Value(node_value) => view_value(data_path, node, node_value),
         Node::Null => text("null").into(),
         Node::Array(node_array) => view_array(data_path, node, node_array),
-        Node::Any => todo!(),
+        Node::Any(node_any) => view_any(data_path, node, node_any),
+        Node::Lazy(_) => text("not loaded").into(),
     };
 
     column()
         .push(view_data_path(&page.data_path))
+        .push_maybe(view_diagnostics(page))
+        .push_maybe(page.error.as_deref().map(text))
+        .push(view_origin(data_path, node))
         .push(scrollable(content))
         .spacing(10)
         .into()
 }
 
-fn no_value_defined_warning_icon<'a, M: 'a>() -> Element<'a, M> {
+/// Which layer provides the value, with a way to drop the user value
+fn view_origin<'a>(data_path: &[DataPathType], node: &NodeContainer) -> Element<'a, PageMsg> {
+    row()
+        .push(text(format!("Provided by: {}", node.origin)))
+        .push(horizontal_space())
+        .push_maybe(
+            if node.origin.is_user()
+                && let Some(DataPathType::Name(_)) = data_path.last()
+            {
+                Some(
+                    button::text("Reset to inherited").on_press(PageMsg::ChangeMsg(
+                        data_path.to_vec(),
+                        ChangeMsg::ResetToInherited,
+                    )),
+                )
+            } else {
+                None
+            },
+        )
+        .align_y(Alignment::Center)
+        .spacing(SPACING)
+        .into()
+}
+
+fn warning_icon<'a, M: 'a>(message: String) -> Element<'a, M> {
     tooltip(
         icon!("report24").class(cosmic::theme::Svg::custom(|e| cosmic::widget::svg::Style {
             color: Some(Color::from_rgb(236.0, 194.0, 58.0)),
         })),
-        text("You need to define some values that have no default!"),
+        text(message),
         Position::Top,
     )
     .into()
 }
 
+fn no_value_defined_warning_icon<'a, M: 'a>() -> Element<'a, M> {
+    warning_icon("You need to define some values that have no default!".into())
+}
+
+/// Show the most important problem of this node, if any
+fn diagnostics_badge<'a, M: 'a>(node: &NodeContainer) -> Option<Element<'a, M>> {
+    let diagnostics = node.diagnostics();
+
+    let first = diagnostics
+        .iter()
+        .find(|d| d.is_error())
+        .or(diagnostics.first())?;
+
+    let message = match diagnostics.len() {
+        1 => first.to_string(),
+        len => format!("{first} (and {} more)", len - 1),
+    };
+
+    Some(warning_icon(message))
+}
+
+/// Explain why the config is not written, with a shortcut to the first invalid node
+fn view_diagnostics(page: &Page) -> Option<Element<'_, PageMsg>> {
+    let first = page.first_error()?;
+
+    let errors = page.diagnostics.iter().filter(|d| d.is_error()).count();
+
+    Some(
+        row()
+            .push(icon!("report24"))
+            .push(text(format!(
+                "The configuration is not saved ({errors} errors). {first}"
+            )))
+            .push(horizontal_space())
+            .push(button::text("Show").on_press(PageMsg::GoTo(first.data_path.clone())))
+            .align_y(Alignment::Center)
+            .spacing(SPACING)
+            .into(),
+    )
+}
+
 fn this_will_remove_all_children<'a, M: 'a>() -> Element<'a, M> {
     tooltip(
         icon!("info24"),
@@ -120,6 +198,7 @@ fn node_list<'a>(
     name: DataPathType,
     inner_node: &'a NodeContainer,
     data_path: &'a [DataPathType],
+    can_remove: bool,
 ) -> Element<'a, PageMsg> {
     fn append_data_path(data_path: &[DataPathType], field: &DataPathType) -> Vec<DataPathType> {
         let mut new_vec = Vec::with_capacity(data_path.len() + 1);
@@ -147,6 +226,7 @@ fn node_list<'a>(
                 },
             )
             .push(horizontal_space())
+            .push(text::caption(inner_node.origin.to_string()))
             .push_maybe(match &inner_node.node {
                 Node::Null => Some(Element::from(text("null"))),
                 Node::Bool(node_bool) => Some(
@@ -217,16 +297,11 @@ fn node_list<'a>(
 
                 _ => None,
             })
-            .push_maybe(if !inner_node.is_valid() {
-                Some(no_value_defined_warning_icon())
-            } else {
-                None
-            })
+            .push_maybe(diagnostics_badge(inner_node))
             .push_maybe(if inner_node.removable {
-                Some(icon_button!("close24").on_press(PageMsg::ChangeMsg(
-                    data_path.to_vec(),
-                    ChangeMsg::Remove(name_cloned.clone()),
-                )))
+                Some(icon_button!("close24").on_press_maybe(can_remove.then(|| {
+                    PageMsg::ChangeMsg(data_path.to_vec(), ChangeMsg::Remove(name_cloned.clone()))
+                })))
             } else {
                 None
             }),
@@ -240,6 +315,9 @@ fn view_object<'a>(
     node: &'a NodeContainer,
     node_object: &'a NodeObject,
 ) -> Element<'a, PageMsg> {
+    // fields of inactive conditional subschemas
+    let hidden = node.hidden_keys();
+
     column()
         .push_maybe(
             node.desc
@@ -247,13 +325,22 @@ fn view_object<'a>(
                 .map(|desc| section().title("Description").add(text(desc))),
         )
         .push(
-            section()
-                .title("Values")
-                .extend(node_object.nodes.iter().map(|(name, inner_node)| {
-                    node_list(DataPathType::Name(name.clone()), inner_node, data_path)
-                })),
+            section().title("Values").extend(
+                node_object
+                    .nodes
+                    .iter()
+                    .filter(|(name, _)| !hidden.contains(name.as_str()))
+                    .map(|(name, inner_node)| {
+                        node_list(
+                            DataPathType::Name(name.clone()),
+                            inner_node,
+                            data_path,
+                            true,
+                        )
+                    }),
+            ),
         )
-        .push_maybe(node_object.template.as_ref().map(|_| {
+        .push_maybe(node_object.can_add().then(|| {
             icon_button!("add24").on_press(PageMsg::DialogAddNewNodeToObject(data_path.to_vec()))
         }))
         .push_maybe(node.default.as_ref().map(|default| {
@@ -294,14 +381,28 @@ fn view_array<'a>(
                     .iter()
                     .enumerate()
                     .map(|(pos, inner_node)| {
-                        node_list(DataPathType::Indice(pos), inner_node, data_path)
+                        node_list(
+                            DataPathType::Indice(pos),
+                            inner_node,
+                            data_path,
+                            node_array.can_remove(),
+                        )
                     }),
             ),
         )
-        .push(icon_button!("add24").on_press(PageMsg::ChangeMsg(
-            data_path.to_vec(),
-            ChangeMsg::AddNewNodeToArray,
-        )))
+        .push_maybe(
+            node_array
+                .check_constraints()
+                .err()
+                .map(|err| text(err.to_string())),
+        )
+        .push(
+            icon_button!("add24").on_press_maybe(
+                node_array
+                    .can_add()
+                    .then(|| PageMsg::ChangeMsg(data_path.to_vec(), ChangeMsg::AddNewNodeToArray)),
+            ),
+        )
         .push_maybe(node.default.as_ref().map(|default| {
             section().title("Default").add(
                 row()
@@ -354,13 +455,7 @@ fn view_enum<'a>(
                                     button::text("modify")
                                         .on_press(PageMsg::OpenDataPath(DataPathType::Indice(pos)))
                                 }))
-                                .push_maybe(is_active.and_then(|_| {
-                                    if !inner_node.is_valid() {
-                                        Some(no_value_defined_warning_icon())
-                                    } else {
-                                        None
-                                    }
-                                }))
+                                .push_maybe(is_active.and_then(|_| diagnostics_badge(inner_node)))
                                 .align_y(Alignment::Center)
                         },
                         pos,
@@ -479,10 +574,11 @@ fn view_string<'a>(
                             },
                         ),
                     )
-                    .push_maybe(if node_string.value.is_none() {
-                        Some(no_value_defined_warning_icon())
-                    } else {
-                        None
+                    .push_maybe(match &node_string.value {
+                        None => Some(no_value_defined_warning_icon()),
+                        Some(value) => node_string.validate(value).err().map(|err| {
+                            tooltip(icon!("report24"), text(err.to_string()), Position::Top).into()
+                        }),
                     }),
             ),
         )
@@ -512,6 +608,58 @@ fn view_string<'a>(
         .into()
 }
 
+fn view_any<'a>(
+    data_path: &'a [DataPathType],
+    node: &'a NodeContainer,
+    node_any: &'a NodeAny,
+) -> Element<'a, PageMsg> {
+    column()
+        .push_maybe(
+            node.desc
+                .as_ref()
+                .map(|desc| section().title("Description").add(text(desc))),
+        )
+        .push(
+            section()
+                .title("Value")
+                .add(
+                    row()
+                        .push(text("JSON or RON"))
+                        .push(horizontal_space())
+                        .push(
+                            text_input("value", &node_any.value_string).on_input(move |value| {
+                                PageMsg::ChangeMsg(data_path.to_vec(), ChangeMsg::ChangeAny(value))
+                            }),
+                        )
+                        .push_maybe(node_any.value.is_none().then(no_value_defined_warning_icon)),
+                )
+                .add_maybe(
+                    NodeAny::parse(&node_any.value_string)
+                        .err()
+                        .filter(|_| !node_any.value_string.is_empty())
+                        .map(|err| text(err.to_string())),
+                ),
+        )
+        .push_maybe(node.default.as_ref().map(|default| {
+            section()
+                .title("Default")
+                .add(
+                    row()
+                        .push(text("Default value"))
+                        .push(horizontal_space())
+                        .push(text(NodeAny::format(default))),
+                )
+                .add(row().push(horizontal_space()).push(
+                    button::text("reset to default").on_press(PageMsg::ChangeMsg(
+                        data_path.to_vec(),
+                        ChangeMsg::ApplyDefault,
+                    )),
+                ))
+        }))
+        .spacing(SPACING)
+        .into()
+}
+
 fn view_number<'a>(
     data_path: &'a [DataPathType],
     node: &'a NodeContainer,
@@ -524,33 +672,67 @@ fn view_number<'a>(
                 .map(|desc| section().title("Description").add(text(desc))),
         )
         .push(
-            section().title("Value").add(
-                row()
-                    .push(text("Current value"))
-                    .push(horizontal_space())
-                    .push(
-                        text_input("value", &node_number.value_string).on_input(move |value| {
-                            PageMsg::ChangeMsg(data_path.to_vec(), ChangeMsg::ChangeNumber(value))
+            section()
+                .title("Value")
+                .add(
+                    row()
+                        .push(text("Current value"))
+                        .push(horizontal_space())
+                        .push(text_input("value", &node_number.value_string).on_input(
+                            move |value| {
+                                PageMsg::ChangeMsg(
+                                    data_path.to_vec(),
+                                    ChangeMsg::ChangeNumber(value),
+                                )
+                            },
+                        ))
+                        .push_maybe(if node_number.value.is_none() {
+                            Some(no_value_defined_warning_icon())
+                        } else if let Err(err) =
+                            node_number.try_parse_from_str(&node_number.value_string)
+                        {
+                            Some(
+                                tooltip(icon!("report24"), text(err.to_string()), Position::Top)
+                                    .into(),
+                            )
+                        } else {
+                            None
                         }),
-                    )
-                    .push_maybe(if node_number.value.is_none() {
-                        Some(no_value_defined_warning_icon())
-                    } else if node_number
-                        .try_parse_from_str(&node_number.value_string)
-                        .is_err()
-                    {
-                        Some(
-                            tooltip(
-                                icon!("report24"),
-                                text("This value is incorrect."),
-                                Position::Top,
+                )
+                .add_maybe(node_number.range().map(|(min, max)| {
+                    let is_integer = node_number.is_integer();
+
+                    let step = match node_number.multiple_of {
+                        Some(multiple_of) => multiple_of,
+                        None if is_integer => 1.,
+                        None => (max - min) / 100.,
+                    };
+
+                    row()
+                        .push(text(min.to_string()))
+                        .push(
+                            slider(
+                                min..=max,
+                                node_number.value.as_ref().map_or(min, |v| v.to_f64()),
+                                move |value| {
+                                    let value = if is_integer {
+                                        (value.round() as i128).to_string()
+                                    } else {
+                                        value.to_string()
+                                    };
+
+                                    PageMsg::ChangeMsg(
+                                        data_path.to_vec(),
+                                        ChangeMsg::ChangeNumber(value),
+                                    )
+                                },
                             )
-                            .into(),
+                            .step(step),
                         )
-                    } else {
-                        None
-                    }),
-            ),
+                        .push(text(max.to_string()))
+                        .spacing(SPACING)
+                        .align_y(Alignment::Center)
+                })),
         )
         .push_maybe(
             node.default
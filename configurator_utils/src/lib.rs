@@ -6,6 +6,10 @@ use anyhow::anyhow;
 pub enum ConfigFormat {
     Json,
     CosmicRon,
+    Toml,
+    Yaml,
+    Kdl,
+    Ini,
 }
 
 impl Display for ConfigFormat {
@@ -13,6 +17,10 @@ impl Display for ConfigFormat {
         match self {
             ConfigFormat::Json => write!(f, "json"),
             ConfigFormat::CosmicRon => write!(f, "cosmic_ron"),
+            ConfigFormat::Toml => write!(f, "toml"),
+            ConfigFormat::Yaml => write!(f, "yaml"),
+            ConfigFormat::Kdl => write!(f, "kdl"),
+            ConfigFormat::Ini => write!(f, "ini"),
         }
     }
 }
@@ -24,6 +32,10 @@ impl TryFrom<&str> for ConfigFormat {
         let format = match value {
             "json" => ConfigFormat::Json,
             "cosmic_ron" => ConfigFormat::CosmicRon,
+            "toml" => ConfigFormat::Toml,
+            "yaml" | "yml" => ConfigFormat::Yaml,
+            "kdl" => ConfigFormat::Kdl,
+            "ini" | "conf" | "desktop" => ConfigFormat::Ini,
             _ => Err(anyhow!("unknown format: {}", value))?,
         };
         Ok(format)